@@ -0,0 +1,138 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use std::io::BufRead;
+
+use timely::dataflow::ProbeHandle;
+use timely::dataflow::operators::unordered_input::UnorderedInput;
+use timely::dataflow::operators::Probe;
+use timely::progress::frontier::AntichainRef;
+use timely::PartialOrder;
+
+use differential_dataflow::AsCollection;
+use differential_dataflow::lattice::MultiProduct;
+use differential_dataflow::operators::{Count, arrange::ArrangeBySelf};
+use differential_dataflow::trace::{Cursor, TraceReader};
+
+/// A time with three coordinates: round, iteration, and scenario.
+type Time = MultiProduct<(isize, isize, isize)>;
+
+fn main() {
+
+    timely::execute_from_args(std::env::args(), move |worker| {
+
+        // Used to determine if our output has caught up to our input.
+        let mut probe: ProbeHandle<Time> = ProbeHandle::new();
+
+        let (mut input, mut capability, mut trace) =
+        worker.dataflow(|scope| {
+
+            let ((input, capability), data) = scope.new_unordered_input();
+
+            let arrangement =
+            data.as_collection()
+                .count()
+                .map(|(_value, count)| count)
+                .arrange_by_self();
+
+            arrangement.stream.probe_with(&mut probe);
+
+            (input, capability, arrangement.trace)
+        });
+
+        // Do not hold back physical compaction.
+        trace.set_physical_compaction(AntichainRef::new(&[]));
+
+        println!("Three-dimensional histogram; valid commands are (integer arguments):");
+        println!("  update   value time1 time2 time3 change");
+        println!("  advance-input  time1 time2 time3");
+        println!("  advance-output time1 time2 time3");
+        println!("  query          time1 time2 time3");
+
+        let std_input = std::io::stdin();
+        for line in std_input.lock().lines().map(|x| x.unwrap()) {
+            let mut elts = line[..].split_whitespace();
+            if let Some(command) = elts.next() {
+                if let Ok(arguments) = read_integers(elts) {
+                    match (command, arguments.len()) {
+                        ("update", 5) => {
+                            let time = MultiProduct::new((arguments[1], arguments[2], arguments[3]));
+                            if capability.time().less_equal(&time) {
+                                input
+                                    .session(capability.clone())
+                                    .give((arguments[0], time, arguments[4]));
+                            } else {
+                                println!("Requested time {:?} no longer open (input from {:?})", time, capability.time());
+                            }
+                        },
+                        ("advance-input", 3) => {
+                            let time = MultiProduct::new((arguments[0], arguments[1], arguments[2]));
+                            if capability.time().less_equal(&time) {
+                                capability.downgrade(&time);
+                                while probe.less_than(capability.time()) {
+                                    worker.step();
+                                }
+                            } else {
+                                println!("Requested time {:?} no longer open (input from {:?})", time, capability.time());
+                            }
+                        },
+                        ("advance-output", 3) => {
+                            let time = MultiProduct::new((arguments[0], arguments[1], arguments[2]));
+                            if trace.get_logical_compaction().less_equal(&time) {
+                                trace.set_logical_compaction(AntichainRef::new(&[time]));
+                                while probe.less_than(capability.time()) {
+                                    worker.step();
+                                }
+                            } else {
+                                println!("Requested time {:?} not readable (output from {:?})", time, trace.get_logical_compaction());
+                            }
+                        },
+                        ("query", 3) => {
+                            // Check that the query times are not beyond the current capabilities.
+                            let query_time = MultiProduct::new((arguments[0], arguments[1], arguments[2]));
+                            if capability.time().less_equal(&query_time) {
+                                println!("Query time ({:?}) is still open (input from {:?}).", query_time, capability.time());
+                            } else if !trace.get_logical_compaction().less_equal(&query_time) {
+                                println!("Query time ({:?}) no longer available in output (output from {:?}).", query_time, trace.get_logical_compaction());
+                            }
+                            else {
+                                println!("Report at {:?}", query_time);
+                                // enumerate the contents of `trace` at `query_time`.
+                                let (mut cursor, storage) = trace.cursor();
+                                while let Some(key) = cursor.get_key(&storage) {
+                                    while let Some(_val) = cursor.get_val(&storage) {
+                                        let mut sum = 0;
+                                        cursor.map_times(&storage,
+                                            |time, diff| if time.less_equal(&query_time) { sum += diff; }
+                                        );
+                                        cursor.step_val(&storage);
+                                        if sum != 0 {
+                                            println!("    values with occurrence count {:?}: {:?}", key, sum);
+                                        }
+                                    }
+                                    cursor.step_key(&storage);
+                                }
+                                println!("Report complete");
+                            }
+                        },
+                        _ => {
+                            println!("Command not recognized: {:?} with {} arguments.", command, arguments.len());
+                        }
+                    }
+                }
+                else {
+                    println!("Error parsing command arguments");
+                }
+            }
+        }
+    }).unwrap();
+}
+
+/// Read a command and its arguments.
+fn read_integers<'a>(input: impl Iterator<Item=&'a str>) -> Result<Vec<isize>, std::num::ParseIntError> {
+    let mut integers = Vec::new();
+    for text in input {
+        integers.push(text.parse()?);
+    }
+    Ok(integers)
+}
@@ -0,0 +1,34 @@
+extern crate interactive;
+
+use std::io::Read;
+use std::time::Duration;
+
+use interactive::Command;
+use interactive::concrete::Session;
+use interactive::datalog;
+
+fn main() {
+
+    let mut args = std::env::args().skip(1);
+    let filename = args.next().expect("must specify a program file");
+    let address = args.next().unwrap_or("127.0.0.1:8000".to_string());
+
+    let mut text = String::new();
+    std::fs::File::open(&filename)
+        .expect("failed to open program")
+        .read_to_string(&mut text)
+        .expect("failed to read program");
+
+    match datalog::compile(&text) {
+        Ok(program) => {
+            let socket = std::net::TcpStream::connect(address).expect("failed to connect");
+            let mut session = Session::new(socket);
+            for command in program.commands().into_iter() {
+                session.issue(command);
+            }
+            // Advance time so that facts are visible to the installed rules.
+            session.issue(Command::AdvanceTime(Duration::from_secs(1)));
+        },
+        Err(error) => eprintln!("error: {}", error),
+    }
+}
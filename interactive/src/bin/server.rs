@@ -68,6 +68,13 @@ fn main() {
                     .map(|s| s.push(command));
             }
 
+            // Retire subscriptions whose clients have disconnected.
+            for index in manager.disconnected.borrow_mut().drain(..) {
+                sequencer
+                    .as_mut()
+                    .map(|s| s.push(Command::DropSubscription(index)));
+            }
+
             // Dequeue and act on commands.
             // Once per iteration, so that Shutdown works "immediately".
             if let Some(command) = sequencer.as_mut().and_then(|s| s.next()) {
@@ -81,6 +88,9 @@ fn main() {
                 // Only consider parking if the sequencer is empty too.
                 worker.step_or_park(None);
             }
+
+            // Answer peeks whose traces have since become complete.
+            manager.answer_peeks();
         }
 
         println!("Shutting down");
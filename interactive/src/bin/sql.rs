@@ -0,0 +1,50 @@
+extern crate interactive;
+
+use std::io::BufRead;
+use std::time::Duration;
+
+use interactive::Command;
+use interactive::concrete::Session;
+use interactive::sql::{self, Catalog};
+
+fn main() {
+
+    let address = std::env::args().nth(1).unwrap_or("127.0.0.1:8000".to_string());
+    let socket = std::net::TcpStream::connect(address).expect("failed to connect");
+    let mut session = Session::new(socket);
+
+    let mut catalog = Catalog::new();
+    let mut time = Duration::from_secs(0);
+
+    // Statements are accumulated until a line ends with a semicolon.
+    let mut buffer = String::new();
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.expect("failed to read stdin");
+        buffer.push_str(&line);
+        buffer.push('\n');
+        if !line.trim_end().ends_with(';') {
+            continue;
+        }
+
+        match sql::parse(&buffer) {
+            Ok(statements) => {
+                for statement in statements.into_iter() {
+                    match catalog.execute(statement, time) {
+                        Ok(commands) => {
+                            for command in commands.into_iter() {
+                                session.issue(command);
+                            }
+                            // Advance time so that each statement's effects are visible.
+                            time += Duration::from_secs(1);
+                            session.issue(Command::AdvanceTime(time));
+                        },
+                        Err(error) => eprintln!("error: {}", error),
+                    }
+                }
+            },
+            Err(error) => eprintln!("error: {}", error),
+        }
+        buffer.clear();
+    }
+}
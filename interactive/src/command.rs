@@ -10,6 +10,7 @@ use timely::logging::TimelyEvent;
 use differential_dataflow::logging::DifferentialEvent;
 
 use differential_dataflow::ExchangeData;
+use differential_dataflow::trace::{Cursor, TraceReader};
 
 use super::{Query, Rule, Plan, Time, Diff, Manager, Datum};
 use crate::logging::LoggingValue;
@@ -17,7 +18,8 @@ use crate::logging::LoggingValue;
 /// Commands accepted by the system.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Command<V: Datum> {
-    /// Installs the query and publishes public rules.
+    /// Installs the query and publishes public rules. Queries with rules that fail
+    /// `Plan::validate` are reported and not installed.
     Query(Query<V>),
     /// Advances all inputs and traces to `time`, and advances computation.
     AdvanceTime(Time),
@@ -27,12 +29,44 @@ pub enum Command<V: Datum> {
     UpdateInput(String, Vec<(Vec<V>, Time, Diff)>),
     /// Closes a specified input.
     CloseInput(String),
+    /// Uninstalls the query that installed a named rule.
+    DropQuery(String),
+    /// Removes a specified input, and uninstalls all queries that depend on it.
+    DropInput(String),
+    /// Streams the updates of a published rule to a client listening at an address. (name, address)
+    Subscribe(String, String),
+    /// Retires the subscription dataflow at an index, once its client has disconnected.
+    DropSubscription(usize),
+    /// Reads the records of a published rule whose values at some columns equal some values,
+    /// as of a time, and sends them to a client listening at an address once the rule is
+    /// complete through the time. The time must be at least that of the most recent
+    /// `AdvanceTime`, as traces are compacted through it.
+    /// (name, key columns, key values, time, address)
+    Peek(String, Vec<usize>, Vec<V>, Time, String),
+    /// Describes how the query would be rendered, without rendering it, and sends the
+    /// description to a client listening at an address. (query, address)
+    Explain(Query<V>, String),
     /// Attaches a logging source. (address, flavor, number, granularity, name_as)
     SourceLogging(String, String, usize, u64, String),
     /// Terminates the system.
     Shutdown,
 }
 
+/// Responses sent to clients.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Response<V: Datum> {
+    /// Consolidated updates to a collection.
+    Updates(Vec<(Vec<V>, Time, Diff)>),
+    /// The frontier of times at which further updates may occur.
+    Progress(Vec<Time>),
+    /// Accumulated records from one of a number of workers.
+    Peek(Vec<(Vec<V>, Diff)>, usize),
+    /// A description of the physical plan for a query.
+    Explain(String),
+    /// A command could not be performed.
+    Error(String),
+}
+
 impl<V: Datum> From<Query<V>> for Command<V> {
     fn from(query: Query<V>) -> Self { Command::Query(query) }
 }
@@ -66,6 +100,19 @@ where
                 // traces, and the types present in imported traces are not
                 // the same as those in arrangements.
 
+                // Rules that refer to themselves or to each other are rendered
+                // as the fixed point of the group of rules they belong to.
+                let rules = query.recursive_rules();
+                if let Err(error) = rules.iter().try_for_each(|rule| rule.plan.validate()) {
+                    println!("Query not installed: {}", error);
+                    return;
+                }
+                let names = rules.iter().map(|rule| rule.name.clone()).collect();
+
+                // Track the arrangements the dataflow installs and uses, to retire it later.
+                let index = worker.next_dataflow_index();
+                let mut probe = manager.traces.begin_dataflow(index);
+
                 worker.dataflow(|scope| {
 
                     use timely::dataflow::operators::Probe;
@@ -75,12 +122,10 @@ where
                     let mut collections = std::collections::HashMap::new();
                     // let mut arrangements = std::collections::HashMap::new();
 
-                    for Rule { name, plan } in query.rules.into_iter() {
-                        let collection =
-                        plan.render(scope, &mut collections, &mut manager.traces)
-                            .arrange_by_self();
+                    for Rule { name, plan } in rules.into_iter() {
+                        let collection = plan.render(scope, &mut collections, &mut manager.traces).arrange_by_self();
 
-                        collection.stream.probe_with(&mut manager.probe);
+                        collection.stream.probe_with(&mut probe);
                         let trace = collection.trace;
 
                         // Can bind the trace to both the plan and the name.
@@ -89,13 +134,16 @@ where
                     }
 
                 });
+
+                manager.install_dataflow(index, names, probe);
             },
 
             Command::AdvanceTime(time) => {
                 manager.advance_time(&time);
-                while manager.probe.less_than(&time) {
+                while manager.less_than(&time) {
                     worker.step();
                 }
+                manager.answer_peeks();
             },
 
             Command::CreateInput(name, updates) => {
@@ -103,13 +151,20 @@ where
                 use differential_dataflow::input::Input;
                 use differential_dataflow::operators::arrange::ArrangeBySelf;
 
+                use timely::dataflow::operators::Probe;
+
+                let index = worker.next_dataflow_index();
+                let mut probe = manager.traces.begin_dataflow(index);
+
                 let (input, trace) = worker.dataflow(|scope| {
                     let (input, collection) = scope.new_collection_from(updates.into_iter());
-                    let trace = collection.arrange_by_self().trace;
-                    (input, trace)
+                    let arrangement = collection.arrange_by_self();
+                    arrangement.stream.probe_with(&mut probe);
+                    (input, arrangement.trace)
                 });
 
-                manager.insert_input(name, input, trace);
+                manager.insert_input(name.clone(), input, trace);
+                manager.install_input(index, name, probe);
 
             },
 
@@ -128,6 +183,210 @@ where
                 manager.inputs.sessions.remove(&name);
             },
 
+            Command::DropQuery(name) => {
+                manager.drop_query(&name, worker);
+            },
+
+            Command::DropInput(name) => {
+                manager.drop_input(&name, worker);
+            },
+
+            Command::DropSubscription(index) => {
+                manager.drop_subscription(index, worker);
+            },
+
+            Command::Subscribe(name, address) => {
+
+                use std::net::TcpStream;
+                use timely::dataflow::Stream;
+                use timely::dataflow::channels::pact::Exchange;
+                use timely::dataflow::operators::{Operator, Probe};
+                use timely::progress::frontier::Antichain;
+                use differential_dataflow::consolidation::consolidate_updates;
+
+                // Only the first worker connects, and all updates are routed to it.
+                let connect = || TcpStream::connect(&address).map_err(|error| println!("Failed to connect to {:?}: {}", address, error)).ok();
+
+                let index = worker.next_dataflow_index();
+                let mut probe = manager.traces.begin_dataflow(index);
+
+                if let Some(mut trace) = manager.traces.get_unkeyed(&Plan::Source(name.clone())) {
+
+                    // Writes must not block the worker, and are buffered until the client accepts them.
+                    let mut socket =
+                    if worker.index() == 0 {
+                        connect().and_then(|stream| {
+                            stream.set_nonblocking(true).map_err(|error| println!("Failed to configure {:?}: {}", address, error)).ok()?;
+                            Some(stream)
+                        })
+                    }
+                    else { None };
+
+                    let disconnected = manager.disconnected.clone();
+
+                    worker.dataflow(|scope| {
+
+                        // Updates at times the input frontier has not yet passed.
+                        let mut pending = Vec::new();
+                        // The frontier through which updates have been written.
+                        let mut written = Antichain::from_elem(Time::default());
+                        // Serialized responses the client has not yet accepted.
+                        let mut buffer = Vec::new();
+
+                        // The imported trace first produces a snapshot, and then changes.
+                        let updates =
+                        trace
+                            .import(scope)
+                            .as_collection(|k,&()| k.clone())
+                            .inner;
+
+                        let _: Stream<_, ()> =
+                        updates
+                            .unary_frontier(Exchange::new(|_: &(Vec<V>, Time, Diff)| 0), "Subscribe", |_capability, info| {
+
+                                let activator = scope.activator_for(&info.address[..]);
+
+                                move |input, _output| {
+
+                                    input.for_each(|_time, data| {
+                                        if socket.is_some() {
+                                            pending.extend(data.iter().cloned());
+                                        }
+                                    });
+
+                                    if let Some(stream) = socket.as_mut() {
+
+                                        let frontier = input.frontier().frontier();
+                                        if written.elements() != &frontier[..] {
+
+                                            consolidate_updates(&mut pending);
+                                            let (ready, retained): (Vec<_>, Vec<_>) =
+                                            pending
+                                                .drain(..)
+                                                .partition(|(_, time, _)| !frontier.less_equal(time));
+                                            pending = retained;
+
+                                            if !ready.is_empty() {
+                                                bincode::serialize_into(&mut buffer, &Response::Updates(ready)).expect("bincode: serialization failed");
+                                            }
+                                            bincode::serialize_into(&mut buffer, &Response::<V>::Progress(frontier.to_vec())).expect("bincode: serialization failed");
+                                            written = frontier.to_owned();
+                                        }
+
+                                        match write_nonblocking(stream, &mut buffer) {
+                                            // Retry once the client may accept more.
+                                            Ok(()) => if !buffer.is_empty() { activator.activate(); },
+                                            // Stop writing if the client disconnects, and have all workers retire the dataflow.
+                                            Err(error) => {
+                                                println!("Subscription to {:?} failed: {}", name, error);
+                                                disconnected.borrow_mut().push(index);
+                                                pending = Vec::new();
+                                                buffer = Vec::new();
+                                                socket = None;
+                                            },
+                                        }
+                                    }
+                                }
+                            });
+
+                        updates.probe_with(&mut probe);
+                    });
+
+                    manager.install_dataflow(index, Vec::new(), probe);
+                }
+                else {
+                    manager.traces.end_dataflow();
+                    if worker.index() == 0 {
+                        if let Some(mut stream) = connect() {
+                            let response = Response::<V>::Error(format!("Rule not found: {:?}", name));
+                            bincode::serialize_into(&mut stream, &response).ok();
+                        }
+                    }
+                }
+            },
+
+            Command::Peek(name, keys, values, time, address) => {
+
+                // Peeks read directly from the traces of the manager, which all workers hold
+                // a part of; each worker replies with its part, so that no dataflow is needed.
+                // A worker whose part is not yet complete through `time` parks the peek, and
+                // answers it once the part is complete; see `Manager::answer_peeks`.
+                let peers = worker.peers();
+                let respond = move |result: Result<Vec<(Vec<V>, Diff)>, String>| {
+                    let response =
+                    match result {
+                        Ok(records) => Response::Peek(records, peers),
+                        Err(error) => Response::Error(error),
+                    };
+                    match std::net::TcpStream::connect(&address) {
+                        Ok(mut stream) => { bincode::serialize_into(&mut stream, &response).ok(); },
+                        Err(error) => println!("Failed to connect to {:?}: {}", address, error),
+                    }
+                };
+
+                let plan = Plan::Source(name.clone());
+                let mut attempt: Box<dyn FnMut()->bool> =
+                if let Some(mut trace) = manager.traces.get_keyed(&plan, &keys[..]) {
+                    Box::new(move || {
+                        // Records are reassembled from their key values and remaining values.
+                        let result = peek(&mut trace, &time, Some(&values), |key, val| {
+                            let mut val = val.iter();
+                            let record =
+                            (0 .. key.len() + val.len())
+                                .map(|index| match keys.iter().position(|k| *k == index) {
+                                    Some(position) => key[position].clone(),
+                                    None => val.next().expect("Insufficient values").clone(),
+                                })
+                                .collect();
+                            Some(record)
+                        });
+                        result.map(&respond).is_some()
+                    })
+                }
+                else if let Some(mut trace) = manager.traces.get_unkeyed(&plan) {
+                    Box::new(move || {
+                        // Without an arrangement by the key columns, all records are scanned.
+                        let result = peek(&mut trace, &time, None, |key, &()| {
+                            if keys.iter().zip(values.iter()).all(|(index, value)| &key[*index] == value) {
+                                Some(key.clone())
+                            }
+                            else {
+                                None
+                            }
+                        });
+                        result.map(&respond).is_some()
+                    })
+                }
+                else {
+                    respond(Err(format!("Rule not found: {:?}", name)));
+                    Box::new(|| true)
+                };
+
+                if !attempt() {
+                    manager.peeks.push(attempt);
+                }
+            },
+
+            Command::Explain(query, address) => {
+
+                // All workers would describe the same plan, so only the first replies.
+                if worker.index() == 0 {
+                    let response =
+                    match query.recursive_rules().iter().try_for_each(|rule| rule.plan.validate()) {
+                        Ok(()) => {
+                            let mut explainer = crate::plan::Explainer::new(&manager.traces);
+                            explainer.query(&query);
+                            Response::<V>::Explain(explainer.finish())
+                        },
+                        Err(error) => Response::Error(error),
+                    };
+                    match std::net::TcpStream::connect(&address) {
+                        Ok(mut stream) => { bincode::serialize_into(&mut stream, &response).ok(); },
+                        Err(error) => println!("Failed to connect to {:?}: {}", address, error),
+                    }
+                }
+            },
+
             Command::SourceLogging(address, flavor, number, granularity, name_as) => {
 
                 match flavor.as_str() {
@@ -194,4 +453,83 @@ where
     pub fn serialize_into<W: Write>(&self, writer: W) {
         bincode::serialize_into(writer, self).expect("bincode: serialization failed");
     }
+}
+
+/// Writes as much of `buffer` as `writer` accepts without blocking, and removes what was written.
+fn write_nonblocking<W: Write>(writer: &mut W, buffer: &mut Vec<u8>) -> std::io::Result<()> {
+    use std::io::ErrorKind;
+    let mut written = 0;
+    let result = loop {
+        if written == buffer.len() {
+            break Ok(());
+        }
+        match writer.write(&buffer[written ..]) {
+            Ok(0) => break Err(ErrorKind::WriteZero.into()),
+            Ok(count) => written += count,
+            Err(ref error) if error.kind() == ErrorKind::Interrupted => { },
+            Err(ref error) if error.kind() == ErrorKind::WouldBlock => break Ok(()),
+            Err(error) => break Err(error),
+        }
+    };
+    buffer.drain(.. written);
+    result
+}
+
+/// Accumulates records of `trace` as of `time`, once the trace is complete through `time`.
+///
+/// If `key` is supplied only records with that key are visited, and otherwise all are.
+/// Records are formed by `logic`, which may also decline to produce them. Returns `None` if
+/// the trace may still change at `time`, having first advanced the compaction frontiers of
+/// `trace` to `time`, so that the handle holds back compaction only as far as it must to be
+/// read later. Times the trace has already compacted away produce errors.
+fn peek<Tr, V, F>(
+    trace: &mut Tr,
+    time: &Time,
+    key: Option<&Tr::Key>,
+    mut logic: F,
+) -> Option<Result<Vec<(Vec<V>, Diff)>, String>>
+where
+    Tr: TraceReader<Time=Time, R=Diff>,
+    Tr::Key: Eq,
+    F: FnMut(&Tr::Key, &Tr::Val) -> Option<Vec<V>>,
+{
+    use timely::progress::frontier::Antichain;
+
+    if !trace.get_logical_compaction().less_equal(time) {
+        return Some(Err(format!("Time {:?} is not beyond the compaction frontier {:?}", time, trace.get_logical_compaction().to_vec())));
+    }
+    let frontier = Antichain::from_elem(time.clone());
+    trace.set_logical_compaction(frontier.borrow());
+    trace.set_physical_compaction(frontier.borrow());
+
+    let mut upper = Antichain::new();
+    trace.read_upper(&mut upper);
+    if upper.less_equal(time) {
+        return None;
+    }
+
+    let (mut cursor, storage) = trace.cursor();
+    if let Some(key) = key {
+        cursor.seek_key(&storage, key);
+    }
+
+    let mut results = Vec::new();
+    while let Some(current) = cursor.get_key(&storage) {
+        if key.map(|key| key != current).unwrap_or(false) {
+            break;
+        }
+        while let Some(val) = cursor.get_val(&storage) {
+            let mut count = 0;
+            cursor.map_times(&storage, |t, r| if t.less_equal(time) { count += r; });
+            if count != 0 {
+                if let Some(record) = logic(current, val) {
+                    results.push((record, count));
+                }
+            }
+            cursor.step_val(&storage);
+        }
+        cursor.step_key(&storage);
+    }
+
+    Some(Ok(results))
 }
\ No newline at end of file
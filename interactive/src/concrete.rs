@@ -1,7 +1,11 @@
 //! An example value type.
 
-use std::time::Duration;
-use super::{Datum, VectorFrom, Command};
+use std::convert::TryFrom;
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+use super::{Datum, VectorFrom, Command, Response, Diff, Query};
+use plan::Predicate;
+use plan::filter::SecondArgument;
 
 /// A session.
 pub struct Session<W: std::io::Write> {
@@ -17,6 +21,96 @@ impl<W: std::io::Write> Session<W> {
         bincode::serialize_into(&mut self.write, &command)
             .expect("bincode: serialization failed");
     }
+    /// Subscribe to a published rule, receiving responses at `address`.
+    ///
+    /// The server connects to `address`, and sends a snapshot of the rule's
+    /// contents followed by changes, each followed by the frontier they reach.
+    /// An error is returned if the server does not connect within `CONNECT_TIMEOUT`.
+    pub fn subscribe(&mut self, name: &str, address: &str) -> Result<Responses<TcpStream>, String> {
+        let listener = TcpListener::bind(address).expect("failed to bind listener");
+        self.issue(Command::Subscribe(name.to_string(), address.to_string()));
+        accept_within(&listener, CONNECT_TIMEOUT).map(Responses::new)
+    }
+    /// Peek at the records of a published rule as of `time`, receiving responses at `address`.
+    ///
+    /// Only records whose values at `keys` equal `values` are returned. Each server worker
+    /// connects to `address` and reports the records it holds, once they are complete
+    /// through `time`.
+    pub fn peek(&mut self, name: &str, keys: Vec<usize>, values: Vec<Value>, time: Duration, address: &str) -> Result<Vec<(Vec<Value>, Diff)>, String> {
+        let listener = TcpListener::bind(address).expect("failed to bind listener");
+        self.issue(Command::Peek(name.to_string(), keys, values, time, address.to_string()));
+        let mut results = Vec::new();
+        let mut pending = None;
+        while pending != Some(0) {
+            let (socket, _) = listener.accept().expect("failed to accept connection");
+            match Responses::new(socket).next() {
+                Some(Response::Peek(records, peers)) => {
+                    results.extend(records);
+                    pending = Some(pending.unwrap_or(peers) - 1);
+                },
+                Some(Response::Error(error)) => { return Err(error); },
+                other => { return Err(format!("Unexpected response: {:?}", other)); },
+            }
+        }
+        Ok(results)
+    }
+    /// Describe how a query would be rendered, receiving the description at `address`.
+    ///
+    /// The query is not installed. The description reports the operators rendering would
+    /// construct, which arrangements would be reused or built, and how data would be exchanged.
+    pub fn explain(&mut self, query: Query<Value>, address: &str) -> Result<String, String> {
+        let listener = TcpListener::bind(address).expect("failed to bind listener");
+        self.issue(Command::Explain(query, address.to_string()));
+        let socket = accept_within(&listener, CONNECT_TIMEOUT)?;
+        match Responses::new(socket).next() {
+            Some(Response::Explain(text)) => Ok(text),
+            Some(Response::Error(error)) => Err(error),
+            other => Err(format!("Unexpected response: {:?}", other)),
+        }
+    }
+}
+
+/// How long a session waits for the server to connect, for commands answered immediately.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Accepts a connection at `listener`, waiting at most `timeout`.
+fn accept_within(listener: &TcpListener, timeout: Duration) -> Result<TcpStream, String> {
+    use std::io::ErrorKind;
+    listener.set_nonblocking(true).map_err(|error| format!("Failed to configure listener: {}", error))?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        match listener.accept() {
+            Ok((socket, _)) => {
+                // Some platforms pass the listener's configuration on to accepted sockets.
+                socket.set_nonblocking(false).map_err(|error| format!("Failed to configure connection: {}", error))?;
+                return Ok(socket);
+            },
+            Err(ref error) if error.kind() == ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(format!("Server did not connect within {:?}", timeout));
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            },
+            Err(error) => { return Err(format!("Failed to accept connection: {}", error)); },
+        }
+    }
+}
+
+/// Responses read from a server.
+pub struct Responses<R: std::io::Read> {
+    read: R,
+}
+
+impl<R: std::io::Read> Responses<R> {
+    /// Create a new reader of responses.
+    pub fn new(read: R) -> Self { Self { read } }
+}
+
+impl<R: std::io::Read> Iterator for Responses<R> {
+    type Item = Response<Value>;
+    fn next(&mut self) -> Option<Self::Item> {
+        bincode::deserialize_from(&mut self.read).ok()
+    }
 }
 
 /// An example value type
@@ -32,12 +126,375 @@ pub enum Value {
     Vector(Vec<Value>),
     /// duration
     Duration(Duration),
+    /// error, from evaluating an expression
+    Error(String),
 }
 
 impl Datum for Value {
-    type Expression = usize;
-    fn subject_to(data: &[Self], expr: &Self::Expression) -> Self { data[*expr].clone() }
-    fn projection(index: usize) -> Self::Expression { index }
+    type Expression = Expression;
+    fn subject_to(data: &[Self], expr: &Self::Expression) -> Self { expr.evaluate(data) }
+    fn satisfies(data: &[Self], expr: &Self::Expression) -> bool { expr.evaluate(data) == Value::Bool(true) }
+    fn projection(index: usize) -> Self::Expression { Expression::Column(index) }
+    fn count(count: Diff) -> Self {
+        usize::try_from(count)
+            .map(Value::Usize)
+            .unwrap_or_else(|_| Value::Error(format!("Cannot count negative multiplicity: {}", count)))
+    }
+    fn sum<'a, I: Iterator<Item=(&'a Self, Diff)>>(values: I) -> Self where Self: 'a {
+        // Sums are of integers, unless durations are presented.
+        let mut integers: usize = 0;
+        let mut durations = None;
+        for (value, count) in values {
+            let count = match usize::try_from(count) {
+                Ok(count) => count,
+                Err(_) => { return Value::Error(format!("Cannot sum negative multiplicity: {}", count)); },
+            };
+            match value {
+                Value::Usize(x) => {
+                    match x.checked_mul(count).and_then(|product| integers.checked_add(product)) {
+                        Some(total) => { integers = total; },
+                        None => { return Value::Error("Overflow in sum".to_string()); },
+                    }
+                },
+                Value::Duration(x) => {
+                    let total = durations.unwrap_or(Duration::from_secs(0));
+                    match u32::try_from(count).ok().and_then(|count| x.checked_mul(count)).and_then(|product| total.checked_add(product)) {
+                        Some(total) => { durations = Some(total); },
+                        None => { return Value::Error("Overflow in sum".to_string()); },
+                    }
+                },
+                Value::Error(_) => { return value.clone(); },
+                _ => { return Value::Error(format!("Cannot sum non-numeric value: {:?}", value)); },
+            }
+        }
+        durations.map(Value::Duration).unwrap_or(Value::Usize(integers))
+    }
+}
+
+/// Expressions evaluated against records of values.
+///
+/// Evaluation does not panic. Operands of the wrong types, arithmetic overflow, division
+/// by zero, and the like produce `Value::Error`, which operators pass along unchanged.
+/// Filters discard records for which expressions produce anything but `Value::Bool(true)`.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Expression {
+    /// A literal value.
+    Literal(Value),
+    /// The value at an index of the record.
+    Column(usize),
+    /// A binary operator applied to two expressions.
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    /// The negation of a boolean expression.
+    Not(Box<Expression>),
+    /// A function applied to a list of expressions.
+    Call(Function, Vec<Expression>),
+    /// An expression converted to a type.
+    Cast(Box<Expression>, Type),
+    /// The result for the first condition that holds, or the default. (cases, default)
+    Case(Vec<(Expression, Expression)>, Box<Expression>),
+    /// A vector of the values of expressions.
+    Tuple(Vec<Expression>),
+}
+
+/// Binary operators.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Operator {
+    /// Sum of integers or of durations.
+    Add,
+    /// Difference of integers or of durations, which must not be negative.
+    Subtract,
+    /// Product of integers, or of a duration and an integer.
+    Multiply,
+    /// Quotient of integers, or of a duration by an integer.
+    Divide,
+    /// Remainder of integers.
+    Modulo,
+    /// Equality of values of the same type.
+    Equal,
+    /// Inequality of values of the same type.
+    NotEqual,
+    /// Strictly less than, for values of the same type.
+    LessThan,
+    /// Less than or equal, for values of the same type.
+    LessEqual,
+    /// Strictly greater than, for values of the same type.
+    GreaterThan,
+    /// Greater than or equal, for values of the same type.
+    GreaterEqual,
+    /// Conjunction of booleans, evaluating the second only if the first holds.
+    And,
+    /// Disjunction of booleans, evaluating the second only if the first fails.
+    Or,
+}
+
+/// Functions of values.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Function {
+    /// The number of characters of a string, or of elements of a vector.
+    Length,
+    /// A string in lower case.
+    Lower,
+    /// A string in upper case.
+    Upper,
+    /// A string without leading and trailing whitespace.
+    Trim,
+    /// The concatenation of any number of strings.
+    Concat,
+    /// The characters of a string from a position, up to a number of them. (string, position, length)
+    Substring,
+    /// Whether a string contains another string.
+    Contains,
+}
+
+/// Types to which values can be cast.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Type {
+    /// Booleans, from integers (non-zero is true) and from `"true"` and `"false"`.
+    Bool,
+    /// Integers, from booleans, from parsed strings, and from durations in whole seconds.
+    Usize,
+    /// Strings, from booleans, integers, and durations in seconds.
+    String,
+    /// Durations, from integers in seconds.
+    Duration,
+}
+
+impl Expression {
+    /// Evaluates the expression against `data`.
+    pub fn evaluate(&self, data: &[Value]) -> Value {
+        match self {
+            Expression::Literal(value) => value.clone(),
+            Expression::Column(index) => {
+                data.get(*index)
+                    .cloned()
+                    .unwrap_or_else(|| Value::Error(format!("Column {} not found in record of {} values", index, data.len())))
+            },
+            Expression::Binary(Operator::And, left, right) => {
+                match left.evaluate(data) {
+                    Value::Bool(true) => right.evaluate(data).boolean(),
+                    Value::Bool(false) => Value::Bool(false),
+                    other => other.boolean(),
+                }
+            },
+            Expression::Binary(Operator::Or, left, right) => {
+                match left.evaluate(data) {
+                    Value::Bool(true) => Value::Bool(true),
+                    Value::Bool(false) => right.evaluate(data).boolean(),
+                    other => other.boolean(),
+                }
+            },
+            Expression::Binary(operator, left, right) => {
+                match (left.evaluate(data), right.evaluate(data)) {
+                    (Value::Error(error), _) | (_, Value::Error(error)) => Value::Error(error),
+                    (left, right) => operator.apply(left, right),
+                }
+            },
+            Expression::Not(expression) => {
+                match expression.evaluate(data).boolean() {
+                    Value::Bool(value) => Value::Bool(!value),
+                    other => other,
+                }
+            },
+            Expression::Call(function, arguments) => {
+                let arguments = arguments.iter().map(|argument| argument.evaluate(data)).collect::<Vec<_>>();
+                if let Some(error) = arguments.iter().find(|argument| argument.is_error()) {
+                    error.clone()
+                }
+                else {
+                    function.apply(arguments)
+                }
+            },
+            Expression::Cast(expression, typ) => typ.cast(expression.evaluate(data)),
+            Expression::Case(cases, default) => {
+                for (condition, result) in cases.iter() {
+                    match condition.evaluate(data).boolean() {
+                        Value::Bool(true) => { return result.evaluate(data); },
+                        Value::Bool(false) => { },
+                        other => { return other; },
+                    }
+                }
+                default.evaluate(data)
+            },
+            Expression::Tuple(expressions) => {
+                let values = expressions.iter().map(|expression| expression.evaluate(data)).collect::<Vec<_>>();
+                if let Some(error) = values.iter().find(|value| value.is_error()) {
+                    error.clone()
+                }
+                else {
+                    Value::Vector(values)
+                }
+            },
+        }
+    }
+}
+
+/// Converts a predicate to an expression, for filters that mix the two.
+///
+/// The conversion preserves the results of predicates on values of matching types, but not
+/// otherwise. `Predicate::satisfied` compares values of any types by the derived order of
+/// `Value`, in which values of different variants are ordered by variant, whereas the converted
+/// comparison produces an error, and so discards the record. Negation differs as a consequence:
+/// a negated predicate holds where its comparison of mismatched types fails, whereas the negation
+/// of the converted comparison remains an error. Records with too few values, which predicates do
+/// not tolerate, are also errors rather than panics.
+impl From<Predicate<Value>> for Expression {
+    fn from(predicate: Predicate<Value>) -> Self {
+        let compare = |operator, index, other: SecondArgument<Value>| {
+            let other = match other {
+                SecondArgument::Constant(value) => Expression::Literal(value),
+                SecondArgument::Position(position) => Expression::Column(position),
+            };
+            Expression::Binary(operator, Box::new(Expression::Column(index)), Box::new(other))
+        };
+        // Connectives of no predicates hold vacuously, as their predicates do.
+        let connect = |operator, identity, predicates: Vec<Predicate<Value>>| {
+            predicates
+                .into_iter()
+                .map(Expression::from)
+                .fold(None, |result, expression| match result {
+                    None => Some(expression),
+                    Some(result) => Some(Expression::Binary(operator, Box::new(result), Box::new(expression))),
+                })
+                .unwrap_or(Expression::Literal(Value::Bool(identity)))
+        };
+        match predicate {
+            Predicate::LessThan(index, other) => compare(Operator::LessThan, index, other),
+            Predicate::LessEqual(index, other) => compare(Operator::LessEqual, index, other),
+            Predicate::GreaterThan(index, other) => compare(Operator::GreaterThan, index, other),
+            Predicate::GreaterEqual(index, other) => compare(Operator::GreaterEqual, index, other),
+            Predicate::Equal(index, other) => compare(Operator::Equal, index, other),
+            Predicate::NotEqual(index, other) => compare(Operator::NotEqual, index, other),
+            Predicate::Any(predicates) => connect(Operator::Or, false, predicates),
+            Predicate::All(predicates) => connect(Operator::And, true, predicates),
+            Predicate::Not(predicate) => Expression::Not(Box::new(Expression::from(*predicate))),
+            Predicate::Expression(expression) => expression,
+        }
+    }
+}
+
+impl Operator {
+    /// Applies the operator to values that are not errors.
+    ///
+    /// Connectives are applied here to evaluated operands, without short-circuiting.
+    fn apply(&self, left: Value, right: Value) -> Value {
+        use std::cmp::Ordering;
+        let result =
+        match self {
+            Operator::Add => match (&left, &right) {
+                (Value::Usize(x), Value::Usize(y)) => x.checked_add(*y).map(Value::Usize),
+                (Value::Duration(x), Value::Duration(y)) => x.checked_add(*y).map(Value::Duration),
+                _ => None,
+            },
+            Operator::Subtract => match (&left, &right) {
+                (Value::Usize(x), Value::Usize(y)) => x.checked_sub(*y).map(Value::Usize),
+                (Value::Duration(x), Value::Duration(y)) => x.checked_sub(*y).map(Value::Duration),
+                _ => None,
+            },
+            Operator::Multiply => match (&left, &right) {
+                (Value::Usize(x), Value::Usize(y)) => x.checked_mul(*y).map(Value::Usize),
+                (Value::Duration(x), Value::Usize(y)) |
+                (Value::Usize(y), Value::Duration(x)) => u32::try_from(*y).ok().and_then(|y| x.checked_mul(y)).map(Value::Duration),
+                _ => None,
+            },
+            Operator::Divide => match (&left, &right) {
+                (Value::Usize(x), Value::Usize(y)) => x.checked_div(*y).map(Value::Usize),
+                (Value::Duration(x), Value::Usize(y)) => u32::try_from(*y).ok().and_then(|y| x.checked_div(y)).map(Value::Duration),
+                _ => None,
+            },
+            Operator::Modulo => match (&left, &right) {
+                (Value::Usize(x), Value::Usize(y)) => x.checked_rem(*y).map(Value::Usize),
+                _ => None,
+            },
+            Operator::And | Operator::Or => match (&left, &right) {
+                (Value::Bool(x), Value::Bool(y)) => Some(Value::Bool(if *self == Operator::And { *x && *y } else { *x || *y })),
+                _ => None,
+            },
+            comparison => {
+                if std::mem::discriminant(&left) != std::mem::discriminant(&right) {
+                    None
+                }
+                else {
+                    let ordering = left.cmp(&right);
+                    Some(Value::Bool(match comparison {
+                        Operator::Equal => ordering == Ordering::Equal,
+                        Operator::NotEqual => ordering != Ordering::Equal,
+                        Operator::LessThan => ordering == Ordering::Less,
+                        Operator::LessEqual => ordering != Ordering::Greater,
+                        Operator::GreaterThan => ordering == Ordering::Greater,
+                        _ => ordering != Ordering::Less,
+                    }))
+                }
+            },
+        };
+        result.unwrap_or_else(|| Value::Error(format!("Cannot apply {:?} to {:?} and {:?}", self, left, right)))
+    }
+}
+
+impl Function {
+    /// Applies the function to arguments that are not errors.
+    fn apply(&self, arguments: Vec<Value>) -> Value {
+        let result =
+        match (self, &arguments[..]) {
+            (Function::Length, [Value::String(string)]) => Some(Value::Usize(string.chars().count())),
+            (Function::Length, [Value::Vector(vector)]) => Some(Value::Usize(vector.len())),
+            (Function::Lower, [Value::String(string)]) => Some(Value::String(string.to_lowercase())),
+            (Function::Upper, [Value::String(string)]) => Some(Value::String(string.to_uppercase())),
+            (Function::Trim, [Value::String(string)]) => Some(Value::String(string.trim().to_string())),
+            (Function::Concat, strings) => {
+                let mut result = String::new();
+                for string in strings.iter() {
+                    if let Value::String(string) = string { result.push_str(string); }
+                    else { return Value::Error(format!("Cannot apply {:?} to {:?}", self, arguments)); }
+                }
+                Some(Value::String(result))
+            },
+            (Function::Substring, [Value::String(string), Value::Usize(position), Value::Usize(length)]) => {
+                Some(Value::String(string.chars().skip(*position).take(*length).collect()))
+            },
+            (Function::Contains, [Value::String(string), Value::String(pattern)]) => Some(Value::Bool(string.contains(pattern.as_str()))),
+            _ => None,
+        };
+        result.unwrap_or_else(|| Value::Error(format!("Cannot apply {:?} to {:?}", self, arguments)))
+    }
+}
+
+impl Type {
+    /// Converts a value to the type, passing errors along.
+    fn cast(&self, value: Value) -> Value {
+        let result =
+        match (self, &value) {
+            (_, Value::Error(_)) => return value,
+            (Type::Bool, Value::Bool(_)) => Some(value.clone()),
+            (Type::Bool, Value::Usize(x)) => Some(Value::Bool(*x != 0)),
+            (Type::Bool, Value::String(x)) => x.parse::<bool>().ok().map(Value::Bool),
+            (Type::Usize, Value::Usize(_)) => Some(value.clone()),
+            (Type::Usize, Value::Bool(x)) => Some(Value::Usize(if *x { 1 } else { 0 })),
+            (Type::Usize, Value::String(x)) => x.trim().parse::<usize>().ok().map(Value::Usize),
+            (Type::Usize, Value::Duration(x)) => Some(Value::Usize(x.as_secs() as usize)),
+            (Type::String, Value::String(_)) => Some(value.clone()),
+            (Type::String, Value::Bool(x)) => Some(Value::String(x.to_string())),
+            (Type::String, Value::Usize(x)) => Some(Value::String(x.to_string())),
+            (Type::String, Value::Duration(x)) => Some(Value::String(format!("{}", x.as_secs_f64()))),
+            (Type::Duration, Value::Duration(_)) => Some(value.clone()),
+            (Type::Duration, Value::Usize(x)) => Some(Value::Duration(Duration::from_secs(*x as u64))),
+            _ => None,
+        };
+        result.unwrap_or_else(|| Value::Error(format!("Cannot cast {:?} to {:?}", value, self)))
+    }
+}
+
+impl Value {
+    /// Indicates if the value is an error.
+    pub fn is_error(&self) -> bool {
+        if let Value::Error(_) = self { true } else { false }
+    }
+    /// The value if it is a boolean or an error, and otherwise an error.
+    fn boolean(self) -> Value {
+        match self {
+            Value::Bool(_) | Value::Error(_) => self,
+            other => Value::Error(format!("Expected a boolean, found {:?}", other)),
+        }
+    }
 }
 
 impl From<usize> for Value { fn from(x: usize) -> Self { Value::Usize(x) } }
@@ -122,6 +579,11 @@ impl VectorFrom<DifferentialEvent> for Value {
                     x.complete.is_some().into(),
                 ]
             },
+            DifferentialEvent::Size(x) => {
+                vec![
+                    x.operator.into(),
+                ]
+            },
             _ => { vec![] },
         }
     }
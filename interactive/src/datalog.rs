@@ -0,0 +1,592 @@
+//! A Datalog front end producing query plans.
+//!
+//! This module parses Datalog programs and compiles them to `Query<Value>`
+//! instances. A program is a sequence of clauses and directives,
+//!
+//! ```text
+//! edge(1, 2).
+//! reach(X, Y) :- edge(X, Y).
+//! reach(X, Z) :- reach(X, Y), edge(Y, Z).
+//! unreached(X, Y) :- node(X), node(Y), !reach(X, Y), X != Y.
+//! .output unreached
+//! ```
+//!
+//! Variables start with an upper case letter or an underscore, and `_` alone is a
+//! wildcard matching any value. Constants are unsigned integers, double-quoted
+//! strings, `true`, and `false`. Comments start with `//` and extend to the end of
+//! the line.
+//!
+//! The positive atoms of each rule body are compiled to a `Plan::MultiwayJoin`, or
+//! to a single source if there is only one, with equivalence classes drawn from the
+//! variables shared between atoms. Constants, repeated variables, and comparisons
+//! become predicates. Negated atoms are compiled as antijoins, and must only use
+//! variables bound by positive atoms. The rules for each relation are combined and
+//! made distinct, and relations that depend on themselves are evaluated iteratively
+//! by `Query::recursive_rules`. Negation must be stratified, in that no relation
+//! may depend on itself through a negated atom.
+//!
+//! Relations defined by facts are loaded as inputs, and may not also be defined
+//! by rules. Other relations that are not defined must already exist as inputs or
+//! as the results of prior queries.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use plan::{Plan, Predicate};
+use plan::filter::SecondArgument;
+use concrete::Value;
+use {Command, Query};
+
+/// A lexical token.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    /// A relation, variable, or keyword.
+    Word(String),
+    /// An unsigned integer.
+    Number(usize),
+    /// A double-quoted string.
+    Text(String),
+    /// Punctuation and comparison operators.
+    Symbol(&'static str),
+}
+
+/// Splits `text` into tokens.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+
+    const SYMBOLS: &[&str] = &[":-", "!=", "<=", ">=", "<", ">", "=", "!", "(", ")", ",", "."];
+
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while position < chars.len() {
+        let next = chars[position];
+        if next.is_whitespace() {
+            position += 1;
+        }
+        else if next == '/' && chars.get(position + 1) == Some(&'/') {
+            // Comments extend to the end of the line.
+            while position < chars.len() && chars[position] != '\n' { position += 1; }
+        }
+        else if next.is_alphabetic() || next == '_' {
+            let start = position;
+            while position < chars.len() && (chars[position].is_alphanumeric() || chars[position] == '_') { position += 1; }
+            tokens.push(Token::Word(chars[start .. position].iter().collect()));
+        }
+        else if next.is_ascii_digit() {
+            let start = position;
+            while position < chars.len() && chars[position].is_ascii_digit() { position += 1; }
+            let digits = chars[start .. position].iter().collect::<String>();
+            let number = digits.parse().map_err(|_| format!("Invalid number: {}", digits))?;
+            tokens.push(Token::Number(number));
+        }
+        else if next == '"' {
+            // Strings may contain escaped quotes and backslashes.
+            let mut string = String::new();
+            position += 1;
+            loop {
+                match chars.get(position) {
+                    None => { return Err("Unterminated string".to_string()); },
+                    Some('\\') => {
+                        let escaped = chars.get(position + 1).ok_or_else(|| "Unterminated string".to_string())?;
+                        string.push(*escaped);
+                        position += 2;
+                    },
+                    Some('"') => { position += 1; break; },
+                    Some(c) => { string.push(*c); position += 1; },
+                }
+            }
+            tokens.push(Token::Text(string));
+        }
+        else if let Some(symbol) = SYMBOLS.iter().find(|symbol| symbol.chars().enumerate().all(|(i, c)| chars.get(position + i) == Some(&c))) {
+            position += symbol.len();
+            tokens.push(Token::Symbol(*symbol));
+        }
+        else {
+            return Err(format!("Unexpected character: {:?}", next));
+        }
+    }
+    Ok(tokens)
+}
+
+/// An argument of an atom or comparison.
+#[derive(Clone, Debug)]
+enum Term {
+    Variable(String),
+    Constant(Value),
+    Wildcard,
+}
+
+/// A relation applied to arguments.
+#[derive(Clone, Debug)]
+struct Atom {
+    relation: String,
+    terms: Vec<Term>,
+}
+
+/// Comparison operators.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
+}
+
+impl Comparison {
+    /// The comparison with its arguments exchanged.
+    fn flip(self) -> Self {
+        match self {
+            Comparison::LessThan => Comparison::GreaterThan,
+            Comparison::LessEqual => Comparison::GreaterEqual,
+            Comparison::GreaterThan => Comparison::LessThan,
+            Comparison::GreaterEqual => Comparison::LessEqual,
+            other => other,
+        }
+    }
+    /// The predicate comparing the value at `index` with `other`.
+    fn predicate(self, index: usize, other: SecondArgument<Value>) -> Predicate<Value> {
+        match self {
+            Comparison::Equal => Predicate::Equal(index, other),
+            Comparison::NotEqual => Predicate::NotEqual(index, other),
+            Comparison::LessThan => Predicate::LessThan(index, other),
+            Comparison::LessEqual => Predicate::LessEqual(index, other),
+            Comparison::GreaterThan => Predicate::GreaterThan(index, other),
+            Comparison::GreaterEqual => Predicate::GreaterEqual(index, other),
+        }
+    }
+}
+
+/// An element of a rule body.
+#[derive(Clone, Debug)]
+enum Literal {
+    Positive(Atom),
+    Negative(Atom),
+    Compare(Term, Comparison, Term),
+}
+
+/// A rule, or a fact if the body is empty.
+#[derive(Clone, Debug)]
+struct Clause {
+    head: Atom,
+    body: Vec<Literal>,
+}
+
+/// Elements of a program.
+#[derive(Clone, Debug)]
+enum Statement {
+    Clause(Clause),
+    Output(String),
+}
+
+/// A recursive descent parser over tokens.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.position) }
+
+    /// Consumes the symbol if it is next.
+    fn symbol(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Some(Token::Symbol(next)) if *next == symbol => { self.position += 1; true },
+            _ => false,
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if self.symbol(symbol) { Ok(()) }
+        else { Err(format!("Expected {:?}, found {:?}", symbol, self.peek())) }
+    }
+
+    /// Consumes a relation name, which must start with a lower case letter.
+    fn relation(&mut self) -> Result<String, String> {
+        match self.peek().cloned() {
+            Some(Token::Word(word)) if word.starts_with(|c: char| c.is_lowercase()) => {
+                self.position += 1;
+                Ok(word)
+            },
+            other => Err(format!("Expected relation, found {:?}", other)),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        if self.symbol(".") {
+            match self.peek().cloned() {
+                Some(Token::Word(ref word)) if word == "output" => {
+                    self.position += 1;
+                    Ok(Statement::Output(self.relation()?))
+                },
+                other => Err(format!("Unknown directive: {:?}", other)),
+            }
+        }
+        else {
+            let head = self.atom()?;
+            let mut body = Vec::new();
+            if self.symbol(":-") {
+                body.push(self.literal()?);
+                while self.symbol(",") {
+                    body.push(self.literal()?);
+                }
+            }
+            self.expect_symbol(".")?;
+            Ok(Statement::Clause(Clause { head, body }))
+        }
+    }
+
+    fn atom(&mut self) -> Result<Atom, String> {
+        let relation = self.relation()?;
+        let mut terms = Vec::new();
+        self.expect_symbol("(")?;
+        if !self.symbol(")") {
+            terms.push(self.term()?);
+            while self.symbol(",") {
+                terms.push(self.term()?);
+            }
+            self.expect_symbol(")")?;
+        }
+        Ok(Atom { relation, terms })
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        let term = match self.peek().cloned() {
+            Some(Token::Word(ref word)) if word == "_" => Term::Wildcard,
+            Some(Token::Word(ref word)) if word.starts_with(|c: char| c.is_uppercase() || c == '_') => Term::Variable(word.clone()),
+            Some(Token::Word(ref word)) if word == "true" => Term::Constant(Value::Bool(true)),
+            Some(Token::Word(ref word)) if word == "false" => Term::Constant(Value::Bool(false)),
+            Some(Token::Number(number)) => Term::Constant(Value::Usize(number)),
+            Some(Token::Text(text)) => Term::Constant(Value::String(text)),
+            other => { return Err(format!("Expected term, found {:?}", other)); },
+        };
+        self.position += 1;
+        Ok(term)
+    }
+
+    fn literal(&mut self) -> Result<Literal, String> {
+        if self.symbol("!") {
+            Ok(Literal::Negative(self.atom()?))
+        }
+        else if let (Some(Token::Word(_)), Some(Token::Symbol("("))) = (self.peek(), self.tokens.get(self.position + 1)) {
+            Ok(Literal::Positive(self.atom()?))
+        }
+        else {
+            let left = self.term()?;
+            let comparison =
+            if self.symbol("=") { Comparison::Equal }
+            else if self.symbol("!=") { Comparison::NotEqual }
+            else if self.symbol("<=") { Comparison::LessEqual }
+            else if self.symbol(">=") { Comparison::GreaterEqual }
+            else if self.symbol("<") { Comparison::LessThan }
+            else if self.symbol(">") { Comparison::GreaterThan }
+            else { return Err(format!("Expected comparison, found {:?}", self.peek())); };
+            let right = self.term()?;
+            Ok(Literal::Compare(left, comparison, right))
+        }
+    }
+}
+
+/// Parses a program into its statements.
+fn parse(text: &str) -> Result<Vec<Statement>, String> {
+    let mut parser = Parser { tokens: tokenize(text)?, position: 0 };
+    let mut statements = Vec::new();
+    while parser.peek().is_some() {
+        statements.push(parser.statement()?);
+    }
+    Ok(statements)
+}
+
+/// A compiled Datalog program.
+pub struct Program {
+    /// Relations defined by facts, to be created as inputs.
+    pub facts: Vec<(String, Vec<Vec<Value>>)>,
+    /// Rules for the relations defined by rules, followed by rules printing outputs.
+    pub query: Query<Value>,
+}
+
+impl Program {
+    /// Commands creating the program's inputs and installing its rules.
+    pub fn commands(self) -> Vec<Command<Value>> {
+        let mut commands =
+        self.facts
+            .into_iter()
+            .map(|(name, facts)| Command::CreateInput(name, facts))
+            .collect::<Vec<_>>();
+        commands.push(self.query.into_command());
+        commands
+    }
+}
+
+/// Compiles the program `text`.
+pub fn compile(text: &str) -> Result<Program, String> {
+
+    let mut facts = Vec::<(String, Vec<Vec<Value>>)>::new();
+    let mut rules = Vec::<(String, Vec<Plan<Value>>)>::new();
+    let mut outputs = Vec::new();
+    let mut arities = HashMap::new();
+    let mut negations = Vec::new();
+
+    for statement in parse(text)?.into_iter() {
+        match statement {
+            Statement::Output(name) => outputs.push(name),
+            Statement::Clause(clause) => {
+
+                // Each relation must be used with a consistent number of arguments.
+                let atoms = clause.body.iter().filter_map(|literal| match literal {
+                    Literal::Positive(atom) | Literal::Negative(atom) => Some(atom),
+                    Literal::Compare(..) => None,
+                });
+                for atom in Some(&clause.head).into_iter().chain(atoms) {
+                    let arity = *arities.entry(atom.relation.clone()).or_insert(atom.terms.len());
+                    if arity != atom.terms.len() {
+                        return Err(format!("Relation {} used with {} and {} arguments", atom.relation, arity, atom.terms.len()));
+                    }
+                }
+
+                let name = clause.head.relation.clone();
+                if clause.body.is_empty() {
+                    let fact =
+                    clause.head.terms
+                        .into_iter()
+                        .map(|term| match term {
+                            Term::Constant(value) => Ok(value),
+                            other => Err(format!("Facts must only contain constants: {} has {:?}", name, other)),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    match facts.iter_mut().find(|(relation, _)| relation == &name) {
+                        Some((_, list)) => list.push(fact),
+                        None => facts.push((name, vec![fact])),
+                    }
+                }
+                else {
+                    for literal in clause.body.iter() {
+                        if let Literal::Negative(atom) = literal {
+                            negations.push((name.clone(), atom.relation.clone()));
+                        }
+                    }
+                    let plan = rule(&clause)?;
+                    match rules.iter_mut().find(|(relation, _)| relation == &name) {
+                        Some((_, list)) => list.push(plan),
+                        None => rules.push((name, vec![plan])),
+                    }
+                }
+            },
+        }
+    }
+
+    if let Some((name, _)) = facts.iter().find(|(name, _)| rules.iter().any(|(relation, _)| relation == name)) {
+        return Err(format!("Relation {} is defined by both facts and rules", name));
+    }
+
+    // Rules are installed after the rules they refer to, other than those they
+    // recursively depend upon and which will be iterated along with them.
+    let names = rules.iter().map(|(name, _)| name.clone()).collect::<BTreeSet<_>>();
+    let mut reach =
+    rules
+        .iter()
+        .map(|(name, plans)| {
+            let sources = plans.iter().flat_map(|plan| plan.sources()).filter(|source| names.contains(source)).collect::<BTreeSet<_>>();
+            (name.clone(), sources)
+        })
+        .collect::<BTreeMap<_,_>>();
+    let direct = reach.clone();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for name in names.iter() {
+            let mut extended = reach[name].clone();
+            for other in reach[name].iter() {
+                extended.extend(reach[other].iter().cloned());
+            }
+            if extended.len() > reach[name].len() {
+                reach.insert(name.clone(), extended);
+                changed = true;
+            }
+        }
+    }
+
+    // Negated relations must be fully evaluated before the rules that negate them.
+    for (head, negated) in negations.iter() {
+        if names.contains(negated) && (head == negated || (reach[head].contains(negated) && reach[negated].contains(head))) {
+            return Err(format!("Negation of {} in a rule for {} is not stratified", negated, head));
+        }
+    }
+
+    let mut query = Query::new();
+    let mut installed = BTreeSet::new();
+    while installed.len() < rules.len() {
+        for (name, plans) in rules.iter() {
+            let ready = direct[name].iter().all(|other| installed.contains(other) || reach[other].contains(name));
+            if !installed.contains(name) && ready {
+                let plan = if plans.len() == 1 { plans[0].clone() } else { Plan::concatenate(plans.clone()) };
+                query = query.add_rule(plan.distinct().into_rule(name));
+                installed.insert(name.clone());
+            }
+        }
+    }
+
+    for name in outputs.into_iter() {
+        if !arities.contains_key(&name) {
+            return Err(format!("Unknown relation: {}", name));
+        }
+        query = query.add_rule(Plan::source(&name).inspect(&name).into_rule(&format!("{}.output", name)));
+    }
+
+    Ok(Program { facts, query })
+}
+
+/// Compiles the body of a rule, and projects it to the terms of the head.
+fn rule(clause: &Clause) -> Result<Plan<Value>, String> {
+
+    let positive = clause.body.iter().filter_map(|literal| match literal {
+        Literal::Positive(atom) => Some(atom),
+        _ => None,
+    }).collect::<Vec<_>>();
+
+    if positive.is_empty() {
+        return Err(format!("Rule for {} has no positive atoms", clause.head.relation));
+    }
+
+    // Records of the body are all arguments of the positive atoms, in order.
+    let mut results = Vec::new();
+    let mut occurrences = BTreeMap::new();
+    let mut predicates = Vec::new();
+    for (input, atom) in positive.iter().enumerate() {
+        for (attr, term) in atom.terms.iter().enumerate() {
+            match term {
+                Term::Variable(name) => occurrences.entry(name.clone()).or_insert_with(Vec::new).push(results.len()),
+                Term::Constant(value) => predicates.push(Predicate::Equal(results.len(), SecondArgument::Constant(value.clone()))),
+                Term::Wildcard => { },
+            }
+            results.push((attr, input));
+        }
+    }
+
+    // Variables are bound to their first occurrence. Repeated occurrences in one atom
+    // are filtered, and occurrences in multiple atoms are equivalence classes.
+    let mut bindings = HashMap::new();
+    let mut equalities = Vec::new();
+    for (name, positions) in occurrences.into_iter() {
+        for (index, position) in positions.iter().enumerate() {
+            if let Some(prior) = positions[.. index].iter().find(|prior| results[**prior].1 == results[*position].1) {
+                predicates.push(Predicate::Equal(*position, SecondArgument::Position(*prior)));
+            }
+        }
+        if positions.iter().any(|position| results[*position].1 != results[positions[0]].1) {
+            equalities.push(positions.iter().map(|position| results[*position]).collect::<Vec<_>>());
+        }
+        bindings.insert(name, positions[0]);
+    }
+
+    let mut plan =
+    if positive.len() == 1 {
+        Plan::source(&positive[0].relation)
+    }
+    else {
+        // Each atom must share a variable with another, as cross joins are not supported.
+        let mut connected = vec![0];
+        let mut active = true;
+        while active {
+            active = false;
+            for class in equalities.iter() {
+                if class.iter().any(|(_, input)| connected.contains(input)) {
+                    for (_, input) in class.iter() {
+                        if !connected.contains(input) {
+                            connected.push(*input);
+                            active = true;
+                        }
+                    }
+                }
+            }
+        }
+        if connected.len() < positive.len() {
+            return Err(format!("Rule for {} requires a cross join", clause.head.relation));
+        }
+
+        let sources = positive.iter().map(|atom| Plan::source(&atom.relation)).collect();
+        Plan::multiway_join(sources, equalities, results.clone())
+    };
+
+    // Comparisons must compare bound variables with variables or constants.
+    let resolve = |term: &Term| match term {
+        Term::Variable(name) => bindings.get(name).map(|p| SecondArgument::Position(*p)).ok_or_else(|| format!("Variable {} is not bound by a positive atom", name)),
+        Term::Constant(value) => Ok(SecondArgument::Constant(value.clone())),
+        Term::Wildcard => Err("Wildcards may not be compared".to_string()),
+    };
+    for literal in clause.body.iter() {
+        if let Literal::Compare(left, comparison, right) = literal {
+            match (resolve(left)?, resolve(right)?) {
+                (SecondArgument::Position(index), other) => predicates.push(comparison.predicate(index, other)),
+                (other, SecondArgument::Position(index)) => predicates.push(comparison.flip().predicate(index, other)),
+                _ => { return Err(format!("Comparison of constants: {:?} {:?} {:?}", left, comparison, right)); },
+            }
+        }
+    }
+
+    if !predicates.is_empty() {
+        let predicate = if predicates.len() == 1 { predicates.pop().unwrap() } else { Predicate::All(predicates) };
+        plan = plan.filter(predicate);
+    }
+
+    // Negated atoms remove records that join with them on their variables.
+    for literal in clause.body.iter() {
+        if let Literal::Negative(atom) = literal {
+
+            let mut filters = Vec::new();
+            let mut variables = Vec::<(&String, usize)>::new();
+            for (attr, term) in atom.terms.iter().enumerate() {
+                match term {
+                    Term::Variable(name) => {
+                        if let Some((_, prior)) = variables.iter().find(|(other, _)| *other == name) {
+                            filters.push(Predicate::Equal(attr, SecondArgument::Position(*prior)));
+                        }
+                        else {
+                            variables.push((name, attr));
+                        }
+                    },
+                    Term::Constant(value) => filters.push(Predicate::Equal(attr, SecondArgument::Constant(value.clone()))),
+                    Term::Wildcard => { },
+                }
+            }
+
+            let mut negated = Plan::source(&atom.relation);
+            if !filters.is_empty() {
+                negated = negated.filter(if filters.len() == 1 { filters.pop().unwrap() } else { Predicate::All(filters) });
+            }
+            let negated = negated.project(variables.iter().map(|(_, attr)| *attr).collect()).distinct();
+
+            let mut keys = Vec::new();
+            for (index, (name, _)) in variables.iter().enumerate() {
+                let position = bindings.get(*name).ok_or_else(|| format!("Variable {} in negated {} is not bound by a positive atom", name, atom.relation))?;
+                keys.push((*position, index));
+            }
+
+            // Joined records lead with their keys, and must be restored to their prior order.
+            let restore =
+            (0 .. results.len())
+                .map(|index| match keys.iter().position(|(position, _)| *position == index) {
+                    Some(key) => key,
+                    None => keys.len() + index - keys.iter().filter(|(position, _)| *position < index).count(),
+                })
+                .collect();
+
+            plan = plan.clone().concat(plan.join(negated, keys).project(restore).negate());
+        }
+    }
+
+    // The head selects values of bound variables.
+    let projection =
+    clause.head.terms
+        .iter()
+        .map(|term| match term {
+            Term::Variable(name) => bindings.get(name).cloned().ok_or_else(|| format!("Variable {} is not bound by a positive atom", name)),
+            other => Err(format!("Rule heads must only contain variables: {} has {:?}", clause.head.relation, other)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(plan.project(projection))
+}
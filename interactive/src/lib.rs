@@ -21,12 +21,16 @@ pub mod manager;
 pub use manager::{Manager, TraceManager, InputManager};
 
 pub mod command;
-pub use command::Command;
+pub use command::{Command, Response};
 
 pub mod logging;
 
 pub mod concrete;
 
+pub mod sql;
+
+pub mod datalog;
+
 /// System-wide notion of time.
 pub type Time = ::std::time::Duration;
 /// System-wide update type.
@@ -34,6 +38,7 @@ pub type Diff = isize;
 
 use std::hash::Hash;
 use std::fmt::Debug;
+use differential_dataflow::ExchangeData;
 use serde::{Serialize, Deserialize};
 
 /// Types capable of use as data in interactive.
@@ -42,8 +47,24 @@ pub trait Datum : Hash+Sized+Debug {
     type Expression : Clone+Debug+Eq+Ord+Hash+Serialize+for<'a>Deserialize<'a>;
     /// Applies an expression to a slice of data.
     fn subject_to(data: &[Self], expr: &Self::Expression) -> Self;
+    /// Indicates if an expression applied to a slice of data holds, for filtering.
+    ///
+    /// By default no expression holds, and filters by expressions discard all data.
+    fn satisfies(_data: &[Self], _expr: &Self::Expression) -> bool { false }
     /// Creates a expression that implements projection.
     fn projection(index: usize) -> Self::Expression;
+    /// Creates a value reporting a number of records, for counting aggregates.
+    ///
+    /// By default this panics, and types used in counting aggregates must implement it.
+    fn count(_count: Diff) -> Self {
+        unimplemented!("Datum::count is not implemented for {}", std::any::type_name::<Self>())
+    }
+    /// Sums values with multiplicities, for summing aggregates.
+    ///
+    /// By default this panics, and types used in summing aggregates must implement it.
+    fn sum<'a, I: Iterator<Item=(&'a Self, Diff)>>(_values: I) -> Self where Self: 'a {
+        unimplemented!("Datum::sum is not implemented for {}", std::any::type_name::<Self>())
+    }
 }
 
 /// A type that can be converted to a vector of another type.
@@ -71,6 +92,69 @@ impl<V: Datum> Query<V> {
     }
 }
 
+impl<V: ExchangeData+Hash+Datum> Query<V> {
+    /// Rewrites rules that are mutually recursive as `Plan::Iterate` plans.
+    ///
+    /// Rules are recursive if they refer, through other rules of the query, to
+    /// themselves. Each such rule is replaced by a plan that iterates all rules
+    /// that it and they mutually refer to, and produces the rule's contents.
+    pub fn recursive_rules(&self) -> Vec<Rule<V>> {
+
+        use std::collections::BTreeSet;
+
+        let names = self.rules.iter().map(|rule| rule.name.clone()).collect::<BTreeSet<_>>();
+
+        // The rules each rule refers to, directly or indirectly.
+        let mut reach =
+        self.rules
+            .iter()
+            .map(|rule| rule.plan.sources().intersection(&names).cloned().collect::<BTreeSet<_>>())
+            .collect::<Vec<_>>();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 0 .. self.rules.len() {
+                let mut extended = reach[index].clone();
+                for (other, rule) in self.rules.iter().enumerate() {
+                    if reach[index].contains(&rule.name) {
+                        extended.extend(reach[other].iter().cloned());
+                    }
+                }
+                if extended.len() > reach[index].len() {
+                    reach[index] = extended;
+                    changed = true;
+                }
+            }
+        }
+
+        self.rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                if reach[index].contains(&rule.name) {
+                    // Rules that this rule reaches, and that reach it in turn.
+                    let group =
+                    self.rules
+                        .iter()
+                        .enumerate()
+                        .filter(|(other, other_rule)| reach[index].contains(&other_rule.name) && reach[*other].contains(&rule.name))
+                        .map(|(_, other_rule)| other_rule.clone())
+                        .collect::<Vec<_>>();
+
+                    Rule {
+                        name: rule.name.clone(),
+                        plan: Plan::Iterate(plan::Iterate { rules: group, result: rule.name.clone() }),
+                    }
+                }
+                else {
+                    rule.clone()
+                }
+            })
+            .collect()
+    }
+}
+
 impl<V: Datum> Query<V> {
     /// Converts the query into a command.
     pub fn into_command(self) -> Command<V> {
@@ -219,7 +219,7 @@ where
     I : IntoIterator,
     <I as IntoIterator>::Item: EventIterator<Duration, (Duration, usize, DifferentialEvent)>+'static
 {
-    let (merge,batch) =
+    let (merge,batch,used,allocated) =
     worker.dataflow(move |scope| {
 
         use timely::dataflow::operators::capture::Replay;
@@ -234,6 +234,8 @@ where
 
         let (mut batch_out, batch) = demux.new_output();
         let (mut merge_out, merge) = demux.new_output();
+        let (mut used_out, used) = demux.new_output();
+        let (mut allocated_out, allocated) = demux.new_output();
 
         let mut demux_buffer = Vec::new();
 
@@ -243,12 +245,16 @@ where
 
                 let mut batch = batch_out.activate();
                 let mut merge = merge_out.activate();
+                let mut used = used_out.activate();
+                let mut allocated = allocated_out.activate();
 
                 input.for_each(|time, data| {
 
                     data.swap(&mut demux_buffer);
                     let mut batch_session = batch.session(&time);
                     let mut merge_session = merge.session(&time);
+                    let mut used_session = used.session(&time);
+                    let mut allocated_session = allocated.session(&time);
 
                     for (time, _worker, datum) in demux_buffer.drain(..) {
 
@@ -263,6 +269,13 @@ where
                             DifferentialEvent::Merge(_) => {
                                 merge_session.give((V::vector_from(datum), time, 1));
                             },
+                            // Sizes are recorded as the multiplicities of operator identifiers.
+                            DifferentialEvent::Size(ref size) => {
+                                let (bytes_used, bytes_allocated) = (size.used, size.allocated);
+                                let record = V::vector_from(datum.clone());
+                                if bytes_used != 0 { used_session.give((record.clone(), time, bytes_used)); }
+                                if bytes_allocated != 0 { allocated_session.give((record, time, bytes_allocated)); }
+                            },
                             _ => { },
                         }
                     }
@@ -274,10 +287,14 @@ where
         use differential_dataflow::operators::arrange::ArrangeBySelf;
         let batch = batch.as_collection().arrange_by_self().trace;
         let merge = merge.as_collection().arrange_by_self().trace;
+        let used = used.as_collection().arrange_by_self().trace;
+        let allocated = allocated.as_collection().arrange_by_self().trace;
 
-        (merge,batch)
+        (merge,batch,used,allocated)
     });
 
     manager.traces.set_unkeyed(&Plan::Source(format!("logs/{}/differential/arrange/batch", name)), &batch);
     manager.traces.set_unkeyed(&Plan::Source(format!("logs/{}/differential/arrange/merge", name)), &merge);
+    manager.traces.set_unkeyed(&Plan::Source(format!("logs/{}/differential/arrange/size/used", name)), &used);
+    manager.traces.set_unkeyed(&Plan::Source(format!("logs/{}/differential/arrange/size/allocated", name)), &allocated);
 }
\ No newline at end of file
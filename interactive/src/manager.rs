@@ -1,19 +1,21 @@
 //! Management of inputs and traces.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::hash::Hash;
+use std::rc::Rc;
+use std::cell::RefCell;
 // use std::time::Duration;
 
-use timely::dataflow::ProbeHandle;
+use timely::dataflow::{Scope, ProbeHandle};
 use timely::communication::Allocate;
 use timely::worker::Worker;
 use timely::logging::TimelyEvent;
 
 // use timely::dataflow::operators::capture::event::EventIterator;
 
-use differential_dataflow::ExchangeData;
+use differential_dataflow::{Collection, ExchangeData};
 use differential_dataflow::trace::implementations::ord::{OrdKeySpine, OrdValSpine};
-use differential_dataflow::operators::arrange::TraceAgent;
+use differential_dataflow::operators::arrange::{Arranged, TraceAgent};
 use differential_dataflow::input::InputSession;
 
 use differential_dataflow::logging::DifferentialEvent;
@@ -35,7 +37,33 @@ pub struct Manager<V: ExchangeData+Datum> {
     pub inputs: InputManager<V>,
     /// Manages maintained traces.
     pub traces: TraceManager<V>,
-    /// Probes all computations.
+    /// Installed dataflows, by dataflow index.
+    pub dataflows: HashMap<usize, Dataflow>,
+    /// Indices of subscription dataflows whose clients have disconnected.
+    ///
+    /// Only the worker writing to a client observes its disconnection, and so the
+    /// dataflows should be retired with `Command::DropSubscription`, for all workers.
+    pub disconnected: Rc<RefCell<Vec<usize>>>,
+    /// Peeks awaiting the completion of the traces they read, through the times they read at.
+    ///
+    /// Each attempts to answer its peek, and reports whether it has. The peeks hold their own
+    /// handles to the traces, which hold back the compaction of those traces until answered.
+    pub peeks: Vec<Box<dyn FnMut()->bool>>,
+}
+
+/// Book-keeping for an installed dataflow.
+pub struct Dataflow {
+    /// Names of the inputs or rules the dataflow publishes, if any.
+    ///
+    /// Dataflows that publish nothing, like subscriptions, are retired with their inputs.
+    pub names: Vec<String>,
+    /// Indicates the dataflow maintains an input, and is retired only with the input.
+    pub input: bool,
+    /// Indices of dataflows whose arrangements the dataflow imports.
+    pub uses: BTreeSet<usize>,
+    /// Indicates the dataflow has been dropped, and remains only while it is used.
+    pub dropped: bool,
+    /// Probes the dataflow's outputs.
     pub probe: ProbeHandle<Time>,
 }
 
@@ -49,7 +77,9 @@ impl<V: ExchangeData+Datum> Manager<V>
         Manager {
             inputs: InputManager::new(),
             traces: TraceManager::new(),
-            probe: ProbeHandle::new(),
+            dataflows: HashMap::new(),
+            disconnected: Rc::new(RefCell::new(Vec::new())),
+            peeks: Vec::new(),
         }
     }
 
@@ -83,6 +113,12 @@ impl<V: ExchangeData+Datum> Manager<V>
         self.inputs.sessions.clear();
         self.traces.inputs.clear();
         self.traces.arrangements.clear();
+        self.traces.input_owners.clear();
+        self.traces.arrangement_owners.clear();
+        self.traces.statistics.clear();
+        self.dataflows.clear();
+        self.disconnected.borrow_mut().clear();
+        self.peeks.clear();
 
         // Deregister loggers, so that the logging dataflows can shut down.
         worker
@@ -105,6 +141,129 @@ impl<V: ExchangeData+Datum> Manager<V>
         self.traces.set_unkeyed(&Plan::Source(name), &trace);
     }
 
+    /// Records the dataflow at `index`, which publishes `names`.
+    ///
+    /// The dataflow should have been built between calls to `begin_dataflow` and
+    /// this method, so that the arrangements it installs and imports are known.
+    pub fn install_dataflow(&mut self, index: usize, names: Vec<String>, probe: ProbeHandle<Time>) {
+        let uses = self.traces.end_dataflow();
+        self.dataflows.insert(index, Dataflow { names, input: false, uses, dropped: false, probe });
+    }
+
+    /// Records the dataflow at `index`, which maintains the input `name`.
+    ///
+    /// The dataflow is retired by `drop_input`, rather than by `drop_query`.
+    pub fn install_input(&mut self, index: usize, name: String, probe: ProbeHandle<Time>) {
+        self.install_dataflow(index, vec![name], probe);
+        if let Some(dataflow) = self.dataflows.get_mut(&index) {
+            dataflow.input = true;
+        }
+    }
+
+    /// Indicates if any installed dataflow may still produce outputs at times less than `time`.
+    ///
+    /// Dataflows have their own probes, as the probes of dropped dataflows are never
+    /// advanced further.
+    pub fn less_than(&self, time: &Time) -> bool {
+        self.dataflows.values().any(|dataflow| dataflow.probe.less_than(time))
+    }
+
+    /// Finds the live dataflow publishing `name`, among input or query dataflows.
+    fn find(&self, name: &str, input: bool) -> Option<usize> {
+        self.dataflows
+            .iter()
+            .find(|(_, dataflow)| !dataflow.dropped && dataflow.input == input && dataflow.names.iter().any(|n| n == name))
+            .map(|(index, _)| *index)
+    }
+
+    /// Retires the rule `name`, along with rules installed by the same query.
+    ///
+    /// Subscriptions to the rules are closed, but the dataflow is dropped only once
+    /// no other dataflow imports its arrangements. Inputs are removed with `drop_input`.
+    pub fn drop_query<A: Allocate>(&mut self, name: &str, worker: &mut Worker<A>) {
+        if let Some(index) = self.find(name, false) {
+            self.retire(index);
+            let subscriptions =
+            self.dataflows
+                .iter()
+                .filter(|(_, dataflow)| dataflow.names.is_empty() && dataflow.uses.contains(&index))
+                .map(|(index, _)| *index)
+                .collect::<Vec<_>>();
+            for subscription in subscriptions.into_iter() {
+                self.retire(subscription);
+            }
+            self.collect(worker);
+        }
+        else {
+            println!("Query not found: {:?}", name);
+        }
+    }
+
+    /// Removes the input `name`, along with all rules that depend on it.
+    pub fn drop_input<A: Allocate>(&mut self, name: &str, worker: &mut Worker<A>) {
+        self.inputs.sessions.remove(name);
+        if let Some(index) = self.find(name, true) {
+            // Retire the input's dataflow and all dataflows that transitively use it.
+            let mut todo = vec![index];
+            while let Some(index) = todo.pop() {
+                if !self.dataflows[&index].dropped {
+                    self.retire(index);
+                }
+                todo.extend(self.dataflows.iter().filter(|(_, d)| !d.dropped && d.uses.contains(&index)).map(|(i, _)| *i));
+            }
+            self.collect(worker);
+        }
+        else {
+            println!("Input not found: {:?}", name);
+        }
+    }
+
+    /// Answers the pending peeks whose traces are now complete through their times.
+    pub fn answer_peeks(&mut self) {
+        for mut peek in ::std::mem::replace(&mut self.peeks, Vec::new()) {
+            if !peek() {
+                self.peeks.push(peek);
+            }
+        }
+    }
+
+    /// Retires the subscription dataflow at `index`, if it has not already been retired.
+    pub fn drop_subscription<A: Allocate>(&mut self, index: usize, worker: &mut Worker<A>) {
+        let subscription = self.dataflows.get(&index).map(|dataflow| !dataflow.dropped && !dataflow.input && dataflow.names.is_empty());
+        if subscription == Some(true) {
+            self.retire(index);
+            self.collect(worker);
+        }
+    }
+
+    /// Marks a dataflow as dropped, and releases its published arrangements.
+    fn retire(&mut self, index: usize) {
+        if let Some(dataflow) = self.dataflows.get_mut(&index) {
+            dataflow.dropped = true;
+            self.traces.release(index);
+        }
+    }
+
+    /// Drops retired dataflows that are no longer used by other dataflows.
+    fn collect<A: Allocate>(&mut self, worker: &mut Worker<A>) {
+        let mut active = true;
+        while active {
+            active = false;
+            let unused =
+            self.dataflows
+                .iter()
+                .filter(|(index, dataflow)| dataflow.dropped && !self.dataflows.values().any(|other| other.uses.contains(*index)))
+                .map(|(index, _)| *index)
+                .collect::<Vec<_>>();
+
+            for index in unused.into_iter() {
+                self.dataflows.remove(&index);
+                worker.drop_dataflow(index);
+                active = true;
+            }
+        }
+    }
+
     /// Advances inputs and traces to `time`.
     pub fn advance_time(&mut self, time: &Time) {
         self.inputs.advance_time(time);
@@ -169,6 +328,60 @@ pub struct TraceManager<V: ExchangeData+Datum> {
     /// Arrangements of collections by key.
     arrangements: HashMap<Plan<V>, HashMap<Vec<usize>, KeysValsHandle<V>>>,
 
+    /// Indices of the dataflows maintaining unkeyed arrangements, where known.
+    input_owners: HashMap<Plan<V>, usize>,
+
+    /// Indices of the dataflows maintaining keyed arrangements, where known.
+    arrangement_owners: HashMap<(Plan<V>, Vec<usize>), usize>,
+
+    /// The index of the dataflow under construction, if it is tracked.
+    building: Option<usize>,
+
+    /// Indices of dataflows whose arrangements the dataflow under construction uses.
+    uses: BTreeSet<usize>,
+
+    /// Probes the dataflow under construction, including its statistics.
+    probe: ProbeHandle<Time>,
+
+    /// Statistics about arrangements, by plan and keys if keyed, and the index of their dataflow.
+    statistics: HashMap<(Plan<V>, Option<Vec<usize>>), (usize, Statistics)>,
+
+    /// The most recent time passed to `advance_time`.
+    time: Time,
+}
+
+/// Counts of the records and distinct keys of an arrangement.
+///
+/// Each worker receives the counts of all workers, so that all workers agree on
+/// the counts as of times that computation has completed.
+#[derive(Clone)]
+struct Statistics {
+    counts: Rc<RefCell<Counts>>,
+}
+
+/// Counts accumulated through some time, and updates to them at later times.
+#[derive(Default)]
+struct Counts {
+    /// The counts of records (`0`) and of distinct keys (`1`) at times already passed.
+    accumulated: [isize; 2],
+    /// Updates to the counts of records (`0`) and of distinct keys (`1`) at later times.
+    updates: Vec<(usize, Time, Diff)>,
+}
+
+impl Statistics {
+    /// Accumulates updates at times less than `time`, and reports the resulting counts.
+    ///
+    /// Times must not decrease from call to call, as earlier updates are no longer retained.
+    fn advance(&self, time: &Time) -> (usize, usize) {
+        let mut counts = self.counts.borrow_mut();
+        let Counts { accumulated, updates } = &mut *counts;
+        for (kind, _, diff) in updates.iter().filter(|(_, t, _)| t < time) {
+            accumulated[*kind] += diff;
+        }
+        updates.retain(|(_, t, _)| t >= time);
+        differential_dataflow::consolidation::consolidate_updates(updates);
+        (accumulated[0] as usize, accumulated[1] as usize)
+    }
 }
 
 impl<V: ExchangeData+Hash+Datum> TraceManager<V> {
@@ -177,15 +390,77 @@ impl<V: ExchangeData+Hash+Datum> TraceManager<V> {
     pub fn new() -> Self {
         Self {
             inputs: HashMap::new(),
-            arrangements: HashMap::new()
+            arrangements: HashMap::new(),
+            input_owners: HashMap::new(),
+            arrangement_owners: HashMap::new(),
+            building: None,
+            uses: BTreeSet::new(),
+            probe: ProbeHandle::new(),
+            statistics: HashMap::new(),
+            time: Default::default(),
+        }
+    }
+
+    /// Starts tracking the arrangements installed and used by the dataflow at `index`.
+    ///
+    /// Returns a probe for the dataflow, which also probes any statistics it maintains.
+    pub fn begin_dataflow(&mut self, index: usize) -> ProbeHandle<Time> {
+        self.building = Some(index);
+        self.uses.clear();
+        self.probe = ProbeHandle::new();
+        self.probe.clone()
+    }
+
+    /// Stops tracking, and returns the indices of dataflows whose arrangements were used.
+    pub fn end_dataflow(&mut self) -> BTreeSet<usize> {
+        self.building = None;
+        ::std::mem::replace(&mut self.uses, BTreeSet::new())
+    }
+
+    /// Removes all arrangements maintained by the dataflow at `index`.
+    ///
+    /// This releases the compaction the manager holds on their traces.
+    pub fn release(&mut self, index: usize) {
+        let inputs = &mut self.inputs;
+        self.input_owners.retain(|plan, owner| {
+            if *owner == index { inputs.remove(plan); }
+            *owner != index
+        });
+        let arrangements = &mut self.arrangements;
+        self.arrangement_owners.retain(|(plan, keys), owner| {
+            if *owner == index {
+                if let Some(map) = arrangements.get_mut(plan) {
+                    map.remove(keys);
+                    if map.is_empty() { arrangements.remove(plan); }
+                }
+            }
+            *owner != index
+        });
+        self.statistics.retain(|_, (owner, _)| *owner != index);
+    }
+
+    /// Records a use of an arrangement maintained by the dataflow `owner`.
+    fn record_use(&mut self, owner: Option<usize>) {
+        if let (Some(owner), Some(building)) = (owner, self.building) {
+            if owner != building {
+                self.uses.insert(owner);
+            }
         }
     }
 
     /// Advances the frontier of each maintained trace.
+    ///
+    /// Traces are compacted through `time`, and remain readable at times from `time` on.
+    /// Pending peeks hold their own handles to the traces they read, which hold back the
+    /// compaction of only those traces, and only to the times of the peeks.
     pub fn advance_time(&mut self, time: &Time) {
         use differential_dataflow::trace::TraceReader;
         use timely::progress::frontier::Antichain;
+        self.time = time.clone();
         let frontier = Antichain::from_elem(time.clone());
+        for (_, statistics) in self.statistics.values() {
+            statistics.advance(&self.time);
+        }
         for trace in self.inputs.values_mut() {
             trace.set_logical_compaction(frontier.borrow());
             trace.set_physical_compaction(frontier.borrow());
@@ -199,23 +474,42 @@ impl<V: ExchangeData+Hash+Datum> TraceManager<V> {
     }
 
     /// Recover an arrangement by plan and keys, if it is cached.
-    pub fn get_unkeyed(&self, plan: &Plan<V>) -> Option<KeysOnlyHandle<V>> {
+    ///
+    /// The dataflow under construction is recorded as using the arrangement.
+    pub fn get_unkeyed(&mut self, plan: &Plan<V>) -> Option<KeysOnlyHandle<V>> {
+        let result =
         self.inputs
             .get(plan)
-            .map(|x| x.clone())
+            .map(|x| x.clone());
+        if result.is_some() {
+            let owner = self.input_owners.get(plan).cloned();
+            self.record_use(owner);
+        }
+        result
     }
 
     /// Installs an unkeyed arrangement for a specified plan.
     pub fn set_unkeyed(&mut self, plan: &Plan<V>, handle: &KeysOnlyHandle<V>) {
         self.inputs
             .insert(plan.clone(), handle.clone());
+        if let Some(building) = self.building {
+            self.input_owners.insert(plan.clone(), building);
+        }
     }
 
     /// Recover an arrangement by plan and keys, if it is cached.
-    pub fn get_keyed(&self, plan: &Plan<V>, keys: &[usize]) -> Option<KeysValsHandle<V>> {
+    ///
+    /// The dataflow under construction is recorded as using the arrangement.
+    pub fn get_keyed(&mut self, plan: &Plan<V>, keys: &[usize]) -> Option<KeysValsHandle<V>> {
+        let result =
         self.arrangements
             .get(plan)
-            .and_then(|map| map.get(keys).map(|x| x.clone()))
+            .and_then(|map| map.get(keys).map(|x| x.clone()));
+        if result.is_some() {
+            let owner = self.arrangement_owners.get(&(plan.clone(), keys.to_vec())).cloned();
+            self.record_use(owner);
+        }
+        result
     }
 
     /// Installs a keyed arrangement for a specified plan and sequence of keys.
@@ -224,6 +518,90 @@ impl<V: ExchangeData+Hash+Datum> TraceManager<V> {
             .entry(plan.clone())
             .or_insert(HashMap::new())
             .insert(keys.to_vec(), handle.clone());
+        if let Some(building) = self.building {
+            self.arrangement_owners.insert((plan.clone(), keys.to_vec()), building);
+        }
+    }
+
+    /// Indicates if an unkeyed arrangement exists, without recording a use of it.
+    pub fn contains_unkeyed(&self, plan: &Plan<V>) -> bool {
+        self.inputs.contains_key(plan)
+    }
+
+    /// Indicates if a keyed arrangement exists, without recording a use of it.
+    pub fn contains_keyed(&self, plan: &Plan<V>, keys: &[usize]) -> bool {
+        self.arrangements
+            .get(plan)
+            .map(|map| map.contains_key(keys))
+            .unwrap_or(false)
+    }
+
+    /// Maintains statistics for an unkeyed arrangement of `plan`.
+    ///
+    /// The statistics are computed in, and retired with, the dataflow under construction.
+    /// Untracked dataflows do not maintain statistics, and statistics already maintained
+    /// for `plan` are not maintained again.
+    pub fn set_unkeyed_statistics<G>(&mut self, plan: &Plan<V>, arranged: &Arranged<G, KeysOnlyHandle<V>>)
+    where
+        G: Scope<Timestamp = Time>,
+    {
+        use differential_dataflow::operators::CountTotal;
+        if self.building.is_some() && !self.statistics.contains_key(&(plan.clone(), None)) {
+            // Records are their own keys, and their counts are read from the arrangement.
+            let counts = arranged.count_total();
+            self.set_statistics(plan, None, &counts);
+        }
+    }
+
+    /// Maintains statistics for an arrangement of `plan` by `keys`.
+    ///
+    /// The statistics are computed in, and retired with, the dataflow under construction.
+    /// Untracked dataflows do not maintain statistics.
+    pub fn set_keyed_statistics<G>(&mut self, plan: &Plan<V>, keys: &[usize], arranged: &Arranged<G, KeysValsHandle<V>>)
+    where
+        G: Scope<Timestamp = Time>,
+    {
+        use differential_dataflow::operators::CountTotal;
+        if self.building.is_some() {
+            // Counting keys arranges only the keys, each once with its number of records.
+            let counts = arranged.as_collection(|key, _val| key.clone()).count_total();
+            self.set_statistics(plan, Some(keys), &counts);
+        }
+    }
+
+    /// Maintains statistics from the changing numbers of records for each key.
+    fn set_statistics<G>(&mut self, plan: &Plan<V>, keys: Option<&[usize]>, counts: &Collection<G, (Vec<V>, Diff), isize>)
+    where
+        G: Scope<Timestamp = Time>,
+    {
+        use timely::dataflow::operators::{Broadcast, Inspect, Map, Probe};
+
+        if let Some(building) = self.building {
+
+            let statistics = Statistics { counts: Rc::new(RefCell::new(Counts::default())) };
+            let shared = statistics.counts.clone();
+            counts
+                .inner
+                .flat_map(|((_key, count), time, diff)| vec![(0, time.clone(), count * diff), (1, time, diff)])
+                .broadcast()
+                .inspect(move |update| shared.borrow_mut().updates.push(update.clone()))
+                .probe_with(&mut self.probe);
+
+            self.statistics.insert((plan.clone(), keys.map(|keys| keys.to_vec())), (building, statistics));
+        }
+    }
+
+    /// Reports the numbers of records and distinct keys in an arrangement, if maintained.
+    ///
+    /// The counts are accumulated through times less than the most recent time passed to
+    /// `advance_time`, which all workers agree on once computation reaches that time.
+    /// Statistics maintained by the dataflow under construction have yet to observe any
+    /// records, and are not reported.
+    pub fn get_statistics(&self, plan: &Plan<V>, keys: Option<&[usize]>) -> Option<(usize, usize)> {
+        self.statistics
+            .get(&(plan.clone(), keys.map(|keys| keys.to_vec())))
+            .filter(|(owner, _)| Some(*owner) != self.building)
+            .map(|(_, statistics)| statistics.advance(&self.time))
     }
 
 }
\ No newline at end of file
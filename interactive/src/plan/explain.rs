@@ -0,0 +1,313 @@
+//! Physical plan descriptions.
+//!
+//! An `Explainer` walks plans as rendering would, without rendering them, and describes
+//! the operators rendering would construct. In particular, it reports which arrangements
+//! would be drawn from the `TraceManager` and which would be newly built, the keys by which
+//! data would be exchanged between workers, and the join orders `MultiwayJoin` would pick.
+//!
+//! Arrangements the query itself would build are tracked as it is described, so that later
+//! rules reusing them are reported as rendering would reuse them.
+
+use std::hash::Hash;
+use std::collections::{BTreeSet, HashSet};
+
+use differential_dataflow::ExchangeData;
+use plan::Plan;
+use {TraceManager, Datum, Query};
+
+/// Describes plans as rendering would construct them.
+pub struct Explainer<'a, V: ExchangeData+Hash+Datum> {
+    /// Arrangements available before the query.
+    arrangements: &'a TraceManager<V>,
+    /// Unkeyed arrangements the query would build.
+    unkeyed: HashSet<Plan<V>>,
+    /// Keyed arrangements the query would build.
+    keyed: HashSet<(Plan<V>, Vec<usize>)>,
+    /// Plans already described, whose collections rendering would share.
+    described: HashSet<Plan<V>>,
+    /// Names of variables of the loop under description, if any.
+    variables: BTreeSet<String>,
+    /// Nesting depth of the next line.
+    depth: usize,
+    /// Lines of the description.
+    lines: Vec<String>,
+}
+
+impl<'a, V: ExchangeData+Hash+Datum> Explainer<'a, V> {
+
+    /// Creates an explainer drawing on the arrangements of `arrangements`.
+    pub fn new(arrangements: &'a TraceManager<V>) -> Self {
+        Explainer {
+            arrangements,
+            unkeyed: HashSet::new(),
+            keyed: HashSet::new(),
+            described: HashSet::new(),
+            variables: BTreeSet::new(),
+            depth: 0,
+            lines: Vec::new(),
+        }
+    }
+
+    /// Describes the rules of `query`, as `Command::Query` would render them.
+    pub fn query(&mut self, query: &Query<V>) {
+        for rule in query.recursive_rules() {
+            self.line(format!("Rule {:?}: arrange unkeyed (new), exchanging by record", rule.name));
+            self.nested(|explainer| explainer.plan(&rule.plan));
+            self.unkeyed.insert(rule.plan.clone());
+            self.unkeyed.insert(Plan::Source(rule.name.clone()));
+        }
+    }
+
+    /// The description so far, one operator per line.
+    pub fn finish(self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// The arrangements available before the query.
+    pub fn trace_manager(&self) -> &TraceManager<V> {
+        self.arrangements
+    }
+
+    /// Indicates if an arrangement of `plan` by `keys` exists or would be built.
+    pub fn contains_keyed(&self, plan: &Plan<V>, keys: &[usize]) -> bool {
+        self.arrangements.contains_keyed(plan, keys) || self.keyed.contains(&(plan.clone(), keys.to_vec()))
+    }
+
+    /// Indicates if an unkeyed arrangement of `plan` exists or would be built.
+    pub fn contains_unkeyed(&self, plan: &Plan<V>) -> bool {
+        self.arrangements.contains_unkeyed(plan) || self.unkeyed.contains(plan)
+    }
+
+    /// Adds a line at the current depth.
+    pub fn line(&mut self, text: String) {
+        self.lines.push(format!("{}{}", "  ".repeat(self.depth), text));
+    }
+
+    /// Describes the lines of `logic` nested under the most recent line.
+    pub fn nested<F: FnOnce(&mut Self)>(&mut self, logic: F) {
+        self.depth += 1;
+        logic(self);
+        self.depth -= 1;
+    }
+
+    /// Describes an arrangement of `plan` by the values at `keys`, reused or built.
+    ///
+    /// This mirrors the choice `Plan::arrange_keyed` makes when rendering.
+    pub fn arrange_keyed(&mut self, plan: &Plan<V>, keys: &[usize]) {
+        if self.contains_keyed(plan, keys) {
+            self.line(format!("Arrangement by {:?} of {} (reused)", keys, operator(plan)));
+        }
+        else {
+            self.line(format!("Arrange by {:?} (new), exchanging by columns {:?}", keys, keys));
+            self.nested(|explainer| explainer.plan(plan));
+            self.keyed.insert((plan.clone(), keys.to_vec()));
+        }
+    }
+
+    /// Describes an arrangement of the records of `plan`, reused or built.
+    ///
+    /// This mirrors the choice `Plan::arrange_unkeyed` makes when rendering.
+    pub fn arrange_unkeyed(&mut self, plan: &Plan<V>) {
+        if self.contains_unkeyed(plan) {
+            self.line(format!("Arrangement of {} (reused)", operator(plan)));
+        }
+        else {
+            self.line("Arrange unkeyed (new), exchanging by record".to_string());
+            self.nested(|explainer| explainer.plan(plan));
+            self.unkeyed.insert(plan.clone());
+        }
+    }
+
+    /// Describes the operators rendering `plan` would construct.
+    pub fn plan(&mut self, plan: &Plan<V>) {
+
+        if !self.described.insert(plan.clone()) {
+            self.line(format!("{} (shared with above)", operator(plan)));
+            return;
+        }
+
+        match plan {
+            Plan::Map(map) => {
+                self.line(format!("Map {:?}", map.expressions));
+                self.nested(|explainer| explainer.plan(&map.plan));
+            },
+            Plan::Distinct(distinct) => {
+                if self.contains_unkeyed(plan) {
+                    self.line("Distinct of its own arrangement (reused)".to_string());
+                }
+                else {
+                    self.line("Distinct".to_string());
+                    self.nested(|explainer| explainer.arrange_unkeyed(distinct));
+                }
+                self.unkeyed.insert(plan.clone());
+            },
+            Plan::Concat(concat) => {
+                self.line("Concat".to_string());
+                self.nested(|explainer| for plan in concat.iter() { explainer.plan(plan); });
+            },
+            Plan::Consolidate(consolidate) => {
+                if self.contains_unkeyed(plan) {
+                    self.line("Consolidate from arrangement (reused)".to_string());
+                }
+                else {
+                    self.line("Consolidate, exchanging by record".to_string());
+                    self.nested(|explainer| explainer.plan(consolidate));
+                }
+            },
+            Plan::Join(join) => {
+                self.line(format!("Join on {:?}", join.keys));
+                let keys1 = join.keys.iter().map(|key| key.0).collect::<Vec<_>>();
+                let keys2 = join.keys.iter().map(|key| key.1).collect::<Vec<_>>();
+                self.nested(|explainer| {
+                    explainer.arrange_keyed(&join.plan1, &keys1[..]);
+                    explainer.arrange_keyed(&join.plan2, &keys2[..]);
+                });
+            },
+            Plan::MultiwayJoin(join) => join.explain(self),
+            Plan::Negate(negate) => {
+                self.line("Negate".to_string());
+                self.nested(|explainer| explainer.plan(negate));
+            },
+            Plan::Filter(filter) => {
+                self.line(format!("Filter {:?}", filter.predicate));
+                self.nested(|explainer| explainer.plan(&filter.plan));
+            },
+            Plan::Reduce(reduce) => {
+                let output_keys = (0 .. reduce.keys.len()).collect::<Vec<_>>();
+                if self.contains_keyed(plan, &output_keys[..]) {
+                    self.line(format!("Reduce by {:?} with {:?} from arrangement (reused)", reduce.keys, reduce.aggregates));
+                }
+                else {
+                    self.line(format!("Reduce by {:?} with {:?}", reduce.keys, reduce.aggregates));
+                    self.nested(|explainer| explainer.arrange_keyed(&reduce.plan, &reduce.keys[..]));
+                    self.keyed.insert((plan.clone(), output_keys));
+                }
+            },
+            Plan::Iterate(iterate) => {
+                self.line(format!("Iterate to {:?}", iterate.result));
+                let variables = std::mem::replace(&mut self.variables, iterate.rules.iter().map(|rule| rule.name.clone()).collect());
+                self.nested(|explainer| {
+                    for rule in iterate.rules.iter() {
+                        explainer.line(format!("Variable {:?}:", rule.name));
+                        explainer.nested(|explainer| explainer.recursive(&rule.plan));
+                    }
+                });
+                self.variables = variables;
+                for rule in iterate.rules.iter() {
+                    self.described.insert(Plan::Iterate(crate::plan::Iterate { rules: iterate.rules.clone(), result: rule.name.clone() }));
+                }
+            },
+            Plan::Source(name) => {
+                if self.contains_unkeyed(plan) {
+                    self.line(format!("Source {:?} from arrangement (reused)", name));
+                }
+                else {
+                    self.line(format!("Source {:?} not found", name));
+                }
+            },
+            Plan::Inspect(text, plan) => {
+                self.line(format!("Inspect {:?}", text));
+                self.nested(|explainer| explainer.plan(plan));
+            },
+        }
+    }
+
+    /// Describes the operators rendering `plan` within a loop would construct.
+    ///
+    /// Parts of the plan that do not refer to loop variables are rendered outside the loop.
+    fn recursive(&mut self, plan: &Plan<V>) {
+
+        if !plan.sources().iter().any(|name| self.variables.contains(name)) {
+            self.line("Enter loop".to_string());
+            self.nested(|explainer| explainer.plan(plan));
+            return;
+        }
+
+        match plan {
+            Plan::Map(map) => {
+                self.line(format!("Map {:?}", map.expressions));
+                self.nested(|explainer| explainer.recursive(&map.plan));
+            },
+            Plan::Distinct(distinct) => {
+                self.line("Distinct in loop, exchanging by record".to_string());
+                self.nested(|explainer| explainer.recursive(distinct));
+            },
+            Plan::Concat(concat) => {
+                self.line("Concat".to_string());
+                self.nested(|explainer| for plan in concat.iter() { explainer.recursive(plan); });
+            },
+            Plan::Consolidate(consolidate) => {
+                self.line("Consolidate in loop, exchanging by record".to_string());
+                self.nested(|explainer| explainer.recursive(consolidate));
+            },
+            Plan::Join(join) => {
+                self.line(format!("Join on {:?}", join.keys));
+                let keys1 = join.keys.iter().map(|key| key.0).collect::<Vec<_>>();
+                let keys2 = join.keys.iter().map(|key| key.1).collect::<Vec<_>>();
+                self.nested(|explainer| {
+                    explainer.arrange_loop(&join.plan1, &keys1[..]);
+                    explainer.arrange_loop(&join.plan2, &keys2[..]);
+                });
+            },
+            Plan::MultiwayJoin(join) => {
+                self.line("MultiwayJoin as binary joins".to_string());
+                self.nested(|explainer| explainer.recursive(&join.binary_joins()));
+            },
+            Plan::Negate(negate) => {
+                self.line("Negate".to_string());
+                self.nested(|explainer| explainer.recursive(negate));
+            },
+            Plan::Filter(filter) => {
+                self.line(format!("Filter {:?}", filter.predicate));
+                self.nested(|explainer| explainer.recursive(&filter.plan));
+            },
+            Plan::Reduce(reduce) => {
+                self.line(format!("Reduce by {:?} with {:?} in loop, exchanging by columns {:?}", reduce.keys, reduce.aggregates, reduce.keys));
+                self.nested(|explainer| explainer.recursive(&reduce.plan));
+            },
+            Plan::Iterate(_) => {
+                self.line("Iterate is not supported within recursive rules".to_string());
+            },
+            Plan::Source(name) => {
+                self.line(format!("Variable {:?}", name));
+            },
+            Plan::Inspect(text, plan) => {
+                self.line(format!("Inspect {:?}", text));
+                self.nested(|explainer| explainer.recursive(plan));
+            },
+        }
+    }
+
+    /// Describes an arrangement joined within a loop.
+    ///
+    /// Arrangements of recursive plans are built in the loop, and others are drawn from,
+    /// or installed in, the trace manager and entered into the loop.
+    fn arrange_loop(&mut self, plan: &Plan<V>, keys: &[usize]) {
+        if plan.sources().iter().any(|name| self.variables.contains(name)) {
+            self.line(format!("Arrange by {:?} in loop (new), exchanging by columns {:?}", keys, keys));
+            self.nested(|explainer| explainer.recursive(plan));
+        }
+        else {
+            self.line("Enter loop".to_string());
+            self.nested(|explainer| explainer.arrange_keyed(plan, keys));
+        }
+    }
+}
+
+/// A brief name for the root operator of `plan`.
+fn operator<V: Datum>(plan: &Plan<V>) -> String {
+    match plan {
+        Plan::Map(_) => "Map".to_string(),
+        Plan::Distinct(_) => "Distinct".to_string(),
+        Plan::Concat(_) => "Concat".to_string(),
+        Plan::Consolidate(_) => "Consolidate".to_string(),
+        Plan::Join(_) => "Join".to_string(),
+        Plan::MultiwayJoin(_) => "MultiwayJoin".to_string(),
+        Plan::Negate(_) => "Negate".to_string(),
+        Plan::Filter(_) => "Filter".to_string(),
+        Plan::Reduce(_) => "Reduce".to_string(),
+        Plan::Iterate(iterate) => format!("Iterate to {:?}", iterate.result),
+        Plan::Source(name) => format!("Source {:?}", name),
+        Plan::Inspect(text, _) => format!("Inspect {:?}", text),
+    }
+}
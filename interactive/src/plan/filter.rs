@@ -30,8 +30,11 @@ impl<Value> SecondArgument<Value> {
 }
 
 /// Possible predicates to apply.
+///
+/// Besides the built-in comparisons, a predicate may be any expression of the datum type,
+/// which is satisfied when `Datum::satisfies` says so.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub enum Predicate<Value> {
+pub enum Predicate<Value: Datum> {
     /// Strictly less than.
     LessThan(usize, SecondArgument<Value>),
     /// Less than or equal.
@@ -50,10 +53,15 @@ pub enum Predicate<Value> {
     All(Vec<Predicate<Value>>),
     /// The complement of a predicate.
     Not(Box<Predicate<Value>>),
+    /// An expression, evaluated against the values.
+    Expression(Value::Expression),
 }
 
-impl<Value: Ord> Predicate<Value> {
+impl<Value: Ord+Datum> Predicate<Value> {
     /// Indicates if the predicate is satisfied.
+    ///
+    /// Values are compared by their `Ord` implementation, which need not agree with the comparisons
+    /// made by the datum's expressions.
     pub fn satisfied(&self, values: &[Value]) -> bool {
         match self {
             Predicate::LessThan(index, other) => values[*index].lt(other.value(values)),
@@ -65,6 +73,7 @@ impl<Value: Ord> Predicate<Value> {
             Predicate::Any(predicates) => predicates.iter().any(|p| p.satisfied(values)),
             Predicate::All(predicates) => predicates.iter().all(|p| p.satisfied(values)),
             Predicate::Not(predicate) => !predicate.satisfied(values),
+            Predicate::Expression(expression) => Value::satisfies(values, expression),
         }
     }
 }
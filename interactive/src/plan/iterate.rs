@@ -0,0 +1,234 @@
+//! Fixed-point iteration plan.
+//!
+//! An `Iterate` plan contains rules that may refer to themselves and to each other
+//! by name. The rules are rendered in an iterative scope, where each rule name is
+//! bound to a `Variable` whose contents are repeatedly replaced by the rule's plan
+//! until no further changes occur.
+//!
+//! Parts of the rules that do not refer to any of the variables are rendered in the
+//! enclosing scope, where they may use and populate the `TraceManager`, and they are
+//! then brought into the loop using `enter`. This includes the non-recursive inputs
+//! to joins, whose arrangements are imported and entered rather than re-arranged.
+//! Recursive multiway joins are rendered as a sequence of binary joins.
+
+use std::hash::Hash;
+use std::collections::HashMap;
+
+use timely::dataflow::Scope;
+use timely::dataflow::scopes::Child;
+use timely::order::Product;
+
+use differential_dataflow::{Collection, ExchangeData};
+use differential_dataflow::operators::{Consolidate, JoinCore, Reduce, Threshold};
+use differential_dataflow::operators::arrange::{Arranged, ArrangeByKey};
+use differential_dataflow::operators::iterate::Variable;
+use differential_dataflow::trace::wrappers::enter::TraceEnter;
+
+use plan::{Plan, Render, split};
+use manager::{KeysValsHandle, TraceValHandle};
+use {TraceManager, Time, Diff, Datum, Rule};
+
+/// Mutually recursive rules, evaluated to their fixed point.
+///
+/// The rules should be written to converge, for example by applying `distinct`
+/// to any rule whose derivations might otherwise accumulate without bound.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Iterate<V: Datum> {
+    /// Rules which may refer to each other, and to themselves, by name.
+    pub rules: Vec<Rule<V>>,
+    /// The name of the rule whose contents the plan produces.
+    pub result: String,
+}
+
+impl<V: ExchangeData+Hash+Datum> Render for Iterate<V> {
+
+    type Value = V;
+
+    fn render<S: Scope<Timestamp = Time>>(
+        &self,
+        scope: &mut S,
+        collections: &mut std::collections::HashMap<Plan<Self::Value>, Collection<S, Vec<Self::Value>, Diff>>,
+        arrangements: &mut TraceManager<Self::Value>,
+    ) -> Collection<S, Vec<Self::Value>, Diff>
+    {
+        let results = scope.clone().iterative::<u64,_,_>(|inner| {
+
+            let variables =
+            self.rules
+                .iter()
+                .map(|rule| (rule.name.clone(), Variable::new(inner, Product::new(Default::default(), 1))))
+                .collect::<HashMap<_,_>>();
+
+            let mut context = Context {
+                scope,
+                inner: inner.clone(),
+                variables,
+                collections,
+                inner_collections: HashMap::new(),
+                arrangements,
+            };
+
+            let rendered =
+            self.rules
+                .iter()
+                .map(|rule| (rule.name.clone(), context.render(&rule.plan)))
+                .collect::<Vec<_>>();
+
+            let mut variables = context.variables;
+            rendered
+                .into_iter()
+                .map(|(name, collection)| {
+                    let variable = variables.remove(&name).expect("Variable not found");
+                    (name, variable.set(&collection).leave())
+                })
+                .collect::<Vec<_>>()
+        });
+
+        // Each rule's results are available to other plans naming the same rules.
+        let mut output = None;
+        for (name, collection) in results.into_iter() {
+            if name == self.result {
+                output = Some(collection.clone());
+            }
+            let plan = Plan::Iterate(Iterate { rules: self.rules.clone(), result: name });
+            collections.insert(plan, collection);
+        }
+
+        output.expect(&format!("Failed to find result rule: {:?}", self.result))
+    }
+}
+
+/// A loop body under construction, and the resources needed to render into it.
+struct Context<'a, 'b, S: Scope<Timestamp = Time>+'a, V: ExchangeData+Hash+Datum> {
+    /// The scope enclosing the loop.
+    scope: &'b mut S,
+    /// The iterative scope of the loop.
+    inner: Child<'a, S, Product<Time, u64>>,
+    /// Variables bound to rule names.
+    variables: HashMap<String, Variable<Child<'a, S, Product<Time, u64>>, Vec<V>, Diff>>,
+    /// Collections rendered in the enclosing scope.
+    collections: &'b mut HashMap<Plan<V>, Collection<S, Vec<V>, Diff>>,
+    /// Collections rendered in the loop.
+    inner_collections: HashMap<Plan<V>, Collection<Child<'a, S, Product<Time, u64>>, Vec<V>, Diff>>,
+    /// Arrangements available in the enclosing scope.
+    arrangements: &'b mut TraceManager<V>,
+}
+
+impl<'a, 'b, S: Scope<Timestamp = Time>+'a, V: ExchangeData+Hash+Datum> Context<'a, 'b, S, V> {
+
+    /// Indicates whether the plan refers to any of the loop variables.
+    fn is_recursive(&self, plan: &Plan<V>) -> bool {
+        plan.sources().iter().any(|name| self.variables.contains_key(name))
+    }
+
+    /// Renders a plan in the loop.
+    fn render(&mut self, plan: &Plan<V>) -> Collection<Child<'a, S, Product<Time, u64>>, Vec<V>, Diff> {
+
+        if let Some(collection) = self.inner_collections.get(plan) {
+            return collection.clone();
+        }
+
+        let collection =
+        if !self.is_recursive(plan) {
+            plan.render(self.scope, self.collections, self.arrangements)
+                .enter(&self.inner)
+        }
+        else {
+            match plan {
+                Plan::Map(map) => {
+                    let expressions = map.expressions.clone();
+                    self.render(&map.plan)
+                        .map(move |tuple|
+                            expressions
+                                .iter()
+                                .map(|expr| V::subject_to(&tuple[..], expr))
+                                .collect()
+                        )
+                },
+                Plan::Distinct(distinct) => self.render(distinct).distinct(),
+                Plan::Concat(concat) => {
+                    let plans = concat.iter().map(|plan| self.render(plan)).collect::<Vec<_>>();
+                    differential_dataflow::collection::concatenate(&mut self.inner, plans)
+                },
+                Plan::Consolidate(consolidate) => self.render(consolidate).consolidate(),
+                Plan::Join(join) => {
+                    let keys1 = join.keys.iter().map(|key| key.0).collect::<Vec<_>>();
+                    let keys2 = join.keys.iter().map(|key| key.1).collect::<Vec<_>>();
+                    match (self.is_recursive(&join.plan1), self.is_recursive(&join.plan2)) {
+                        (true, true) => {
+                            let arrange1 = self.arrange(&join.plan1, keys1);
+                            let arrange2 = self.arrange(&join.plan2, keys2);
+                            arrange1.join_core(&arrange2, concat_join)
+                        },
+                        (true, false) => {
+                            let arrange1 = self.arrange(&join.plan1, keys1);
+                            let arrange2 = self.arrange_outer(&join.plan2, keys2);
+                            arrange1.join_core(&arrange2, concat_join)
+                        },
+                        (false, _) => {
+                            let arrange1 = self.arrange_outer(&join.plan1, keys1);
+                            let arrange2 = self.arrange(&join.plan2, keys2);
+                            arrange1.join_core(&arrange2, concat_join)
+                        },
+                    }
+                },
+                Plan::MultiwayJoin(join) => self.render(&join.binary_joins()),
+                Plan::Negate(negate) => self.render(negate).negate(),
+                Plan::Filter(filter) => {
+                    let predicate = filter.predicate.clone();
+                    self.render(&filter.plan)
+                        .filter(move |tuple| predicate.satisfied(tuple))
+                },
+                Plan::Reduce(reduce) => {
+                    self.render(&reduce.plan)
+                        .map(split(reduce.keys.clone()))
+                        .reduce(reduce.logic())
+                        .map(|(keys, vals)| keys.into_iter().chain(vals.into_iter()).collect())
+                },
+                Plan::Iterate(_) => {
+                    // Queries containing such plans are rejected by `Plan::validate`.
+                    panic!("Iterate is not supported within recursive rules: {:?}", plan);
+                },
+                Plan::Source(name) => (*self.variables[name]).clone(),
+                Plan::Inspect(text, plan) => {
+                    let text = text.clone();
+                    self.render(plan)
+                        .inspect(move |x| println!("{}\t{:?}", text, x))
+                },
+            }
+        };
+
+        self.inner_collections.insert(plan.clone(), collection.clone());
+        collection
+    }
+
+    /// Arranges a recursive plan by the values at `keys`, within the loop.
+    fn arrange(&mut self, plan: &Plan<V>, keys: Vec<usize>)
+        -> Arranged<Child<'a, S, Product<Time, u64>>, TraceValHandle<Vec<V>, Vec<V>, Product<Time, u64>, Diff>>
+    {
+        self.render(plan)
+            .map(split(keys))
+            .arrange_by_key()
+    }
+
+    /// Arranges a non-recursive plan by the values at `keys`, and enters it into the loop.
+    ///
+    /// The arrangement is drawn from, or installed in, the trace manager.
+    fn arrange_outer(&mut self, plan: &Plan<V>, keys: Vec<usize>)
+        -> Arranged<Child<'a, S, Product<Time, u64>>, TraceEnter<KeysValsHandle<V>, Product<Time, u64>>>
+    {
+        plan.arrange_keyed(self.scope, self.collections, self.arrangements, &keys[..])
+            .import(self.scope)
+            .enter(&self.inner)
+    }
+}
+
+/// Forms joined tuples from keys followed by the values of each input.
+fn concat_join<V: Clone>(keys: &Vec<V>, vals1: &Vec<V>, vals2: &Vec<V>) -> Option<Vec<V>> {
+    Some(
+        keys.iter().cloned()
+            .chain(vals1.iter().cloned())
+            .chain(vals2.iter().cloned())
+            .collect()
+    )
+}
@@ -34,65 +34,13 @@ impl<V: ExchangeData+Hash+Datum> Render for Join<V> {
         arrangements: &mut TraceManager<Self::Value>,
     ) -> Collection<S, Vec<Self::Value>, Diff>
     {
-        use differential_dataflow::operators::arrange::ArrangeByKey;
-
         // acquire arrangements for each input.
         let keys1 = self.keys.iter().map(|key| key.0).collect::<Vec<_>>();
-        let mut trace1 =
-        if let Some(arrangement) = arrangements.get_keyed(&self.plan1, &keys1[..]) {
-            arrangement
-        }
-        else {
-            let keys = keys1.clone();
-            let arrangement =
-            self.plan1
-                .render(scope, collections, arrangements)
-                .map(move |tuple|
-                    (
-                        // TODO: Re-use `tuple` for values.
-                        keys.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>(),
-                        tuple
-                            .into_iter()
-                            .enumerate()
-                            .filter(|(index,_value)| !keys.contains(index))
-                            .map(|(_index,value)| value)
-                            .collect::<Vec<_>>(),
-                    )
-                )
-                .arrange_by_key();
-
-            arrangements.set_keyed(&self.plan1, &keys1[..], &arrangement.trace);
-            arrangement.trace
-        };
+        let mut trace1 = self.plan1.arrange_keyed(scope, collections, arrangements, &keys1[..]);
 
         // extract relevant fields for each index.
         let keys2 = self.keys.iter().map(|key| key.1).collect::<Vec<_>>();
-        let mut trace2 =
-        if let Some(arrangement) = arrangements.get_keyed(&self.plan2, &keys2[..]) {
-            arrangement
-        }
-        else {
-            let keys = keys2.clone();
-            let arrangement =
-            self.plan2
-                .render(scope, collections, arrangements)
-                .map(move |tuple|
-                    (
-                        // TODO: Re-use `tuple` for values.
-                        keys.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>(),
-                        tuple
-                            .into_iter()
-                            .enumerate()
-                            .filter(|(index,_value)| !keys.contains(index))
-                            .map(|(_index,value)| value)
-                            .collect::<Vec<_>>(),
-                    )
-                )
-                .arrange_by_key();
-
-            arrangements.set_keyed(&self.plan2, &keys2[..], &arrangement.trace);
-            arrangement.trace
-        };
+        let mut trace2 = self.plan2.arrange_keyed(scope, collections, arrangements, &keys2[..]);
 
         let arrange1 = trace1.import(scope);
         let arrange2 = trace2.import(scope);
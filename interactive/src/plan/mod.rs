@@ -6,20 +6,27 @@ use timely::dataflow::Scope;
 use differential_dataflow::{Collection, ExchangeData};
 
 use {TraceManager, Time, Diff};
+use manager::{KeysOnlyHandle, KeysValsHandle};
 
 // pub mod count;
+pub mod explain;
 pub mod filter;
+pub mod iterate;
 pub mod join;
 pub mod map;
+pub mod reduce;
 pub mod sfw;
 
 use crate::Datum;
 
 // pub use self::count::Count;
+pub use self::explain::Explainer;
 pub use self::filter::{Filter, Predicate};
+pub use self::iterate::Iterate;
 pub use self::join::Join;
 pub use self::sfw::MultiwayJoin;
 pub use self::map::Map;
+pub use self::reduce::{Reduce, Aggregate};
 
 /// A type that can be rendered as a collection.
 pub trait Render : Sized {
@@ -58,6 +65,10 @@ pub enum Plan<V: Datum> {
     Negate(Box<Plan<V>>),
     /// Filters bindings by one of the built-in predicates
     Filter(Filter<V>),
+    /// Groups by key values and aggregates each group
+    Reduce(Reduce<V>),
+    /// Fixed point of mutually recursive rules
+    Iterate(Iterate<V>),
     /// Sources data from another relation.
     Source(String),
     /// Prints resulting updates.
@@ -120,6 +131,20 @@ impl<V: ExchangeData+Hash+Datum> Plan<V> {
     pub fn filter(self, predicate: Predicate<V>) -> Self {
         Plan::Filter(Filter { predicate, plan: Box::new(self) } )
     }
+    /// Groups tuples by the values at `keys`, and applies `aggregates` to each group.
+    ///
+    /// The results contain the key values followed by the result of each aggregate.
+    pub fn reduce(self, keys: Vec<usize>, aggregates: Vec<Aggregate>) -> Self {
+        Plan::Reduce(Reduce { keys, aggregates, plan: Box::new(self) })
+    }
+    /// Counts the tuples with each distinct set of values at `keys`.
+    pub fn count(self, keys: Vec<usize>) -> Self {
+        self.reduce(keys, vec![Aggregate::Count])
+    }
+    /// Produces the rule named `result` from the fixed point of mutually recursive `rules`.
+    pub fn iterate(rules: Vec<crate::Rule<V>>, result: &str) -> Self {
+        Plan::Iterate(Iterate { rules, result: result.to_string() })
+    }
     /// Loads a source of data by name.
     pub fn source(name: &str) -> Self {
         Plan::Source(name.to_string())
@@ -135,6 +160,66 @@ impl<V: ExchangeData+Hash+Datum> Plan<V> {
             plan: self,
         }
     }
+    /// Names of the sources the plan reads.
+    ///
+    /// Names bound by the rules of an `Iterate` plan are not sources of that plan.
+    pub fn sources(&self) -> std::collections::BTreeSet<String> {
+        let mut sources = std::collections::BTreeSet::new();
+        match self {
+            Plan::Map(map) => { sources.extend(map.plan.sources()); },
+            Plan::Distinct(plan) => { sources.extend(plan.sources()); },
+            Plan::Concat(plans) => { for plan in plans.iter() { sources.extend(plan.sources()); } },
+            Plan::Consolidate(plan) => { sources.extend(plan.sources()); },
+            Plan::Join(join) => {
+                sources.extend(join.plan1.sources());
+                sources.extend(join.plan2.sources());
+            },
+            Plan::MultiwayJoin(join) => { for plan in join.sources.iter() { sources.extend(plan.sources()); } },
+            Plan::Negate(plan) => { sources.extend(plan.sources()); },
+            Plan::Filter(filter) => { sources.extend(filter.plan.sources()); },
+            Plan::Reduce(reduce) => { sources.extend(reduce.plan.sources()); },
+            Plan::Iterate(iterate) => {
+                for rule in iterate.rules.iter() { sources.extend(rule.plan.sources()); }
+                for rule in iterate.rules.iter() { sources.remove(&rule.name); }
+            },
+            Plan::Source(name) => { sources.insert(name.clone()); },
+            Plan::Inspect(_, plan) => { sources.extend(plan.sources()); },
+        }
+        sources
+    }
+    /// Checks that the plan can be rendered, describing the first problem found otherwise.
+    ///
+    /// An `Iterate` plan may not refer to the rules of an enclosing `Iterate` plan, as loops
+    /// are not rendered within loops.
+    pub fn validate(&self) -> Result<(), String> {
+        self.validate_within(&std::collections::BTreeSet::new())
+    }
+    /// Checks the plan as part of the rules of a loop that binds `variables`.
+    fn validate_within(&self, variables: &std::collections::BTreeSet<String>) -> Result<(), String> {
+        match self {
+            Plan::Map(map) => map.plan.validate_within(variables),
+            Plan::Distinct(plan) |
+            Plan::Consolidate(plan) |
+            Plan::Negate(plan) |
+            Plan::Inspect(_, plan) => plan.validate_within(variables),
+            Plan::Concat(plans) => plans.iter().try_for_each(|plan| plan.validate_within(variables)),
+            Plan::Join(join) => {
+                join.plan1.validate_within(variables)?;
+                join.plan2.validate_within(variables)
+            },
+            Plan::MultiwayJoin(join) => join.sources.iter().try_for_each(|plan| plan.validate_within(variables)),
+            Plan::Filter(filter) => filter.plan.validate_within(variables),
+            Plan::Reduce(reduce) => reduce.plan.validate_within(variables),
+            Plan::Iterate(iterate) => {
+                if let Some(name) = self.sources().iter().find(|name| variables.contains(*name)) {
+                    return Err(format!("Iterate is not supported within recursive rules: {:?} refers to {:?}", iterate.result, name));
+                }
+                let names = iterate.rules.iter().map(|rule| rule.name.clone()).collect();
+                iterate.rules.iter().try_for_each(|rule| rule.plan.validate_within(&names))
+            },
+            Plan::Source(_) => Ok(()),
+        }
+    }
 }
 
 impl<V: ExchangeData+Hash+Datum> Render for Plan<V> {
@@ -157,7 +242,6 @@ impl<V: ExchangeData+Hash+Datum> Render for Plan<V> {
                 Plan::Distinct(distinct) => {
 
                     use differential_dataflow::operators::reduce::ReduceCore;
-                    use differential_dataflow::operators::arrange::ArrangeBySelf;
                     use differential_dataflow::trace::implementations::ord::OrdKeySpine;
 
                     let input =
@@ -165,9 +249,7 @@ impl<V: ExchangeData+Hash+Datum> Render for Plan<V> {
                         trace.import(scope)
                     }
                     else {
-                        let input_arrangement = distinct.render(scope, collections, arrangements).arrange_by_self();
-                        arrangements.set_unkeyed(&distinct, &input_arrangement.trace);
-                        input_arrangement
+                        distinct.arrange_unkeyed(scope, collections, arrangements).import(scope)
                     };
 
                     let output = input.reduce_abelian::<_,OrdKeySpine<_,_,_>>("Distinct", move |_,_,t| t.push(((), 1)));
@@ -206,6 +288,8 @@ impl<V: ExchangeData+Hash+Datum> Render for Plan<V> {
                     negate.render(scope, collections, arrangements).negate()
                 },
                 Plan::Filter(filter) => filter.render(scope, collections, arrangements),
+                Plan::Reduce(reduce) => reduce.render(scope, collections, arrangements),
+                Plan::Iterate(iterate) => iterate.render(scope, collections, arrangements),
                 Plan::Source(source) => {
                     arrangements
                         .get_unkeyed(self)
@@ -226,3 +310,71 @@ impl<V: ExchangeData+Hash+Datum> Render for Plan<V> {
         collections.get(self).expect("We just installed this").clone()
     }
 }
+
+impl<V: ExchangeData+Hash+Datum> Plan<V> {
+
+    /// An arrangement of the records of the plan, drawn from `arrangements` if present,
+    /// and otherwise rendered and installed there.
+    ///
+    /// `Explainer::arrange_unkeyed` describes the same choice.
+    pub fn arrange_unkeyed<S: Scope<Timestamp = Time>>(
+        &self,
+        scope: &mut S,
+        collections: &mut std::collections::HashMap<Plan<V>, Collection<S, Vec<V>, Diff>>,
+        arrangements: &mut TraceManager<V>,
+    ) -> KeysOnlyHandle<V>
+    {
+        use differential_dataflow::operators::arrange::ArrangeBySelf;
+
+        if let Some(trace) = arrangements.get_unkeyed(self) {
+            trace
+        }
+        else {
+            let arrangement = self.render(scope, collections, arrangements).arrange_by_self();
+            arrangements.set_unkeyed(self, &arrangement.trace);
+            arrangement.trace
+        }
+    }
+
+    /// An arrangement of the plan by the values at `keys`, with the remaining values in
+    /// order, drawn from `arrangements` if present, and otherwise rendered and installed there.
+    ///
+    /// `Explainer::arrange_keyed` describes the same choice.
+    pub fn arrange_keyed<S: Scope<Timestamp = Time>>(
+        &self,
+        scope: &mut S,
+        collections: &mut std::collections::HashMap<Plan<V>, Collection<S, Vec<V>, Diff>>,
+        arrangements: &mut TraceManager<V>,
+        keys: &[usize],
+    ) -> KeysValsHandle<V>
+    {
+        use differential_dataflow::operators::arrange::ArrangeByKey;
+
+        if let Some(trace) = arrangements.get_keyed(self, keys) {
+            trace
+        }
+        else {
+            let arrangement =
+            self.render(scope, collections, arrangements)
+                .map(split(keys.to_vec()))
+                .arrange_by_key();
+
+            arrangements.set_keyed(self, keys, &arrangement.trace);
+            arrangement.trace
+        }
+    }
+}
+
+/// Splits tuples into the values at `keys` and the remaining values, in order.
+fn split<V: Clone>(keys: Vec<usize>) -> impl Fn(Vec<V>) -> (Vec<V>, Vec<V>) {
+    move |tuple|
+    (
+        keys.iter().map(|index| tuple[*index].clone()).collect::<Vec<_>>(),
+        tuple
+            .into_iter()
+            .enumerate()
+            .filter(|(index,_value)| !keys.contains(index))
+            .map(|(_index,value)| value)
+            .collect::<Vec<_>>(),
+    )
+}
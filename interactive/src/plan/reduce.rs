@@ -0,0 +1,153 @@
+//! Grouping and aggregation expression plan.
+
+use std::hash::Hash;
+
+use timely::dataflow::Scope;
+
+use differential_dataflow::operators::reduce::ReduceCore;
+use differential_dataflow::trace::implementations::ord::OrdValSpine;
+
+use differential_dataflow::{Collection, ExchangeData};
+use plan::{Plan, Render};
+use {TraceManager, Time, Diff, Datum};
+
+/// Aggregate functions applied to the records of a group.
+///
+/// Indices refer to positions in the records of the input plan.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Aggregate {
+    /// The number of records in the group.
+    Count,
+    /// The sum of the values at an index.
+    Sum(usize),
+    /// The least value at an index.
+    Min(usize),
+    /// The greatest value at an index.
+    Max(usize),
+    /// The value at the second index of the record with the greatest value at the first index.
+    ArgMax(usize, usize),
+}
+
+/// A plan stage grouping source tuples by the values at the specified
+/// indices, and producing for each group its key values followed by
+/// the results of each aggregate.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Reduce<V: Datum> {
+    /// Indices of the values by which tuples are grouped.
+    pub keys: Vec<usize>,
+    /// Aggregates to produce for each group.
+    pub aggregates: Vec<Aggregate>,
+    /// Plan for the data source.
+    pub plan: Box<Plan<V>>,
+}
+
+/// The location of an input value once its tuple is split into keys and values.
+#[derive(Clone, Copy)]
+enum Column {
+    Key(usize),
+    Val(usize),
+}
+
+impl Column {
+    /// Locates `index` in a tuple arranged by the values at `keys`.
+    fn locate(keys: &[usize], index: usize) -> Self {
+        if let Some(position) = keys.iter().position(|key| *key == index) {
+            Column::Key(position)
+        }
+        else {
+            Column::Val(index - keys.iter().filter(|key| **key < index).count())
+        }
+    }
+    /// Extracts the located value.
+    fn get<'a, V>(&self, key: &'a [V], val: &'a [V]) -> &'a V {
+        match self {
+            Column::Key(position) => &key[*position],
+            Column::Val(position) => &val[*position],
+        }
+    }
+}
+
+/// An aggregate with its indices located in split tuples.
+enum Located {
+    Count,
+    Sum(Column),
+    Min(Column),
+    Max(Column),
+    ArgMax(Column, Column),
+}
+
+impl<V: ExchangeData+Hash+Datum> Reduce<V> {
+    /// Produces reduction logic for input tuples split into grouping keys and remaining values.
+    pub fn logic(&self) -> impl FnMut(&Vec<V>, &[(&Vec<V>, Diff)], &mut Vec<(Vec<V>, Diff)>)+'static {
+
+        // Locate the values each aggregate reads, once split into keys and values.
+        let aggregates =
+        self.aggregates
+            .iter()
+            .map(|aggregate| match aggregate {
+                Aggregate::Count => Located::Count,
+                Aggregate::Sum(index) => Located::Sum(Column::locate(&self.keys, *index)),
+                Aggregate::Min(index) => Located::Min(Column::locate(&self.keys, *index)),
+                Aggregate::Max(index) => Located::Max(Column::locate(&self.keys, *index)),
+                Aggregate::ArgMax(index, result) => Located::ArgMax(Column::locate(&self.keys, *index), Column::locate(&self.keys, *result)),
+            })
+            .collect::<Vec<_>>();
+
+        move |key, input, output| {
+            let results =
+            aggregates
+                .iter()
+                .map(|aggregate| match aggregate {
+                    Located::Count => V::count(input.iter().map(|(_, count)| *count).sum()),
+                    Located::Sum(column) => V::sum(input.iter().map(|(val, count)| (column.get(key, val), *count))),
+                    Located::Min(column) => input.iter().map(|(val, _)| column.get(key, val)).min().expect("Non-empty input").clone(),
+                    Located::Max(column) => input.iter().map(|(val, _)| column.get(key, val)).max().expect("Non-empty input").clone(),
+                    Located::ArgMax(column, result) => {
+                        input
+                            .iter()
+                            .map(|(val, _)| (column.get(key, val), result.get(key, val)))
+                            .max()
+                            .expect("Non-empty input")
+                            .1
+                            .clone()
+                    },
+                })
+                .collect::<Vec<_>>();
+            output.push((results, 1));
+        }
+    }
+}
+
+impl<V: ExchangeData+Hash+Datum> Render for Reduce<V> {
+
+    type Value = V;
+
+    fn render<S: Scope<Timestamp = Time>>(
+        &self,
+        scope: &mut S,
+        collections: &mut std::collections::HashMap<Plan<Self::Value>, Collection<S, Vec<Self::Value>, Diff>>,
+        arrangements: &mut TraceManager<Self::Value>,
+    ) -> Collection<S, Vec<Self::Value>, Diff>
+    {
+        // The output is arranged by its leading key values, and may already exist.
+        let plan = Plan::Reduce(self.clone());
+        let output_keys = (0 .. self.keys.len()).collect::<Vec<_>>();
+        if let Some(mut trace) = arrangements.get_keyed(&plan, &output_keys[..]) {
+            return
+            trace
+                .import(scope)
+                .as_collection(|keys, vals| keys.iter().cloned().chain(vals.iter().cloned()).collect());
+        }
+
+        // acquire an arrangement of the input by the grouping keys.
+        let mut trace = self.plan.arrange_keyed(scope, collections, arrangements, &self.keys[..]);
+
+        let output =
+        trace
+            .import(scope)
+            .reduce_abelian::<_,OrdValSpine<_,_,_,_>>("Reduce", self.logic());
+
+        arrangements.set_keyed(&plan, &output_keys[..], &output.trace);
+        output.as_collection(|keys, vals| keys.iter().cloned().chain(vals.iter().cloned()).collect())
+    }
+}
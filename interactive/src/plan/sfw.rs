@@ -20,19 +20,31 @@
 //! current other input collections. For each input collection we may choose very
 //! different join orders, as the order must follow equality constraints.
 //!
-//! A further implementation could develop the results attribute-by-attribute, as
+//! A further implementation develops the results attribute-by-attribute, as
 //! opposed to collection-by-collection, which gives us the ability to use column
-//! indices rather than whole-collection indices.
+//! indices rather than whole-collection indices. This worst-case optimal approach
+//! is used when the equality constraints are cyclic, as for triangles, where any
+//! collection-by-collection order may produce many more intermediate results than
+//! final results.
+//!
+//! The join order for each delta query is chosen greedily, preferring relations with
+//! the fewest expected matches per change, and then relations whose arrangements already
+//! exist. Expected matches come from statistics the trace manager maintains for the sources
+//! and keyed arrangements of previously rendered joins; relations without statistics are
+//! ordered last, as if arbitrarily large.
 
 use std::hash::Hash;
+use std::collections::{BTreeSet, HashMap};
 
 use timely::dataflow::Scope;
 
 use differential_dataflow::operators::Consolidate;
-use differential_dataflow::operators::arrange::{ArrangeBySelf, ArrangeByKey};
+use differential_dataflow::operators::arrange::ArrangeByKey;
 
 use differential_dataflow::{Collection, ExchangeData};
-use plan::{Plan, Render};
+use plan::{Plan, Predicate, Render};
+use plan::filter::SecondArgument;
+use plan::explain::Explainer;
 use {TraceManager, Time, Diff, Datum};
 
 /// A multiway join of muliple relations.
@@ -54,14 +66,522 @@ pub struct MultiwayJoin<V: Datum> {
     pub equalities: Vec<Vec<(usize, usize)>>,
 }
 
-// TODO: This logic fails to perform restrictions in cases where a join does not
-//       occur. One example could be:
-//
-//              result(a,b,c) := R1(a,b), R2(b,c,c)
-//
-//       In this case, the requirement that the 2nd and 3rd columns of R2 be equal
-//       is not surfaced in any join, and is instead a filter that should be applied
-//       directly to R2 (before or after the join with R1; either could be best).
+impl<V: ExchangeData+Hash+Datum> MultiwayJoin<V> {
+
+    /// Sources restricted by the equality constraints among their own attributes.
+    ///
+    /// A constraint like that on the 2nd and 3rd columns of `R2` in
+    ///
+    ///     result(a,b,c) := R1(a,b), R2(b,c,c)
+    ///
+    /// need not surface in any join, and is instead applied as a filter to `R2`.
+    pub fn restricted_sources(&self) -> Vec<Plan<V>> {
+        self.sources
+            .iter()
+            .enumerate()
+            .map(|(index, plan)| {
+                let mut predicates = Vec::new();
+                for constraint in self.equalities.iter() {
+                    let mut attrs = constraint.iter().filter(|(_, input)| *input == index).map(|(attr, _)| *attr);
+                    if let Some(first) = attrs.next() {
+                        predicates.extend(attrs.map(|attr| Predicate::Equal(attr, SecondArgument::Position(first))));
+                    }
+                }
+                match predicates.len() {
+                    0 => plan.clone(),
+                    1 => plan.clone().filter(predicates.pop().expect("Predicate not found")),
+                    _ => plan.clone().filter(Predicate::All(predicates)),
+                }
+            })
+            .collect()
+    }
+
+    /// Attributes we may need from any and all relations, in order.
+    fn relevant_attributes(&self) -> Vec<(usize, usize)> {
+        let mut relevant_attributes = Vec::new();
+        relevant_attributes.extend(self.results.iter().cloned());
+        relevant_attributes.extend(self.equalities.iter().flat_map(|list| list.iter().cloned()));
+        relevant_attributes.sort();
+        relevant_attributes.dedup();
+        relevant_attributes
+    }
+
+    /// Indicates if the equality constraints among relations contain a cycle.
+    ///
+    /// This applies GYO reduction to the hypergraph whose vertices are equivalence classes
+    /// and whose edges are relations: vertices in only one edge are removed, as are edges
+    /// contained in other edges. The constraints are acyclic if one edge remains.
+    pub fn is_cyclic(&self) -> bool {
+
+        let mut edges =
+        (0 .. self.sources.len())
+            .map(|input| {
+                (0 .. self.equalities.len())
+                    .filter(|class| self.equalities[*class].iter().any(|(_, i)| *i == input))
+                    .collect::<BTreeSet<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for class in 0 .. self.equalities.len() {
+                if edges.iter().filter(|edge| edge.contains(&class)).count() == 1 {
+                    for edge in edges.iter_mut() {
+                        changed |= edge.remove(&class);
+                    }
+                }
+            }
+            let mut index = 0;
+            while index < edges.len() {
+                if (0 .. edges.len()).any(|other| other != index && edges[index].is_subset(&edges[other])) {
+                    edges.remove(index);
+                    changed = true;
+                }
+                else {
+                    index += 1;
+                }
+            }
+        }
+
+        edges.len() > 1
+    }
+
+    /// The keys, priors, and values with which relation `join_idx` joins a stream of `attributes`,
+    /// and the plan for the relation projected to its keys and then values.
+    fn join_step(
+        &self,
+        sources: &[Plan<V>],
+        relevant_attributes: &[(usize, usize)],
+        join_idx: usize,
+        attributes: &[(usize, usize)],
+    ) -> (Vec<usize>, Vec<usize>, Vec<(usize, usize)>, Plan<V>)
+    {
+        // To join a relation, we need to determine any constraints on
+        // attributes in common with prior relations. Any other values
+        // should be appended to tuples in `changes` with care taken to
+        // update `attributes`.
+        let (keys, priors) = determine_keys_priors(join_idx, &self.equalities, attributes);
+
+        // The fields in `sources[join_idx]` that should be values are those
+        // that are required output or participate in an equality constraint,
+        // but *WHICH ARE NOT* in `keys`.
+        let vals =
+        relevant_attributes
+            .iter()
+            .filter(|&(attr,index)| index == &join_idx && !keys.contains(&attr))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut projection = Vec::new();
+        for &attr in keys.iter() {
+            projection.push(attr);
+        }
+        for &(attr, _index) in vals.iter() {
+            projection.push(attr);
+        }
+        // TODO: Sort, to improve chances of re-use opportunities.
+        //       Requires understanding how attributes move to get the right
+        //       key selectors out though.
+        // projection.sort();
+        // projection.dedup(); // Should already be deduplicated, probably?
+
+        // Get a plan for the projection on to these few attributes.
+        let plan = sources[join_idx].clone().project(projection);
+
+        (keys, priors, vals, plan)
+    }
+
+    /// Sequences relations to join with changes to relation `index`.
+    ///
+    /// Each relation in the sequence has at least one attribute in common with a prior
+    /// relation. Among the relations that could be joined next, we prefer the one that
+    /// each change is expected to match the fewest records of, and then those whose
+    /// arrangements already exist and can be reused.
+    pub fn delta_order(&self, index: usize, arrangements: &TraceManager<V>) -> Vec<usize> {
+        self.delta_order_with(index, arrangements, |plan, keys| arrangements.contains_keyed(plan, keys))
+    }
+
+    /// Sequences relations as `delta_order`, where `exists` indicates which keyed arrangements exist.
+    fn delta_order_with<F>(&self, index: usize, arrangements: &TraceManager<V>, exists: F) -> Vec<usize>
+    where
+        F: Fn(&Plan<V>, &[usize]) -> bool,
+    {
+        let sources = self.restricted_sources();
+        let relevant_attributes = self.relevant_attributes();
+
+        let mut attributes =
+        relevant_attributes
+            .iter()
+            .filter(|(_attr, input)| input == &index)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut join_order = vec![index];
+        loop {
+            let next =
+            (0 .. sources.len())
+                .filter(|join_idx| !join_order.contains(join_idx))
+                .filter_map(|join_idx| {
+                    let (keys, _priors, vals, plan) = self.join_step(&sources, &relevant_attributes, join_idx, &attributes);
+                    if keys.is_empty() {
+                        None
+                    }
+                    else {
+                        let fanout = fanout(arrangements, &sources[join_idx], &plan, &keys);
+                        let reuse = exists(&plan, &keys);
+                        Some((join_idx, keys, vals, (fanout, reuse)))
+                    }
+                })
+                .min_by(|(_, _, _, (fanout1, reuse1)), (_, _, _, (fanout2, reuse2))| {
+                    fanout1
+                        .partial_cmp(fanout2)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                        .then(reuse2.cmp(reuse1))
+                });
+
+            if let Some((join_idx, keys, vals, _estimate)) = next {
+                attributes.extend(keys.into_iter().map(|x| (x, join_idx)));
+                attributes.extend(vals.into_iter());
+                join_order.push(join_idx);
+            }
+            else {
+                break;
+            }
+        }
+
+        join_order
+    }
+
+    /// Equivalence classes of attributes, and result attributes in no class.
+    ///
+    /// These are the variables bound one at a time by worst-case optimal joins.
+    fn variables(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut variables = self.equalities.clone();
+        for result in self.results.iter() {
+            if !variables.iter().any(|members| members.contains(result)) {
+                variables.push(vec![*result]);
+            }
+        }
+        variables
+    }
+
+    /// For each relation, the variables it binds and an attribute binding each.
+    fn bindings(&self, variables: &[Vec<(usize, usize)>]) -> Vec<Vec<(usize, usize)>> {
+        (0 .. self.sources.len())
+            .map(|input| {
+                let mut binding = Vec::new();
+                for (var, members) in variables.iter().enumerate() {
+                    if let Some((attr, _)) = members.iter().find(|(_, i)| *i == input) {
+                        binding.push((var, *attr));
+                    }
+                }
+                binding
+            })
+            .collect()
+    }
+
+    /// Sequences the variables that worst-case optimal joins bind after those of relation `index`.
+    ///
+    /// Each variable is reported with the other relations that propose and validate its values.
+    /// The next variable is that constrained by the most relations with bound variables.
+    pub fn extension_order(&self, index: usize) -> Vec<(usize, Vec<usize>)> {
+
+        let variables = self.variables();
+        let bindings = self.bindings(&variables);
+
+        let mut bound = bindings[index].iter().map(|(var, _)| *var).collect::<Vec<_>>();
+        let mut order = Vec::new();
+        loop {
+            let next =
+            (0 .. variables.len())
+                .filter(|var| !bound.contains(var))
+                .map(|var| {
+                    let inputs =
+                    (0 .. self.sources.len())
+                        .filter(|input| *input != index)
+                        .filter(|input| bindings[*input].iter().any(|(v, _)| *v == var))
+                        .filter(|input| bindings[*input].iter().any(|(v, _)| bound.contains(v)))
+                        .collect::<Vec<_>>();
+                    (var, inputs)
+                })
+                .filter(|(_, inputs)| !inputs.is_empty())
+                .max_by_key(|(var, inputs)| (inputs.len(), std::cmp::Reverse(*var)));
+
+            if let Some((var, inputs)) = next {
+                bound.push(var);
+                order.push((var, inputs));
+            }
+            else {
+                break;
+            }
+        }
+
+        order
+    }
+
+    /// Renders the join as worst-case optimal delta queries.
+    ///
+    /// Each delta query binds the attributes of changes to one relation, and then binds the
+    /// remaining equivalence classes one at a time, by proposing and validating values among
+    /// all relations that contain the class and some already bound class. Relations index
+    /// distinct bindings for this, so that a relation consulted for several classes does not
+    /// contribute its multiplicities more than once; each relation finally contributes its
+    /// multiplicities through a lookup on all of its bound classes.
+    fn render_extend<S: Scope<Timestamp = Time>>(
+        &self,
+        scope: &mut S,
+        collections: &mut HashMap<Plan<V>, Collection<S, Vec<V>, Diff>>,
+        arrangements: &mut TraceManager<V>,
+    ) -> Collection<S, Vec<V>, Diff>
+    {
+        use dogsdogsdogs::{CollectionIndex, PrefixExtender, ProposeExtensionMethod};
+        use dogsdogsdogs::altneu::AltNeu;
+        use differential_dataflow::operators::Threshold;
+
+        let variables = self.variables();
+        let bindings = self.bindings(&variables);
+
+        let sources =
+        self.restricted_sources()
+            .iter()
+            .map(|plan| plan.render(scope, collections, arrangements))
+            .collect::<Vec<_>>();
+
+        scope.clone().scoped::<AltNeu<_>,_,_>("ExtendRule", |inner| {
+
+            let sources = sources.iter().map(|source| source.enter(inner)).collect::<Vec<_>>();
+
+            // Indices of relations by the values of some variables, proposing values of others.
+            // Indices are shared among delta queries, and keyed by whether they use `neu` times.
+            let mut indices = HashMap::new();
+            let mut index_for = |input: usize, keys: &[usize], vals: &[usize], neu: bool, distinct: bool| {
+                indices
+                    .entry((input, keys.to_vec(), vals.to_vec(), neu, distinct))
+                    .or_insert_with(|| {
+                        let keys = keys.to_vec();
+                        let vals = vals.to_vec();
+                        let mut pairs =
+                        sources[input]
+                            .map(move |tuple| (
+                                keys.iter().map(|attr| tuple[*attr].clone()).collect::<Vec<_>>(),
+                                vals.iter().map(|attr| tuple[*attr].clone()).collect::<Vec<_>>(),
+                            ));
+                        if distinct {
+                            pairs = pairs.distinct();
+                        }
+                        if neu {
+                            pairs = pairs.delay(|time| AltNeu::neu(time.time.clone()));
+                        }
+                        CollectionIndex::<Vec<V>, Vec<V>, AltNeu<Time>, Diff>::index(&pairs)
+                    })
+                    .clone()
+            };
+
+            let mut accumulated_changes = Vec::new();
+
+            for index in 0 .. self.sources.len() {
+
+                // The variables bound by the prefix, in order.
+                let mut bound = bindings[index].iter().map(|(var, _)| *var).collect::<Vec<_>>();
+                let attrs = bindings[index].iter().map(|(_, attr)| *attr).collect::<Vec<_>>();
+
+                let mut prefixes =
+                sources[index]
+                    .map(move |tuple| attrs.iter().map(|attr| tuple[*attr].clone()).collect::<Vec<_>>());
+
+                for (var, inputs) in self.extension_order(index) {
+
+                    let mut extenders: Vec<Box<dyn PrefixExtender<_, Diff, Prefix=Vec<V>, Extension=Vec<V>>>> = Vec::new();
+                    for input in inputs {
+                        let keyed = bindings[input].iter().filter(|(v, _)| bound.contains(v)).cloned().collect::<Vec<_>>();
+                        let keys = keyed.iter().map(|(_, attr)| *attr).collect::<Vec<_>>();
+                        let vals = bindings[input].iter().filter(|(v, _)| *v == var).map(|(_, attr)| *attr).collect::<Vec<_>>();
+                        let positions =
+                        keyed
+                            .iter()
+                            .map(|(v, _)| bound.iter().position(|b| b == v).expect("Variable not bound"))
+                            .collect::<Vec<_>>();
+                        let collection_index = index_for(input, &keys, &vals, input > index, true);
+                        extenders.push(Box::new(collection_index.extend_using(move |prefix: &Vec<V>|
+                            positions.iter().map(|position| prefix[*position].clone()).collect::<Vec<_>>()
+                        )));
+                    }
+
+                    let mut extenders: Vec<&mut dyn PrefixExtender<_, Diff, Prefix=Vec<V>, Extension=Vec<V>>> =
+                    extenders.iter_mut().map(|extender| &mut **extender as _).collect();
+                    prefixes =
+                    prefixes
+                        .extend(&mut extenders[..])
+                        .map(|(mut prefix, extension)| { prefix.extend(extension.into_iter()); prefix });
+
+                    bound.push(var);
+                }
+
+                // Each other relation contributes its multiplicities, and restricts any prefixes
+                // whose bindings it was never consulted for.
+                for input in (0 .. self.sources.len()).filter(|input| *input != index) {
+                    let keys = bindings[input].iter().map(|(_, attr)| *attr).collect::<Vec<_>>();
+                    let positions =
+                    bindings[input]
+                        .iter()
+                        .map(|(v, _)| bound.iter().position(|b| b == v).expect("Variable not bound"))
+                        .collect::<Vec<_>>();
+                    let collection_index = index_for(input, &keys, &[], input > index, false);
+                    prefixes =
+                    prefixes
+                        .propose_using(&mut collection_index.extend_using(move |prefix: &Vec<V>|
+                            positions.iter().map(|position| prefix[*position].clone()).collect::<Vec<_>>()
+                        ))
+                        .map(|(prefix, _)| prefix);
+                }
+
+                // Extract `self.results` in order, using `bound`.
+                let extract_map =
+                self.results
+                    .iter()
+                    .map(|result| {
+                        let var = variables.iter().position(|members| members.contains(result)).expect("Variable not found");
+                        bound.iter().position(|b| *b == var).expect("Variable not bound")
+                    })
+                    .collect::<Vec<_>>();
+
+                accumulated_changes.push(
+                    prefixes.map(move |tuple| extract_map.iter().map(|&i| tuple[i].clone()).collect::<Vec<_>>())
+                );
+            }
+
+            differential_dataflow::collection::concatenate(inner, accumulated_changes.into_iter())
+                .leave()
+        })
+        .consolidate()
+    }
+
+    /// Describes the join as rendering would construct it, without rendering it.
+    pub fn explain(&self, explainer: &mut Explainer<V>) {
+
+        let sources = self.restricted_sources();
+
+        if self.is_cyclic() {
+            explainer.line(format!("MultiwayJoin (worst-case optimal) producing {:?}", self.results));
+            explainer.nested(|explainer| {
+                for plan in sources.iter() {
+                    explainer.plan(plan);
+                }
+                for index in 0 .. sources.len() {
+                    explainer.line(format!("Delta query for input {}:", index));
+                    explainer.nested(|explainer| {
+                        for (var, inputs) in self.extension_order(index) {
+                            explainer.line(format!("Extend variable {} from inputs {:?} (new indices), exchanging prefixes by bound variables", var, inputs));
+                        }
+                        let others = (0 .. sources.len()).filter(|other| *other != index).collect::<Vec<_>>();
+                        explainer.line(format!("Weigh by inputs {:?} (new indices), exchanging prefixes by their variables", others));
+                    });
+                }
+            });
+        }
+        else {
+            explainer.line(format!("MultiwayJoin (delta queries) producing {:?}", self.results));
+            let relevant_attributes = self.relevant_attributes();
+            explainer.nested(|explainer| {
+                for (index, plan) in sources.iter().enumerate() {
+
+                    explainer.arrange_unkeyed(plan);
+
+                    let join_order = self.delta_order_with(index, explainer.trace_manager(), |plan, keys| explainer.contains_keyed(plan, keys));
+                    explainer.line(format!("Delta query for input {} joins inputs {:?}:", index, &join_order[1..]));
+                    explainer.nested(|explainer| {
+                        let mut attributes =
+                        relevant_attributes
+                            .iter()
+                            .filter(|(_attr, input)| input == &index)
+                            .cloned()
+                            .collect::<Vec<_>>();
+
+                        for join_idx in join_order.into_iter().skip(1) {
+                            let (keys, priors, vals, plan) = self.join_step(&sources, &relevant_attributes, join_idx, &attributes[..]);
+                            explainer.line(format!("Propose from input {}, exchanging changes by columns {:?}", join_idx, priors));
+                            explainer.nested(|explainer| explainer.arrange_keyed(&plan, &keys[..]));
+                            attributes.extend(keys.into_iter().map(|x| (x, join_idx)));
+                            attributes.extend(vals.into_iter());
+                        }
+                    });
+                }
+            });
+        }
+    }
+
+    /// An equivalent plan of binary joins, for contexts without delta queries.
+    ///
+    /// Each source is projected to its relevant attributes, and the sources are then
+    /// joined one at a time in an order where each shares an equality constraint with
+    /// those before it. This materializes intermediate results, but can be rendered
+    /// anywhere a `Plan::Join` can, for example within recursive rules.
+    pub fn binary_joins(&self) -> Plan<V> {
+
+        // Attributes we may need from each relation, in order.
+        let mut relevant = vec![Vec::new(); self.sources.len()];
+        for &(attr, input) in self.results.iter().chain(self.equalities.iter().flat_map(|list| list.iter())) {
+            relevant[input].push(attr);
+        }
+        for attributes in relevant.iter_mut() {
+            attributes.sort();
+            attributes.dedup();
+        }
+
+        let sources = self.restricted_sources();
+        let join_order = plan_join_order(0, &self.equalities);
+        assert_eq!(join_order.len(), self.sources.len(), "MultiwayJoin sources are not connected: {:?}", self);
+
+        // The `(attr, input)` pairs present in each record of `plan`.
+        let first = join_order[0];
+        let mut attributes = relevant[first].iter().map(|attr| (*attr, first)).collect::<Vec<_>>();
+        let mut plan = sources[first].clone().project(relevant[first].clone());
+
+        for &index in join_order[1..].iter() {
+
+            // Pair a present attribute of each constraint with the attributes of `index`.
+            let mut keys = Vec::new();
+            for constraint in self.equalities.iter() {
+                if let Some(position) = attributes.iter().position(|x| constraint.contains(x)) {
+                    for &(attr, input) in constraint.iter().filter(|(_, input)| *input == index) {
+                        let other = relevant[input].iter().position(|a| *a == attr).expect("Attribute not found");
+                        keys.push((position, other));
+                    }
+                }
+            }
+
+            // Joined records are keys, then remaining values of each input in order.
+            let mut joined = keys.iter().map(|(position, _)| attributes[*position]).collect::<Vec<_>>();
+            joined.extend(attributes.iter().enumerate().filter(|(i, _)| !keys.iter().any(|k| k.0 == *i)).map(|(_, x)| *x));
+            joined.extend(relevant[index].iter().enumerate().filter(|(i, _)| !keys.iter().any(|k| k.1 == *i)).map(|(_, attr)| (*attr, index)));
+
+            plan = plan.join(sources[index].clone().project(relevant[index].clone()), keys);
+            attributes = joined;
+        }
+
+        // Results may have been consumed as keys, and are then found through their constraint.
+        let projection =
+        self.results
+            .iter()
+            .map(|result| {
+                attributes
+                    .iter()
+                    .position(|x| x == result)
+                    .or_else(|| {
+                        self.equalities
+                            .iter()
+                            .filter(|constraint| constraint.contains(result))
+                            .flat_map(|constraint| constraint.iter().filter_map(|x| attributes.iter().position(|y| y == x)))
+                            .next()
+                    })
+                    .expect("Result attribute not found")
+            })
+            .collect();
+
+        plan.project(projection)
+    }
+}
 
 impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
 
@@ -99,14 +619,16 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
         // This is done to avoid double counting updates; any concurrent changes will be
         // accounted for by the last relation for which there is a concurrent update.
 
-        // println!("{:?}", self);
+        // Cyclic constraints are better served by binding one attribute at a time.
+        if self.is_cyclic() {
+            return self.render_extend(scope, collections, arrangements);
+        }
+
+        // Sources restricted by equalities among their own attributes.
+        let sources = self.restricted_sources();
 
         // Attributes we may need from any and all relations.
-        let mut relevant_attributes = Vec::new();
-        relevant_attributes.extend(self.results.iter().cloned());
-        relevant_attributes.extend(self.equalities.iter().flat_map(|list| list.iter().cloned()));
-        relevant_attributes.sort();
-        relevant_attributes.dedup();
+        let relevant_attributes = self.relevant_attributes();
 
         // println!("Relevant attributes: {:?}", relevant_attributes);
 
@@ -114,7 +636,7 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
         let mut accumulated_changes = Vec::new();
 
         // For each participating relation, we build a delta query dataflow.
-        for (index, plan) in self.sources.iter().enumerate() {
+        for (index, plan) in sources.iter().enumerate() {
 
             // println!("building dataflow for relation {}", index);
 
@@ -130,19 +652,15 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
             // println!("\tinitial attributes: {:?}", attributes);
 
             // Ensure the plan is rendered and cached.
-            if arrangements.get_unkeyed(&plan).is_none() {
-                // println!("\tbuilding/caching source plan");
-                let collection = plan.render(scope, collections, arrangements);
-                arrangements.set_unkeyed(plan, &collection.arrange_by_self().trace);
-            }
-            else {
-                // println!("\tsource plan found");
-            }
+            let source =
+            plan.arrange_unkeyed(scope, collections, arrangements)
+                .import(scope);
+
+            // Statistics about the source inform join orders that fall back to its size.
+            arrangements.set_unkeyed_statistics(&plan, &source);
+
             let changes =
-            arrangements
-                .get_unkeyed(&plan)
-                .expect("Surely we just ensured this")
-                .import(scope)
+            source
                 .as_collection(|val,&()| val.clone())
                 .map(move |tuple| attributes_init.iter().map(|&(attr,_)|
                     tuple[attr].clone()).collect::<Vec<_>>()
@@ -157,7 +675,7 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
             // This is a sequence of relation identifiers, starting with `index`,
             // such that each has at least one attribute in common with a prior
             // relation, and so can be effectively joined.
-            let join_order = plan_join_order(index, &self.equalities);
+            let join_order = self.delta_order(index, arrangements);
             let mut join_plan = Vec::new();
 
             // println!("\tjoin order: {:?}", join_order);
@@ -165,48 +683,20 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
             // Skipping `index`, join in each relation in sequence.
             for join_idx in join_order.into_iter().skip(1) {
 
-                // To join a relation, we need to determine any constraints on
-                // attributes in common with prior relations. Any other values
-                // should be appended to tuples in `changes` with care taken to
-                // update `attributes`.
-                let (keys, priors) = determine_keys_priors(join_idx, &self.equalities, &attributes[..]);
-
-                // The fields in `sources[join_idx]` that should be values are those
-                // that are required output or participate in an equality constraint,
-                // but *WHICH ARE NOT* in `keys`.
-                let vals =
-                relevant_attributes
-                    .iter()
-                    .filter(|&(attr,index)| index == &join_idx && !keys.contains(&attr))
-                    .cloned()
-                    .collect::<Vec<_>>();
+                let (keys, priors, vals, plan) = self.join_step(&sources, &relevant_attributes, join_idx, &attributes[..]);
 
                 // println!("\tkeys: {:?}, priors: {:?}, vals: {:?}", keys, priors, vals);
 
-                let mut projection = Vec::new();
-                for &attr in keys.iter() {
-                    projection.push(attr);
-                }
-                for &(attr, _index) in vals.iter() {
-                    projection.push(attr);
-                }
-                // TODO: Sort, to improve chances of re-use opportunities.
-                //       Requires understanding how attributes move to get the right
-                //       key selectors out though.
-                // projection.sort();
-                // projection.dedup(); // Should already be deduplicated, probably?
-
-                // Get a plan for the projection on to these few attributes.
-                let plan = self.sources[join_idx].clone().project(projection);
-
                 if arrangements.get_keyed(&plan, &keys[..]).is_none() {
                     // println!("\tbuilding key: {:?}, plan: {:?}", keys, plan);
                     let keys_clone = keys.clone();
+                    let collection = plan.render(scope, collections, arrangements);
                     let arrangement =
-                    plan.render(scope, collections, arrangements)
+                    collection
                         .map(move |tuple| (keys_clone.iter().map(|&i| tuple[i].clone()).collect::<Vec<_>>(), tuple))
                         .arrange_by_key();
 
+                    arrangements.set_keyed_statistics(&plan, &keys[..], &arrangement);
                     arrangements.set_keyed(&plan, &keys[..], &arrangement.trace);
                 }
                 else {
@@ -249,19 +739,15 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
                     // tuple in the cursor.
                     changes =
                     if join_idx < index {
-                        let arrangement = trace.import(scope).enter_at(inner, |_,_,t| AltNeu::alt(t.clone()), unimplemented!());
+                        let arrangement = trace.import(scope).enter_at(inner, |_,_,t| AltNeu::alt(t.clone()), |time| time.time.clone());
                         dogsdogsdogs::operators::propose(&changes, arrangement, key_selector)
                     }
                     else {
-                        let arrangement = trace.import(scope).enter_at(inner, |_,_,t| AltNeu::neu(t.clone()), unimplemented!());
+                        let arrangement = trace.import(scope).enter_at(inner, |_,_,t| AltNeu::neu(t.clone()), |time| time.time.clone());
                         dogsdogsdogs::operators::propose(&changes, arrangement, key_selector)
                     }
                     .map(|(mut prefix, extensions)| { prefix.extend(extensions.into_iter()); prefix })
                     ;
-
-                    // TODO: Equality constraints strictly within a relation have the effect
-                    //       of "filtering" data, but they are ignored at the moment. We should
-                    //       check for these and do something about it.
                 }
 
                 // Extract `self.results` in order, using `attributes`.
@@ -301,6 +787,28 @@ impl<V: ExchangeData+Hash+Datum> Render for MultiwayJoin<V> {
     }
 }
 
+/// The expected number of records of `source` each change matches on `keys` of `plan`.
+///
+/// Without statistics for the keyed arrangement, we fall back to the number of records
+/// in `source`, and without those we expect the worst.
+fn fanout<V: ExchangeData+Hash+Datum>(
+    arrangements: &TraceManager<V>,
+    source: &Plan<V>,
+    plan: &Plan<V>,
+    keys: &[usize],
+) -> f64
+{
+    if let Some((records, distinct)) = arrangements.get_statistics(plan, Some(keys)) {
+        if distinct == 0 { 0.0 } else { records as f64 / distinct as f64 }
+    }
+    else if let Some((records, _distinct)) = arrangements.get_statistics(source, None) {
+        records as f64
+    }
+    else {
+        std::f64::INFINITY
+    }
+}
+
 /// Sequences relations in `constraints`.
 ///
 /// Relations become available for sequencing as soon as they share a constraint with
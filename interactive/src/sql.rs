@@ -0,0 +1,783 @@
+//! A SQL front end producing query plans.
+//!
+//! This module parses a small subset of SQL and compiles it to `Query<Value>`
+//! instances. The supported statements are
+//!
+//! ```sql
+//! CREATE TABLE name (column, ...)
+//! INSERT INTO name VALUES (value, ...), ...
+//! CREATE VIEW name AS query
+//! query
+//! ```
+//!
+//! where a query is one or more `SELECT` blocks combined with `UNION`, `UNION ALL`,
+//! or `EXCEPT`. Each block supports `DISTINCT`, multiple tables in `FROM` or joined
+//! with `JOIN .. ON`, a `WHERE` condition, and `GROUP BY` with the `COUNT(*)`, `SUM`,
+//! `MIN`, and `MAX` aggregates. Columns are untyped; literals are unsigned integers,
+//! single-quoted strings, `TRUE`, and `FALSE`.
+//!
+//! Multiple tables are compiled to a `Plan::MultiwayJoin` whose equivalence classes
+//! are drawn from equality constraints between columns of different tables. Other
+//! conditions become a `Predicate` applied to the joined records. Tables must each
+//! be constrained to equal some other table, as cross joins are not supported.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use plan::{Aggregate, Plan, Predicate};
+use plan::filter::SecondArgument;
+use concrete::Value;
+use {Command, Query};
+
+/// A lexical token.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    /// A keyword or identifier.
+    Word(String),
+    /// An unsigned integer.
+    Number(usize),
+    /// A single-quoted string.
+    Text(String),
+    /// Punctuation and comparison operators.
+    Symbol(&'static str),
+}
+
+/// Splits `text` into tokens.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+
+    const SYMBOLS: &[&str] = &["<>", "!=", "<=", ">=", "<", ">", "=", "(", ")", ",", ".", "*", ";"];
+
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while position < chars.len() {
+        let next = chars[position];
+        if next.is_whitespace() {
+            position += 1;
+        }
+        else if next == '-' && chars.get(position + 1) == Some(&'-') {
+            // Comments extend to the end of the line.
+            while position < chars.len() && chars[position] != '\n' { position += 1; }
+        }
+        else if next.is_alphabetic() || next == '_' {
+            let start = position;
+            while position < chars.len() && (chars[position].is_alphanumeric() || chars[position] == '_') { position += 1; }
+            tokens.push(Token::Word(chars[start .. position].iter().collect()));
+        }
+        else if next.is_ascii_digit() {
+            let start = position;
+            while position < chars.len() && chars[position].is_ascii_digit() { position += 1; }
+            let digits = chars[start .. position].iter().collect::<String>();
+            let number = digits.parse().map_err(|_| format!("Invalid number: {}", digits))?;
+            tokens.push(Token::Number(number));
+        }
+        else if next == '\'' {
+            // Strings may contain quotes written twice.
+            let mut string = String::new();
+            position += 1;
+            loop {
+                match chars.get(position) {
+                    None => { return Err("Unterminated string".to_string()); },
+                    Some('\'') if chars.get(position + 1) == Some(&'\'') => { string.push('\''); position += 2; },
+                    Some('\'') => { position += 1; break; },
+                    Some(c) => { string.push(*c); position += 1; },
+                }
+            }
+            tokens.push(Token::Text(string));
+        }
+        else if let Some(symbol) = SYMBOLS.iter().find(|symbol| symbol.chars().enumerate().all(|(i, c)| chars.get(position + i) == Some(&c))) {
+            position += symbol.len();
+            tokens.push(Token::Symbol(*symbol));
+        }
+        else {
+            return Err(format!("Unexpected character: {:?}", next));
+        }
+    }
+    Ok(tokens)
+}
+
+/// A reference to a column, perhaps qualified by a table name or alias.
+#[derive(Clone, Debug)]
+struct ColumnRef {
+    table: Option<String>,
+    column: String,
+}
+
+/// Either side of a comparison.
+#[derive(Clone, Debug)]
+enum Operand {
+    Column(ColumnRef),
+    Literal(Value),
+}
+
+/// Comparison operators.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
+}
+
+impl Comparison {
+    /// The comparison with its arguments exchanged.
+    fn flip(self) -> Self {
+        match self {
+            Comparison::LessThan => Comparison::GreaterThan,
+            Comparison::LessEqual => Comparison::GreaterEqual,
+            Comparison::GreaterThan => Comparison::LessThan,
+            Comparison::GreaterEqual => Comparison::LessEqual,
+            other => other,
+        }
+    }
+    /// The predicate comparing the value at `index` with `other`.
+    fn predicate(self, index: usize, other: SecondArgument<Value>) -> Predicate<Value> {
+        match self {
+            Comparison::Equal => Predicate::Equal(index, other),
+            Comparison::NotEqual => Predicate::NotEqual(index, other),
+            Comparison::LessThan => Predicate::LessThan(index, other),
+            Comparison::LessEqual => Predicate::LessEqual(index, other),
+            Comparison::GreaterThan => Predicate::GreaterThan(index, other),
+            Comparison::GreaterEqual => Predicate::GreaterEqual(index, other),
+        }
+    }
+}
+
+/// Boolean conditions in `WHERE` and `ON` clauses.
+#[derive(Clone, Debug)]
+enum Condition {
+    Compare(Operand, Comparison, Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    /// Appends the conjuncts of the condition to `list`.
+    fn conjuncts(self, list: &mut Vec<Condition>) {
+        match self {
+            Condition::And(left, right) => {
+                left.conjuncts(list);
+                right.conjuncts(list);
+            },
+            other => list.push(other),
+        }
+    }
+}
+
+/// Elements of the `SELECT` list.
+#[derive(Clone, Debug)]
+enum SelectItem {
+    Wildcard,
+    Column(ColumnRef, Option<String>),
+    Count(Option<String>),
+    Aggregate(String, ColumnRef, Option<String>),
+}
+
+/// A table in the `FROM` clause.
+#[derive(Clone, Debug)]
+struct TableRef {
+    name: String,
+    alias: String,
+}
+
+/// A single `SELECT` block.
+#[derive(Clone, Debug)]
+struct Select {
+    distinct: bool,
+    items: Vec<SelectItem>,
+    from: Vec<TableRef>,
+    conditions: Vec<Condition>,
+    group_by: Vec<ColumnRef>,
+}
+
+/// `SELECT` blocks combined by set operations.
+#[derive(Clone, Debug)]
+enum SetExpr {
+    Select(Select),
+    Union(Box<SetExpr>, Box<SetExpr>, bool),
+    Except(Box<SetExpr>, Box<SetExpr>),
+}
+
+/// Statements accepted by the front end.
+#[derive(Clone, Debug)]
+pub struct Statement {
+    kind: StatementKind,
+}
+
+#[derive(Clone, Debug)]
+enum StatementKind {
+    CreateTable(String, Vec<String>),
+    Insert(String, Vec<Vec<Value>>),
+    CreateView(String, SetExpr),
+    Query(SetExpr),
+}
+
+/// A recursive descent parser over tokens.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.position) }
+
+    /// Consumes the keyword if it is next, case insensitively.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => { self.position += 1; true },
+            _ => false,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.keyword(keyword) { Ok(()) }
+        else { Err(format!("Expected {}, found {:?}", keyword, self.peek())) }
+    }
+
+    /// Consumes the symbol if it is next.
+    fn symbol(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Some(Token::Symbol(next)) if *next == symbol => { self.position += 1; true },
+            _ => false,
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if self.symbol(symbol) { Ok(()) }
+        else { Err(format!("Expected {:?}, found {:?}", symbol, self.peek())) }
+    }
+
+    /// Consumes an identifier, which must not be a reserved word.
+    fn identifier(&mut self) -> Result<String, String> {
+        const RESERVED: &[&str] = &[
+            "select", "distinct", "from", "where", "group", "by", "join", "inner", "on", "as",
+            "and", "or", "not", "union", "all", "except", "create", "table", "view", "insert",
+            "into", "values", "true", "false",
+        ];
+        match self.peek().cloned() {
+            Some(Token::Word(word)) if !RESERVED.iter().any(|r| word.eq_ignore_ascii_case(r)) => {
+                self.position += 1;
+                Ok(word)
+            },
+            other => Err(format!("Expected identifier, found {:?}", other)),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        let kind =
+        if self.keyword("create") {
+            if self.keyword("table") {
+                let name = self.identifier()?;
+                self.expect_symbol("(")?;
+                let mut columns = vec![self.identifier()?];
+                while self.symbol(",") { columns.push(self.identifier()?); }
+                self.expect_symbol(")")?;
+                StatementKind::CreateTable(name, columns)
+            }
+            else {
+                self.expect_keyword("view")?;
+                let name = self.identifier()?;
+                self.expect_keyword("as")?;
+                StatementKind::CreateView(name, self.set_expr()?)
+            }
+        }
+        else if self.keyword("insert") {
+            self.expect_keyword("into")?;
+            let name = self.identifier()?;
+            self.expect_keyword("values")?;
+            let mut rows = Vec::new();
+            loop {
+                self.expect_symbol("(")?;
+                let mut row = vec![self.literal()?];
+                while self.symbol(",") { row.push(self.literal()?); }
+                self.expect_symbol(")")?;
+                rows.push(row);
+                if !self.symbol(",") { break; }
+            }
+            StatementKind::Insert(name, rows)
+        }
+        else {
+            StatementKind::Query(self.set_expr()?)
+        };
+        Ok(Statement { kind })
+    }
+
+    fn set_expr(&mut self) -> Result<SetExpr, String> {
+        let mut expr = SetExpr::Select(self.select()?);
+        loop {
+            if self.keyword("union") {
+                let all = self.keyword("all");
+                expr = SetExpr::Union(Box::new(expr), Box::new(SetExpr::Select(self.select()?)), all);
+            }
+            else if self.keyword("except") {
+                expr = SetExpr::Except(Box::new(expr), Box::new(SetExpr::Select(self.select()?)));
+            }
+            else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn select(&mut self) -> Result<Select, String> {
+
+        self.expect_keyword("select")?;
+        let distinct = self.keyword("distinct");
+
+        let mut items = vec![self.select_item()?];
+        while self.symbol(",") { items.push(self.select_item()?); }
+
+        self.expect_keyword("from")?;
+        let mut from = vec![self.table_ref()?];
+        let mut conditions = Vec::new();
+        loop {
+            if self.symbol(",") {
+                from.push(self.table_ref()?);
+            }
+            else if self.keyword("join") || (self.keyword("inner") && self.keyword("join")) {
+                from.push(self.table_ref()?);
+                self.expect_keyword("on")?;
+                self.condition()?.conjuncts(&mut conditions);
+            }
+            else {
+                break;
+            }
+        }
+
+        if self.keyword("where") {
+            self.condition()?.conjuncts(&mut conditions);
+        }
+
+        let mut group_by = Vec::new();
+        if self.keyword("group") {
+            self.expect_keyword("by")?;
+            group_by.push(self.column_ref()?);
+            while self.symbol(",") { group_by.push(self.column_ref()?); }
+        }
+
+        Ok(Select { distinct, items, from, conditions, group_by })
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem, String> {
+        if self.symbol("*") {
+            return Ok(SelectItem::Wildcard);
+        }
+        // Aggregates are function names followed by a parenthesis.
+        let function = match (self.tokens.get(self.position), self.tokens.get(self.position + 1)) {
+            (Some(Token::Word(word)), Some(Token::Symbol("("))) => Some(word.to_lowercase()),
+            _ => None,
+        };
+        let item =
+        if let Some(function) = function {
+            self.position += 2;
+            let item =
+            if function == "count" {
+                self.expect_symbol("*")?;
+                SelectItem::Count(None)
+            }
+            else if function == "sum" || function == "min" || function == "max" {
+                SelectItem::Aggregate(function, self.column_ref()?, None)
+            }
+            else {
+                return Err(format!("Unknown function: {}", function));
+            };
+            self.expect_symbol(")")?;
+            item
+        }
+        else {
+            SelectItem::Column(self.column_ref()?, None)
+        };
+
+        let alias = if self.keyword("as") { Some(self.identifier()?) } else { None };
+        Ok(match item {
+            SelectItem::Column(column, _) => SelectItem::Column(column, alias),
+            SelectItem::Count(_) => SelectItem::Count(alias),
+            SelectItem::Aggregate(function, column, _) => SelectItem::Aggregate(function, column, alias),
+            SelectItem::Wildcard => SelectItem::Wildcard,
+        })
+    }
+
+    fn table_ref(&mut self) -> Result<TableRef, String> {
+        let name = self.identifier()?;
+        self.keyword("as");
+        let alias =
+        if let Some(Token::Word(_)) = self.peek() {
+            self.identifier().unwrap_or_else(|_| name.clone())
+        }
+        else {
+            name.clone()
+        };
+        Ok(TableRef { name, alias })
+    }
+
+    fn column_ref(&mut self) -> Result<ColumnRef, String> {
+        let first = self.identifier()?;
+        if self.symbol(".") {
+            Ok(ColumnRef { table: Some(first), column: self.identifier()? })
+        }
+        else {
+            Ok(ColumnRef { table: None, column: first })
+        }
+    }
+
+    fn literal(&mut self) -> Result<Value, String> {
+        match self.peek().cloned() {
+            Some(Token::Number(number)) => { self.position += 1; Ok(Value::Usize(number)) },
+            Some(Token::Text(text)) => { self.position += 1; Ok(Value::String(text)) },
+            _ if self.keyword("true") => Ok(Value::Bool(true)),
+            _ if self.keyword("false") => Ok(Value::Bool(false)),
+            other => Err(format!("Expected literal, found {:?}", other)),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.peek() {
+            Some(Token::Word(word)) if !word.eq_ignore_ascii_case("true") && !word.eq_ignore_ascii_case("false") => {
+                Ok(Operand::Column(self.column_ref()?))
+            },
+            _ => Ok(Operand::Literal(self.literal()?)),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let mut condition = self.conjunction()?;
+        while self.keyword("or") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.conjunction()?));
+        }
+        Ok(condition)
+    }
+
+    fn conjunction(&mut self) -> Result<Condition, String> {
+        let mut condition = self.negation()?;
+        while self.keyword("and") {
+            condition = Condition::And(Box::new(condition), Box::new(self.negation()?));
+        }
+        Ok(condition)
+    }
+
+    fn negation(&mut self) -> Result<Condition, String> {
+        if self.keyword("not") {
+            Ok(Condition::Not(Box::new(self.negation()?)))
+        }
+        else if self.symbol("(") {
+            let condition = self.condition()?;
+            self.expect_symbol(")")?;
+            Ok(condition)
+        }
+        else {
+            let left = self.operand()?;
+            let comparison = match self.peek() {
+                Some(Token::Symbol("=")) => Comparison::Equal,
+                Some(Token::Symbol("<>")) | Some(Token::Symbol("!=")) => Comparison::NotEqual,
+                Some(Token::Symbol("<")) => Comparison::LessThan,
+                Some(Token::Symbol("<=")) => Comparison::LessEqual,
+                Some(Token::Symbol(">")) => Comparison::GreaterThan,
+                Some(Token::Symbol(">=")) => Comparison::GreaterEqual,
+                other => { return Err(format!("Expected comparison, found {:?}", other)); },
+            };
+            self.position += 1;
+            let right = self.operand()?;
+            Ok(Condition::Compare(left, comparison, right))
+        }
+    }
+}
+
+/// Parses a sequence of statements, separated by semicolons.
+pub fn parse(text: &str) -> Result<Vec<Statement>, String> {
+    let mut parser = Parser { tokens: tokenize(text)?, position: 0 };
+    let mut statements = Vec::new();
+    while parser.peek().is_some() {
+        if !parser.symbol(";") {
+            statements.push(parser.statement()?);
+            if parser.peek().is_some() {
+                parser.expect_symbol(";")?;
+            }
+        }
+    }
+    Ok(statements)
+}
+
+/// Column names of tables and views, used to compile queries.
+pub struct Catalog {
+    tables: HashMap<String, Vec<String>>,
+    queries: usize,
+}
+
+impl Catalog {
+
+    /// Creates a new, empty catalog.
+    pub fn new() -> Self {
+        Catalog { tables: HashMap::new(), queries: 0 }
+    }
+
+    /// Records the column names of a table, replacing any prior definition.
+    pub fn define(&mut self, name: &str, columns: Vec<String>) {
+        self.tables.insert(name.to_string(), columns);
+    }
+
+    /// Compiles the query `text` to a query binding its results to `name`.
+    pub fn compile(&self, name: &str, text: &str) -> Result<Query<Value>, String> {
+        let mut parser = Parser { tokens: tokenize(text)?, position: 0 };
+        let expr = parser.set_expr()?;
+        parser.symbol(";");
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected trailing input: {:?}", token));
+        }
+        let (plan, _columns) = self.set_expr(&expr)?;
+        Ok(plan.into_rule(name).into_query())
+    }
+
+    /// Translates a statement to commands, with any updates at `time`.
+    ///
+    /// Table and view definitions are recorded in the catalog. Queries that are
+    /// not views are installed under generated names and print their results.
+    pub fn execute(&mut self, statement: Statement, time: Duration) -> Result<Vec<Command<Value>>, String> {
+        match statement.kind {
+            StatementKind::CreateTable(name, columns) => {
+                self.define(&name, columns);
+                Ok(vec![Command::CreateInput(name, Vec::new())])
+            },
+            StatementKind::Insert(name, rows) => {
+                let arity = self.tables.get(&name).ok_or_else(|| format!("Unknown table: {}", name))?.len();
+                if let Some(row) = rows.iter().find(|row| row.len() != arity) {
+                    return Err(format!("Expected {} values, found {:?}", arity, row));
+                }
+                let updates = rows.into_iter().map(|row| (row, time, 1)).collect();
+                Ok(vec![Command::UpdateInput(name, updates)])
+            },
+            StatementKind::CreateView(name, expr) => {
+                let (plan, columns) = self.set_expr(&expr)?;
+                self.define(&name, columns);
+                Ok(vec![plan.into_rule(&name).into_query().into_command()])
+            },
+            StatementKind::Query(expr) => {
+                let (plan, _columns) = self.set_expr(&expr)?;
+                let name = format!("query{}", self.queries);
+                self.queries += 1;
+                Ok(vec![plan.inspect(&name).into_rule(&name).into_query().into_command()])
+            },
+        }
+    }
+
+    /// Compiles set operations among `SELECT` blocks.
+    fn set_expr(&self, expr: &SetExpr) -> Result<(Plan<Value>, Vec<String>), String> {
+        match expr {
+            SetExpr::Select(select) => self.select(select),
+            SetExpr::Union(left, right, all) => {
+                let (left, columns) = self.set_expr(left)?;
+                let (right, columns2) = self.set_expr(right)?;
+                check_arity(&columns, &columns2)?;
+                let plan = left.concat(right);
+                Ok((if *all { plan } else { plan.distinct() }, columns))
+            },
+            SetExpr::Except(left, right) => {
+                // Distinct records of `left` not present in `right`.
+                let (left, columns) = self.set_expr(left)?;
+                let (right, columns2) = self.set_expr(right)?;
+                check_arity(&columns, &columns2)?;
+                let left = left.distinct();
+                let keys = (0 .. columns.len()).map(|index| (index, index)).collect();
+                let common = left.clone().join(right.distinct(), keys);
+                Ok((left.concat(common.negate()), columns))
+            },
+        }
+    }
+
+    /// Compiles a `SELECT` block.
+    fn select(&self, select: &Select) -> Result<(Plan<Value>, Vec<String>), String> {
+
+        // Columns of the joined tables, in order, with the alias of their table.
+        let mut columns = Vec::new();
+        let mut results = Vec::new();
+        for (input, table) in select.from.iter().enumerate() {
+            if select.from[.. input].iter().any(|other| other.alias == table.alias) {
+                return Err(format!("Duplicate table name: {}", table.alias));
+            }
+            let schema = self.tables.get(&table.name).ok_or_else(|| format!("Unknown table: {}", table.name))?;
+            for (attr, column) in schema.iter().enumerate() {
+                columns.push((table.alias.clone(), column.clone(), input));
+                results.push((attr, input));
+            }
+        }
+
+        let resolve = |column: &ColumnRef| -> Result<usize, String> {
+            let mut found = columns.iter().enumerate().filter(|(_, (table, name, _))| {
+                name == &column.column && column.table.as_ref().map(|t| t == table).unwrap_or(true)
+            });
+            match (found.next(), found.next()) {
+                (Some((position, _)), None) => Ok(position),
+                (Some(_), Some(_)) => Err(format!("Ambiguous column: {}", column.column)),
+                (None, _) => Err(format!("Unknown column: {:?}", column)),
+            }
+        };
+
+        // Equalities between columns of distinct tables form join constraints.
+        let mut classes: Vec<Vec<usize>> = Vec::new();
+        let mut predicates = Vec::new();
+        for condition in select.conditions.iter() {
+            if let Condition::Compare(Operand::Column(left), Comparison::Equal, Operand::Column(right)) = condition {
+                let left = resolve(left)?;
+                let right = resolve(right)?;
+                if select.from.len() > 1 && columns[left].2 != columns[right].2 {
+                    let mut merged = vec![left, right];
+                    classes.retain(|class| {
+                        if class.contains(&left) || class.contains(&right) {
+                            merged.extend(class.iter().cloned());
+                            false
+                        }
+                        else { true }
+                    });
+                    merged.sort();
+                    merged.dedup();
+                    classes.push(merged);
+                    continue;
+                }
+            }
+            predicates.push(predicate(condition, &resolve)?);
+        }
+
+        let mut plan =
+        if select.from.len() == 1 {
+            Plan::source(&select.from[0].name)
+        }
+        else {
+            // Each table must be reachable through join constraints.
+            let mut reached = vec![0];
+            let mut active = true;
+            while active {
+                active = false;
+                for class in classes.iter() {
+                    if class.iter().any(|position| reached.contains(&columns[*position].2)) {
+                        for position in class.iter() {
+                            if !reached.contains(&columns[*position].2) {
+                                reached.push(columns[*position].2);
+                                active = true;
+                            }
+                        }
+                    }
+                }
+            }
+            if reached.len() < select.from.len() {
+                return Err("Cross joins are not supported; each table must be joined by an equality".to_string());
+            }
+
+            // The join does not enforce equalities among columns of the same table.
+            for class in classes.iter() {
+                for (index, position) in class.iter().enumerate() {
+                    if let Some(prior) = class[.. index].iter().find(|prior| columns[**prior].2 == columns[*position].2) {
+                        predicates.push(Predicate::Equal(*position, SecondArgument::Position(*prior)));
+                    }
+                }
+            }
+
+            let sources = select.from.iter().map(|table| Plan::source(&table.name)).collect();
+            let equalities = classes.iter().map(|class| class.iter().map(|position| results[*position]).collect()).collect();
+            Plan::multiway_join(sources, equalities, results.clone())
+        };
+
+        if predicates.len() == 1 {
+            plan = plan.filter(predicates.pop().expect("Length checked"));
+        }
+        else if predicates.len() > 1 {
+            plan = plan.filter(Predicate::All(predicates));
+        }
+
+        let aggregated = select.items.iter().any(|item| match item { SelectItem::Count(_) | SelectItem::Aggregate(..) => true, _ => false });
+
+        let (plan, names) =
+        if aggregated || !select.group_by.is_empty() {
+
+            // Group keys precede aggregates in the output of the reduction.
+            let keys = select.group_by.iter().map(|column| resolve(column)).collect::<Result<Vec<_>, _>>()?;
+            let mut aggregates = Vec::new();
+            let mut projection = Vec::new();
+            let mut names = Vec::new();
+            for item in select.items.iter() {
+                match item {
+                    SelectItem::Wildcard => { return Err("Cannot select * with GROUP BY".to_string()); },
+                    SelectItem::Column(column, alias) => {
+                        let position = resolve(column)?;
+                        let index = keys.iter().position(|key| *key == position).ok_or_else(|| format!("Column not in GROUP BY: {}", column.column))?;
+                        projection.push(index);
+                        names.push(alias.clone().unwrap_or_else(|| column.column.clone()));
+                    },
+                    SelectItem::Count(alias) => {
+                        projection.push(keys.len() + aggregates.len());
+                        aggregates.push(Aggregate::Count);
+                        names.push(alias.clone().unwrap_or_else(|| "count".to_string()));
+                    },
+                    SelectItem::Aggregate(function, column, alias) => {
+                        let position = resolve(column)?;
+                        projection.push(keys.len() + aggregates.len());
+                        aggregates.push(match function.as_str() {
+                            "sum" => Aggregate::Sum(position),
+                            "min" => Aggregate::Min(position),
+                            _ => Aggregate::Max(position),
+                        });
+                        names.push(alias.clone().unwrap_or_else(|| function.clone()));
+                    },
+                }
+            }
+            (plan.reduce(keys, aggregates).project(projection), names)
+        }
+        else {
+            let mut projection = Vec::new();
+            let mut names = Vec::new();
+            for item in select.items.iter() {
+                match item {
+                    SelectItem::Wildcard => {
+                        projection.extend(0 .. columns.len());
+                        names.extend(columns.iter().map(|(_, name, _)| name.clone()));
+                    },
+                    SelectItem::Column(column, alias) => {
+                        projection.push(resolve(column)?);
+                        names.push(alias.clone().unwrap_or_else(|| column.column.clone()));
+                    },
+                    _ => unreachable!("Aggregates handled above"),
+                }
+            }
+            if projection.iter().cloned().eq(0 .. columns.len()) {
+                (plan, names)
+            }
+            else {
+                (plan.project(projection), names)
+            }
+        };
+
+        Ok((if select.distinct { plan.distinct() } else { plan }, names))
+    }
+}
+
+/// Confirms that the two inputs of a set operation have the same number of columns.
+fn check_arity(columns1: &[String], columns2: &[String]) -> Result<(), String> {
+    if columns1.len() == columns2.len() { Ok(()) }
+    else { Err(format!("Set operation inputs have {} and {} columns", columns1.len(), columns2.len())) }
+}
+
+/// Compiles a condition to a predicate over joined records.
+fn predicate<F>(condition: &Condition, resolve: &F) -> Result<Predicate<Value>, String>
+where
+    F: Fn(&ColumnRef) -> Result<usize, String>,
+{
+    match condition {
+        Condition::Compare(Operand::Column(left), comparison, right) => {
+            let other = match right {
+                Operand::Column(column) => SecondArgument::Position(resolve(column)?),
+                Operand::Literal(value) => SecondArgument::Constant(value.clone()),
+            };
+            Ok(comparison.predicate(resolve(left)?, other))
+        },
+        Condition::Compare(Operand::Literal(value), comparison, Operand::Column(right)) => {
+            Ok(comparison.flip().predicate(resolve(right)?, SecondArgument::Constant(value.clone())))
+        },
+        Condition::Compare(Operand::Literal(_), _, Operand::Literal(_)) => {
+            Err("Comparisons must reference a column".to_string())
+        },
+        Condition::And(left, right) => Ok(Predicate::All(vec![predicate(left, resolve)?, predicate(right, resolve)?])),
+        Condition::Or(left, right) => Ok(Predicate::Any(vec![predicate(left, resolve)?, predicate(right, resolve)?])),
+        Condition::Not(condition) => Ok(Predicate::Not(Box::new(predicate(condition, resolve)?))),
+    }
+}
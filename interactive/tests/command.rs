@@ -0,0 +1,157 @@
+extern crate timely;
+extern crate bincode;
+extern crate interactive;
+
+use std::net::{Shutdown, TcpListener};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use timely::communication::Allocate;
+use timely::worker::Worker;
+
+use interactive::{Command, Manager, Response};
+use interactive::concrete::{Responses, Value};
+
+/// Executes `command`, and returns the response it sends at once to the address it is given.
+fn respond<A, F>(manager: &mut Manager<Value>, worker: &mut Worker<A>, command: F) -> Response<Value>
+where
+    A: Allocate,
+    F: FnOnce(String) -> Command<Value>,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind listener");
+    let address = listener.local_addr().expect("failed to read address").to_string();
+    command(address).execute(manager, worker);
+    let (mut stream, _) = listener.accept().expect("failed to accept connection");
+    bincode::deserialize_from(&mut stream).expect("failed to read response")
+}
+
+fn edge(src: usize, dst: usize) -> Vec<Value> {
+    vec![Value::Usize(src), Value::Usize(dst)]
+}
+
+#[test]
+fn advance_time_then_peek() {
+    timely::execute_directly(move |worker| {
+
+        let mut manager = Manager::<Value>::new();
+        let peek = |time: u64| move |address: String| Command::Peek("edges".to_string(), vec![0], vec![Value::Usize(0)], Duration::from_secs(time), address);
+
+        Command::CreateInput("edges".to_string(), vec![edge(0, 1), edge(1, 2)]).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+        match respond(&mut manager, worker, peek(0)) {
+            Response::Error(error) => assert!(error.contains("compaction frontier"), "{}", error),
+            response => panic!("unexpected response: {:?}", response),
+        }
+
+        // A peek at a time that is not yet complete waits for the trace, whose compaction it holds
+        // back, and sees no changes at later times.
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind listener");
+        let address = listener.local_addr().expect("failed to read address").to_string();
+        peek(1)(address).execute(&mut manager, worker);
+        assert_eq!(manager.peeks.len(), 1);
+
+        Command::UpdateInput("edges".to_string(), vec![(edge(0, 2), Duration::from_secs(1), 1)]).execute(&mut manager, worker);
+        Command::UpdateInput("edges".to_string(), vec![(edge(0, 3), Duration::from_secs(2), 1)]).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(3)).execute(&mut manager, worker);
+        assert!(manager.peeks.is_empty());
+        let (mut stream, _) = listener.accept().expect("failed to accept connection");
+        let response: Response<Value> = bincode::deserialize_from(&mut stream).expect("failed to read response");
+        assert_eq!(response, Response::Peek(vec![(edge(0, 1), 1), (edge(0, 2), 1)], 1));
+
+        // Other peeks find the trace compacted through the most recent time.
+        match respond(&mut manager, worker, peek(2)) {
+            Response::Error(error) => assert!(error.contains("compaction frontier"), "{}", error),
+            response => panic!("unexpected response: {:?}", response),
+        }
+
+        manager.shutdown(worker);
+    });
+}
+
+/// Steps `worker` until `responses` produces a response.
+fn receive<A: Allocate>(worker: &mut Worker<A>, responses: &Receiver<Response<Value>>) -> Response<Value> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Ok(response) = responses.try_recv() {
+            return response;
+        }
+        assert!(Instant::now() < deadline, "timed out awaiting response");
+        worker.step();
+    }
+}
+
+/// Receives responses until one reports progress through `time`, and returns the updates before it.
+///
+/// Each update must be reported before the progress that passes its time.
+fn receive_through<A: Allocate>(worker: &mut Worker<A>, responses: &Receiver<Response<Value>>, time: Duration) -> Vec<(Vec<Value>, Duration, isize)> {
+    let mut updates = Vec::new();
+    let mut frontier = vec![Duration::from_secs(0)];
+    while frontier.iter().any(|lower| lower < &time) {
+        match receive(worker, responses) {
+            Response::Updates(received) => { updates.extend(received); },
+            Response::Progress(progress) => {
+                assert!(updates.iter().all(|(_, at, _)| progress.iter().all(|lower| at < lower)), "progress passed unreported updates");
+                frontier = progress;
+            },
+            response => panic!("unexpected response: {:?}", response),
+        }
+    }
+    updates
+}
+
+#[test]
+fn subscribe_then_drop() {
+    timely::execute_directly(move |worker| {
+
+        let mut manager = Manager::<Value>::new();
+        Command::CreateInput("edges".to_string(), vec![edge(0, 1), edge(1, 2)]).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+
+        // The server connects during the command, and writes as it is stepped.
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind listener");
+        let address = listener.local_addr().expect("failed to read address").to_string();
+        Command::Subscribe("edges".to_string(), address).execute(&mut manager, worker);
+        assert_eq!(manager.dataflows.len(), 2);
+        let (stream, _) = listener.accept().expect("failed to accept connection");
+        let client = stream.try_clone().expect("failed to clone stream");
+        let (sender, responses) = channel();
+        thread::spawn(move || {
+            for response in Responses::new(stream) {
+                if sender.send(response).is_err() { break; }
+            }
+        });
+
+        // A snapshot of the contents as of the compaction frontier, once the next time completes.
+        Command::AdvanceTime(Duration::from_secs(2)).execute(&mut manager, worker);
+        let mut snapshot = receive_through(worker, &responses, Duration::from_secs(2)).into_iter().map(|(record, _, diff)| (record, diff)).collect::<Vec<_>>();
+        snapshot.sort();
+        assert_eq!(snapshot, vec![(edge(0, 1), 1), (edge(1, 2), 1)]);
+
+        // Changes follow at their times, and again the frontier.
+        let updates = vec![(edge(0, 1), Duration::from_secs(2), -1), (edge(2, 0), Duration::from_secs(2), 1)];
+        Command::UpdateInput("edges".to_string(), updates.clone()).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(3)).execute(&mut manager, worker);
+        assert_eq!(receive_through(worker, &responses, Duration::from_secs(3)), updates);
+
+        // Once the client disconnects, writes fail and the subscription may be retired.
+        // The reading thread observes the shutdown and drops its end of the connection as well.
+        client.shutdown(Shutdown::Both).expect("failed to shut down connection");
+        drop(client);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut seconds = 3;
+        while manager.disconnected.borrow().is_empty() {
+            assert!(Instant::now() < deadline, "disconnection not observed");
+            seconds += 1;
+            Command::AdvanceTime(Duration::from_secs(seconds)).execute(&mut manager, worker);
+            worker.step();
+        }
+        let disconnected = manager.disconnected.borrow_mut().drain(..).collect::<Vec<_>>();
+        for index in disconnected {
+            Command::DropSubscription(index).execute(&mut manager, worker);
+        }
+        assert_eq!(manager.dataflows.len(), 1);
+
+        manager.shutdown(worker);
+    });
+}
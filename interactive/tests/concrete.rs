@@ -0,0 +1,215 @@
+extern crate interactive;
+
+use std::time::Duration;
+
+use interactive::Datum;
+use interactive::concrete::{Expression, Function, Operator, Type, Value};
+use interactive::plan::Predicate;
+use interactive::plan::filter::SecondArgument;
+
+#[test]
+fn count_values() {
+    assert_eq!(Value::count(3), Value::Usize(3));
+    assert!(Value::count(-1).is_error());
+}
+
+#[test]
+fn sum_values() {
+    let values = vec![Value::Usize(2), Value::Usize(5)];
+    assert_eq!(Value::sum(values.iter().zip(vec![3, 1])), Value::Usize(11));
+
+    let values = vec![Value::Duration(Duration::from_secs(2))];
+    assert_eq!(Value::sum(values.iter().map(|v| (v, 3))), Value::Duration(Duration::from_secs(6)));
+
+    // Negative multiplicities, overflow, and non-numeric values are errors rather than panics.
+    let values = vec![Value::Usize(2)];
+    assert!(Value::sum(values.iter().map(|v| (v, -1))).is_error());
+    let values = vec![Value::Usize(usize::max_value())];
+    assert!(Value::sum(values.iter().map(|v| (v, 2))).is_error());
+    let values = vec![Value::Usize(usize::max_value()), Value::Usize(1)];
+    assert!(Value::sum(values.iter().map(|v| (v, 1))).is_error());
+    let values = vec![Value::Duration(Duration::from_secs(u64::max_value()))];
+    assert!(Value::sum(values.iter().map(|v| (v, 2))).is_error());
+    let values = vec![Value::Bool(true)];
+    assert!(Value::sum(values.iter().map(|v| (v, 1))).is_error());
+    let values = vec![Value::Usize(1), Value::Error("earlier".to_string())];
+    assert_eq!(Value::sum(values.iter().map(|v| (v, 1))), Value::Error("earlier".to_string()));
+}
+
+fn literal<V: Into<Value>>(value: V) -> Expression { Expression::Literal(value.into()) }
+fn binary(operator: Operator, left: Expression, right: Expression) -> Expression {
+    Expression::Binary(operator, Box::new(left), Box::new(right))
+}
+fn call(function: Function, arguments: Vec<Expression>) -> Expression { Expression::Call(function, arguments) }
+fn cast(expression: Expression, typ: Type) -> Expression { Expression::Cast(Box::new(expression), typ) }
+fn error() -> Expression { Expression::Column(100) }
+
+#[test]
+fn evaluate_expressions() {
+    let data = vec![Value::Usize(3), Value::String("Hello".to_string())];
+
+    assert_eq!(literal(true).evaluate(&data), Value::Bool(true));
+    assert_eq!(Expression::Column(1).evaluate(&data), data[1]);
+    assert!(error().evaluate(&data).is_error());
+
+    // Negation requires a boolean, and passes errors along.
+    assert_eq!(Expression::Not(Box::new(literal(false))).evaluate(&data), Value::Bool(true));
+    assert!(Expression::Not(Box::new(literal(1usize))).evaluate(&data).is_error());
+    assert!(Expression::Not(Box::new(error())).evaluate(&data).is_error());
+
+    // Cases yield the result of the first condition to hold, or the default.
+    let case = |condition: Expression| Expression::Case(vec![(condition, literal(1usize))], Box::new(literal(2usize)));
+    assert_eq!(case(literal(true)).evaluate(&data), Value::Usize(1));
+    assert_eq!(case(literal(false)).evaluate(&data), Value::Usize(2));
+    assert!(case(literal(0usize)).evaluate(&data).is_error());
+    assert!(case(error()).evaluate(&data).is_error());
+
+    // Tuples collect values, unless any is an error.
+    let tuple = Expression::Tuple(vec![Expression::Column(0), literal(true)]);
+    assert_eq!(tuple.evaluate(&data), Value::Vector(vec![Value::Usize(3), Value::Bool(true)]));
+    assert!(Expression::Tuple(vec![literal(true), error()]).evaluate(&data).is_error());
+
+    // Errors in either operand are passed along, and calls with erroneous arguments are not made.
+    assert!(binary(Operator::Add, error(), literal(1usize)).evaluate(&data).is_error());
+    assert!(binary(Operator::Add, literal(1usize), error()).evaluate(&data).is_error());
+    assert!(call(Function::Length, vec![error()]).evaluate(&data).is_error());
+}
+
+#[test]
+fn apply_operators() {
+    let apply = |operator, left: Value, right: Value| {
+        binary(operator, Expression::Literal(left), Expression::Literal(right)).evaluate(&[])
+    };
+    let secs = |secs| Value::Duration(Duration::from_secs(secs));
+
+    assert_eq!(apply(Operator::Add, Value::Usize(2), Value::Usize(3)), Value::Usize(5));
+    assert_eq!(apply(Operator::Add, secs(2), secs(3)), secs(5));
+    assert!(apply(Operator::Add, Value::Usize(usize::max_value()), Value::Usize(1)).is_error());
+    assert!(apply(Operator::Add, Value::Usize(1), secs(1)).is_error());
+
+    assert_eq!(apply(Operator::Subtract, Value::Usize(3), Value::Usize(2)), Value::Usize(1));
+    assert_eq!(apply(Operator::Subtract, secs(3), secs(2)), secs(1));
+    assert!(apply(Operator::Subtract, Value::Usize(2), Value::Usize(3)).is_error());
+    assert!(apply(Operator::Subtract, secs(2), secs(3)).is_error());
+    assert!(apply(Operator::Subtract, Value::Bool(true), Value::Usize(1)).is_error());
+
+    assert_eq!(apply(Operator::Multiply, Value::Usize(2), Value::Usize(3)), Value::Usize(6));
+    assert_eq!(apply(Operator::Multiply, secs(2), Value::Usize(3)), secs(6));
+    assert_eq!(apply(Operator::Multiply, Value::Usize(3), secs(2)), secs(6));
+    assert!(apply(Operator::Multiply, Value::Usize(usize::max_value()), Value::Usize(2)).is_error());
+    assert!(apply(Operator::Multiply, secs(1), Value::Usize(1 << 40)).is_error());
+    assert!(apply(Operator::Multiply, secs(u64::max_value()), Value::Usize(2)).is_error());
+    assert!(apply(Operator::Multiply, secs(1), secs(1)).is_error());
+
+    assert_eq!(apply(Operator::Divide, Value::Usize(7), Value::Usize(2)), Value::Usize(3));
+    assert_eq!(apply(Operator::Divide, secs(6), Value::Usize(2)), secs(3));
+    assert!(apply(Operator::Divide, Value::Usize(1), Value::Usize(0)).is_error());
+    assert!(apply(Operator::Divide, secs(1), Value::Usize(0)).is_error());
+    assert!(apply(Operator::Divide, secs(1), Value::Usize(1 << 40)).is_error());
+    assert!(apply(Operator::Divide, Value::Usize(1), secs(1)).is_error());
+
+    assert_eq!(apply(Operator::Modulo, Value::Usize(7), Value::Usize(2)), Value::Usize(1));
+    assert!(apply(Operator::Modulo, Value::Usize(7), Value::Usize(0)).is_error());
+    assert!(apply(Operator::Modulo, secs(7), Value::Usize(2)).is_error());
+
+    assert_eq!(apply(Operator::Equal, Value::Usize(1), Value::Usize(1)), Value::Bool(true));
+    assert_eq!(apply(Operator::NotEqual, Value::Usize(1), Value::Usize(1)), Value::Bool(false));
+    assert_eq!(apply(Operator::LessThan, Value::Usize(1), Value::Usize(2)), Value::Bool(true));
+    assert_eq!(apply(Operator::LessEqual, Value::Usize(2), Value::Usize(2)), Value::Bool(true));
+    assert_eq!(apply(Operator::GreaterThan, Value::Usize(1), Value::Usize(2)), Value::Bool(false));
+    assert_eq!(apply(Operator::GreaterEqual, Value::Usize(1), Value::Usize(2)), Value::Bool(false));
+    for operator in vec![Operator::Equal, Operator::NotEqual, Operator::LessThan, Operator::LessEqual, Operator::GreaterThan, Operator::GreaterEqual] {
+        assert!(apply(operator, Value::Usize(1), Value::Bool(true)).is_error());
+    }
+
+    // Connectives short-circuit, and otherwise require booleans.
+    assert_eq!(binary(Operator::And, literal(false), error()).evaluate(&[]), Value::Bool(false));
+    assert_eq!(binary(Operator::Or, literal(true), error()).evaluate(&[]), Value::Bool(true));
+    assert_eq!(apply(Operator::And, Value::Bool(true), Value::Bool(true)), Value::Bool(true));
+    assert_eq!(apply(Operator::Or, Value::Bool(false), Value::Bool(false)), Value::Bool(false));
+    assert!(apply(Operator::And, Value::Bool(true), Value::Usize(1)).is_error());
+    assert!(apply(Operator::And, Value::Usize(1), Value::Bool(true)).is_error());
+    assert!(apply(Operator::Or, Value::Bool(false), Value::Usize(1)).is_error());
+    assert!(apply(Operator::Or, Value::Usize(1), Value::Bool(true)).is_error());
+}
+
+#[test]
+fn apply_functions() {
+    let string = |s: &str| literal(s.to_string());
+
+    assert_eq!(call(Function::Length, vec![string("héllo")]).evaluate(&[]), Value::Usize(5));
+    assert_eq!(call(Function::Length, vec![Expression::Tuple(vec![literal(1usize), literal(2usize)])]).evaluate(&[]), Value::Usize(2));
+    assert!(call(Function::Length, vec![literal(1usize)]).evaluate(&[]).is_error());
+    assert!(call(Function::Length, vec![]).evaluate(&[]).is_error());
+
+    assert_eq!(call(Function::Lower, vec![string("AbC")]).evaluate(&[]), Value::String("abc".to_string()));
+    assert_eq!(call(Function::Upper, vec![string("AbC")]).evaluate(&[]), Value::String("ABC".to_string()));
+    assert_eq!(call(Function::Trim, vec![string(" a ")]).evaluate(&[]), Value::String("a".to_string()));
+    assert!(call(Function::Lower, vec![literal(1usize)]).evaluate(&[]).is_error());
+    assert!(call(Function::Upper, vec![string("a"), string("b")]).evaluate(&[]).is_error());
+    assert!(call(Function::Trim, vec![literal(true)]).evaluate(&[]).is_error());
+
+    assert_eq!(call(Function::Concat, vec![string("a"), string("b")]).evaluate(&[]), Value::String("ab".to_string()));
+    assert_eq!(call(Function::Concat, vec![]).evaluate(&[]), Value::String(String::new()));
+    assert!(call(Function::Concat, vec![string("a"), literal(1usize)]).evaluate(&[]).is_error());
+
+    assert_eq!(call(Function::Substring, vec![string("hello"), literal(1usize), literal(3usize)]).evaluate(&[]), Value::String("ell".to_string()));
+    assert_eq!(call(Function::Substring, vec![string("hello"), literal(9usize), literal(3usize)]).evaluate(&[]), Value::String(String::new()));
+    assert!(call(Function::Substring, vec![string("hello"), literal(1usize)]).evaluate(&[]).is_error());
+    assert!(call(Function::Substring, vec![string("hello"), string("1"), literal(3usize)]).evaluate(&[]).is_error());
+
+    assert_eq!(call(Function::Contains, vec![string("hello"), string("ell")]).evaluate(&[]), Value::Bool(true));
+    assert!(call(Function::Contains, vec![string("hello"), literal(1usize)]).evaluate(&[]).is_error());
+}
+
+#[test]
+fn cast_types() {
+    let convert = |value: Expression, typ| cast(value, typ).evaluate(&[]);
+    let string = |s: &str| literal(s.to_string());
+
+    assert_eq!(convert(literal(true), Type::Bool), Value::Bool(true));
+    assert_eq!(convert(literal(2usize), Type::Bool), Value::Bool(true));
+    assert_eq!(convert(string("false"), Type::Bool), Value::Bool(false));
+    assert!(convert(string("maybe"), Type::Bool).is_error());
+    assert!(convert(literal(Duration::from_secs(1)), Type::Bool).is_error());
+
+    assert_eq!(convert(literal(4usize), Type::Usize), Value::Usize(4));
+    assert_eq!(convert(literal(true), Type::Usize), Value::Usize(1));
+    assert_eq!(convert(string(" 12 "), Type::Usize), Value::Usize(12));
+    assert_eq!(convert(literal(Duration::from_millis(2500)), Type::Usize), Value::Usize(2));
+    assert!(convert(string("twelve"), Type::Usize).is_error());
+    assert!(convert(Expression::Tuple(vec![]), Type::Usize).is_error());
+
+    assert_eq!(convert(string("a"), Type::String), Value::String("a".to_string()));
+    assert_eq!(convert(literal(true), Type::String), Value::String("true".to_string()));
+    assert_eq!(convert(literal(12usize), Type::String), Value::String("12".to_string()));
+    assert_eq!(convert(literal(Duration::from_millis(1500)), Type::String), Value::String("1.5".to_string()));
+    assert!(convert(Expression::Tuple(vec![]), Type::String).is_error());
+
+    assert_eq!(convert(literal(Duration::from_secs(3)), Type::Duration), Value::Duration(Duration::from_secs(3)));
+    assert_eq!(convert(literal(3usize), Type::Duration), Value::Duration(Duration::from_secs(3)));
+    assert!(convert(literal(true), Type::Duration).is_error());
+    assert!(convert(string("3"), Type::Duration).is_error());
+
+    // Errors are passed along unchanged.
+    assert_eq!(convert(Expression::Literal(Value::Error("earlier".to_string())), Type::String), Value::Error("earlier".to_string()));
+}
+
+#[test]
+fn predicates_and_expressions() {
+    let data = vec![Value::Usize(1), Value::Bool(true), Value::Usize(2)];
+    let check = |predicate: Predicate<Value>| {
+        (predicate.satisfied(&data), Value::satisfies(&data, &Expression::from(predicate)))
+    };
+
+    // Comparisons of values of the same type agree.
+    assert_eq!(check(Predicate::LessThan(0, SecondArgument::Position(2))), (true, true));
+    assert_eq!(check(Predicate::Equal(0, SecondArgument::Constant(Value::Usize(2)))), (false, false));
+    assert_eq!(check(Predicate::Not(Box::new(Predicate::Equal(0, SecondArgument::Position(2))))), (true, true));
+    assert_eq!(check(Predicate::All(vec![])), (true, true));
+    assert_eq!(check(Predicate::Any(vec![])), (false, false));
+
+    // Comparisons of values of different types do not, as documented.
+    assert_eq!(check(Predicate::LessThan(1, SecondArgument::Position(0))), (true, false));
+    assert_eq!(check(Predicate::Not(Box::new(Predicate::Equal(0, SecondArgument::Position(1))))), (true, false));
+}
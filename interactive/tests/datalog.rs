@@ -0,0 +1,152 @@
+extern crate interactive;
+
+use interactive::{Command, Plan, Query};
+use interactive::concrete::Value;
+use interactive::datalog::compile;
+use interactive::plan::Predicate;
+use interactive::plan::filter::SecondArgument;
+
+fn compiles_to(text: &str, query: Query<Value>) {
+    match compile(text) {
+        Ok(program) => assert_eq!(program.query, query),
+        Err(error) => panic!("{:?} failed with {:?}", text, error),
+    }
+}
+
+fn fails_with(text: &str, error: &str) {
+    match compile(text) {
+        Ok(program) => panic!("{:?} compiled to {:?}", text, program.query),
+        Err(message) => assert!(message.contains(error), "{:?} failed with {:?}", text, message),
+    }
+}
+
+#[test]
+fn recursion() {
+    let text = "
+        edge(1, 2).
+        edge(2, 3).
+        reach(X, Y) :- edge(X, Y).
+        reach(X, Z) :- reach(X, Y), edge(Y, Z).
+        .output reach
+    ";
+
+    let base = Plan::source("edge").project(vec![0, 1]);
+    let step = Plan::multiway_join(
+        vec![Plan::source("reach"), Plan::source("edge")],
+        vec![vec![(1, 0), (0, 1)]],
+        vec![(0, 0), (1, 0), (0, 1), (1, 1)],
+    ).project(vec![0, 3]);
+
+    let program = compile(text).unwrap();
+    assert_eq!(program.facts, vec![("edge".to_string(), vec![vec![Value::Usize(1), Value::Usize(2)], vec![Value::Usize(2), Value::Usize(3)]])]);
+    assert_eq!(
+        program.query,
+        Query::new()
+            .add_rule(Plan::concatenate(vec![base, step]).distinct().into_rule("reach"))
+            .add_rule(Plan::source("reach").inspect("reach").into_rule("reach.output")),
+    );
+
+    let query = program.query.clone();
+    assert_eq!(
+        program.commands(),
+        vec![
+            Command::CreateInput("edge".to_string(), vec![vec![Value::Usize(1), Value::Usize(2)], vec![Value::Usize(2), Value::Usize(3)]]),
+            query.into_command(),
+        ],
+    );
+}
+
+#[test]
+fn dependency_order() {
+    // Rules are installed after the rules they depend on.
+    compiles_to(
+        "two(X, Z) :- hop(X, Y), hop(Y, Z).
+         hop(X, Y) :- edge(X, Y).",
+        Query::new()
+            .add_rule(Plan::source("edge").project(vec![0, 1]).distinct().into_rule("hop"))
+            .add_rule(
+                Plan::multiway_join(
+                    vec![Plan::source("hop"), Plan::source("hop")],
+                    vec![vec![(1, 0), (0, 1)]],
+                    vec![(0, 0), (1, 0), (0, 1), (1, 1)],
+                )
+                .project(vec![0, 3])
+                .distinct()
+                .into_rule("two")
+            ),
+    );
+}
+
+#[test]
+fn predicates() {
+    compiles_to(
+        "from_one(Y) :- edge(1, Y).",
+        Query::new().add_rule(
+            Plan::source("edge")
+                .filter(Predicate::Equal(0, SecondArgument::Constant(Value::Usize(1))))
+                .project(vec![1])
+                .distinct()
+                .into_rule("from_one")
+        ),
+    );
+    compiles_to(
+        "loop(X) :- edge(X, X), X > 1.",
+        Query::new().add_rule(
+            Plan::source("edge")
+                .filter(Predicate::All(vec![
+                    Predicate::Equal(1, SecondArgument::Position(0)),
+                    Predicate::GreaterThan(0, SecondArgument::Constant(Value::Usize(1))),
+                ]))
+                .project(vec![0])
+                .distinct()
+                .into_rule("loop")
+        ),
+    );
+}
+
+#[test]
+fn negation() {
+    let edges = Plan::source("edge");
+    let reversed = Plan::source("edge").project(vec![0, 1]).distinct();
+    compiles_to(
+        "lonely(X, Y) :- edge(X, Y), !edge(Y, X).",
+        Query::new().add_rule(
+            edges.clone()
+                .concat(edges.join(reversed, vec![(1, 0), (0, 1)]).project(vec![1, 0]).negate())
+                .project(vec![0, 1])
+                .distinct()
+                .into_rule("lonely")
+        ),
+    );
+
+    // Negation of relations in earlier strata is permitted.
+    assert!(compile("b(X) :- q(X). a(X) :- q(X), !b(X).").is_ok());
+}
+
+#[test]
+fn errors() {
+    fails_with("pair(X, Y) :- node(X), node(Y).", "requires a cross join");
+    fails_with("p(X, Z) :- q(X).", "Variable Z is not bound by a positive atom");
+    fails_with("p(X) :- q(X), Y < 3.", "Variable Y is not bound by a positive atom");
+    fails_with("p(X) :- q(X), !r(Y).", "Variable Y in negated r is not bound by a positive atom");
+    fails_with("p(X) :- !q(X).", "has no positive atoms");
+    fails_with("p(X) :- q(X). )", "Expected relation");
+    fails_with("p(X) :- q(X)", "Expected \".\"");
+    fails_with("e(1). e(X) :- q(X).", "defined by both facts and rules");
+    fails_with("e(1). f(X) :- e(X, Y).", "used with 1 and 2 arguments");
+    fails_with("e(X).", "Facts must only contain constants");
+    fails_with("p(1) :- q(X).", "Rule heads must only contain variables");
+    fails_with(".output missing", "Unknown relation: missing");
+    fails_with(".input edge", "Unknown directive");
+}
+
+#[test]
+fn stratification() {
+    fails_with("p(X) :- q(X), !p(X).", "Negation of p in a rule for p is not stratified");
+    fails_with(
+        "a(X) :- q(X), !b(X).
+         b(X) :- q(X), c(X).
+         c(X) :- a(X).",
+        "Negation of b in a rule for a is not stratified",
+    );
+}
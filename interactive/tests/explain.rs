@@ -0,0 +1,99 @@
+extern crate timely;
+extern crate interactive;
+
+use std::time::Duration;
+
+use interactive::{Command, Manager, Plan, Query};
+use interactive::concrete::Value;
+use interactive::plan::{Aggregate, Explainer};
+
+/// The lines describing how `query` would be rendered against the arrangements of `manager`.
+fn explain(manager: &Manager<Value>, query: &Query<Value>) -> Vec<String> {
+    let mut explainer = Explainer::new(&manager.traces);
+    explainer.query(query);
+    explainer.finish().lines().map(|line| line.to_string()).collect()
+}
+
+fn edges() -> Vec<Vec<Value>> {
+    vec![vec![Value::Usize(0), Value::Usize(1)], vec![Value::Usize(1), Value::Usize(2)]]
+}
+
+#[test]
+fn join_reuses_installed_arrangements() {
+    timely::execute_directly(move |worker| {
+
+        let pairs = Plan::source("edges").join(Plan::source("edges"), vec![(1, 0)]);
+        let query = Query::new().add_rule(pairs.clone().into_rule("pairs"));
+
+        let mut manager = Manager::<Value>::new();
+        Command::CreateInput("edges".to_string(), edges()).execute(&mut manager, worker);
+        assert_eq!(explain(&manager, &query), vec![
+            "Rule \"pairs\": arrange unkeyed (new), exchanging by record",
+            "  Join on [(1, 0)]",
+            "    Arrange by [1] (new), exchanging by columns [1]",
+            "      Source \"edges\" from arrangement (reused)",
+            "    Arrange by [0] (new), exchanging by columns [0]",
+            "      Source \"edges\" (shared with above)",
+        ]);
+
+        // Counting edges by source arranges them as the join would.
+        let degrees = Plan::source("edges").reduce(vec![0], vec![Aggregate::Count]);
+        Command::from(degrees.into_rule("degrees")).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+        assert_eq!(explain(&manager, &query), vec![
+            "Rule \"pairs\": arrange unkeyed (new), exchanging by record",
+            "  Join on [(1, 0)]",
+            "    Arrange by [1] (new), exchanging by columns [1]",
+            "      Source \"edges\" from arrangement (reused)",
+            "    Arrangement by [0] of Source \"edges\" (reused)",
+        ]);
+
+        // Rendering builds the arrangement described as new, which a second join reuses.
+        Command::Query(query).execute(&mut manager, worker);
+        assert!(manager.traces.contains_keyed(&Plan::source("edges"), &[1]));
+        let again = Query::new().add_rule(pairs.into_rule("again"));
+        assert_eq!(explain(&manager, &again), vec![
+            "Rule \"again\": arrange unkeyed (new), exchanging by record",
+            "  Join on [(1, 0)]",
+            "    Arrangement by [1] of Source \"edges\" (reused)",
+            "    Arrangement by [0] of Source \"edges\" (reused)",
+        ]);
+
+        manager.shutdown(worker);
+    });
+}
+
+#[test]
+fn distinct_reuses_installed_arrangements() {
+    timely::execute_directly(move |worker| {
+
+        let nodes = Plan::source("edges").project(vec![0]).distinct();
+        let query = Query::new().add_rule(nodes.clone().into_rule("nodes"));
+
+        // The input of the distinct is not yet arranged.
+        let mut manager = Manager::<Value>::new();
+        Command::CreateInput("edges".to_string(), edges()).execute(&mut manager, worker);
+        let lines = explain(&manager, &query);
+        assert_eq!(lines[1], "  Distinct");
+        assert_eq!(lines[2], "    Arrange unkeyed (new), exchanging by record");
+
+        // A rule producing the input provides its arrangement.
+        Command::from(Plan::source("edges").project(vec![0]).into_rule("sources")).execute(&mut manager, worker);
+        assert!(manager.traces.contains_unkeyed(&Plan::source("edges").project(vec![0])));
+        assert_eq!(explain(&manager, &query), vec![
+            "Rule \"nodes\": arrange unkeyed (new), exchanging by record",
+            "  Distinct",
+            "    Arrangement of Map (reused)",
+        ]);
+
+        // Once installed, the distinct is drawn from its own arrangement.
+        Command::Query(query).execute(&mut manager, worker);
+        let again = Query::new().add_rule(nodes.into_rule("again"));
+        assert_eq!(explain(&manager, &again), vec![
+            "Rule \"again\": arrange unkeyed (new), exchanging by record",
+            "  Distinct of its own arrangement (reused)",
+        ]);
+
+        manager.shutdown(worker);
+    });
+}
@@ -0,0 +1,93 @@
+extern crate timely;
+extern crate interactive;
+
+use std::time::Duration;
+
+use interactive::{Command, Manager, Plan};
+use interactive::concrete::Value;
+
+/// Names published by the live (not dropped) dataflows of `manager`, sorted.
+fn live(manager: &Manager<Value>) -> Vec<String> {
+    let mut names =
+    manager
+        .dataflows
+        .values()
+        .filter(|dataflow| !dataflow.dropped)
+        .flat_map(|dataflow| dataflow.names.iter().cloned())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[test]
+fn reference_counted_retirement() {
+    timely::execute_directly(move |worker| {
+
+        let mut manager = Manager::<Value>::new();
+        let commands = vec![
+            Command::CreateInput("edges".to_string(), vec![vec![Value::Usize(0), Value::Usize(1)]]),
+            Plan::source("edges").project(vec![1]).into_rule("dsts").into(),
+            Plan::source("dsts").distinct().into_rule("distinct").into(),
+            Command::AdvanceTime(Duration::from_secs(1)),
+        ];
+        for command in commands.into_iter() {
+            command.execute(&mut manager, worker);
+        }
+        assert_eq!(manager.dataflows.len(), 3);
+
+        // Dropping a query whose arrangements are imported retains its dataflow.
+        Command::DropQuery("dsts".to_string()).execute(&mut manager, worker);
+        assert_eq!(live(&manager), vec!["distinct".to_string(), "edges".to_string()]);
+        assert_eq!(manager.dataflows.len(), 3);
+
+        // Inputs are not dropped as queries.
+        Command::DropQuery("edges".to_string()).execute(&mut manager, worker);
+        assert_eq!(live(&manager), vec!["distinct".to_string(), "edges".to_string()]);
+
+        // Dropping the last user drops both query dataflows.
+        Command::DropQuery("distinct".to_string()).execute(&mut manager, worker);
+        assert_eq!(live(&manager), vec!["edges".to_string()]);
+        assert_eq!(manager.dataflows.len(), 1);
+
+        // The input dataflow is dropped with the input.
+        Command::DropInput("edges".to_string()).execute(&mut manager, worker);
+        assert!(manager.dataflows.is_empty());
+
+        manager.shutdown(worker);
+    });
+}
+
+#[test]
+fn join_statistics() {
+    timely::execute_directly(move |worker| {
+
+        let edge = |src, dst| vec![Value::Usize(src), Value::Usize(dst)];
+        let edges = Plan::source("edges");
+        let paths = Plan::multiway_join(vec![edges.clone(), edges.clone()], vec![vec![(1, 0), (0, 1)]], vec![(0, 0), (1, 0), (1, 1)]);
+
+        // Inputs alone maintain no statistics.
+        let mut manager = Manager::<Value>::new();
+        Command::CreateInput("edges".to_string(), vec![edge(0, 1), edge(0, 1), edge(1, 2)]).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+        assert_eq!(manager.traces.get_statistics(&edges, None), None);
+
+        // A multiway join maintains statistics for its sources and the arrangements it builds.
+        Command::from(paths.into_rule("paths")).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(2)).execute(&mut manager, worker);
+        assert_eq!(manager.traces.get_statistics(&edges, None), Some((3, 2)));
+        assert_eq!(manager.traces.get_statistics(&edges.clone().project(vec![0, 1]), Some(&[0])), Some((3, 2)));
+
+        let updates = vec![(edge(0, 1), Duration::from_secs(2), -1), (edge(2, 0), Duration::from_secs(2), 1)];
+        Command::UpdateInput("edges".to_string(), updates).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(3)).execute(&mut manager, worker);
+        assert_eq!(manager.traces.get_statistics(&edges, None), Some((3, 3)));
+        assert_eq!(manager.traces.get_statistics(&edges.clone().project(vec![0, 1]), Some(&[0])), Some((3, 3)));
+        assert_eq!(manager.traces.get_statistics(&edges, Some(&[0])), None);
+
+        // The statistics are retired with the join.
+        Command::DropQuery("paths".to_string()).execute(&mut manager, worker);
+        assert_eq!(manager.traces.get_statistics(&edges, None), None);
+
+        manager.shutdown(worker);
+    });
+}
@@ -0,0 +1,214 @@
+extern crate timely;
+extern crate differential_dataflow;
+extern crate interactive;
+
+use std::time::Duration;
+
+use differential_dataflow::trace::{Cursor, TraceReader};
+
+use interactive::{Command, Manager, Plan, Query};
+use interactive::concrete::Value;
+use interactive::plan::{Aggregate, MultiwayJoin};
+
+/// The consolidated contents of the collection bound to `name`, sorted.
+fn contents(manager: &mut Manager<Value>, name: &str) -> Vec<(Vec<Value>, isize)> {
+    let mut trace = manager.traces.get_unkeyed(&Plan::source(name)).expect("collection not found");
+    let (mut cursor, storage) = trace.cursor();
+    let mut results = Vec::new();
+    while let Some(key) = cursor.get_key(&storage) {
+        let mut sum = 0;
+        cursor.map_times(&storage, |_, diff| sum += diff);
+        if sum != 0 { results.push((key.clone(), sum)); }
+        cursor.step_key(&storage);
+    }
+    results
+}
+
+fn row(values: &[usize]) -> Vec<Value> {
+    values.iter().map(|value| Value::Usize(*value)).collect()
+}
+
+#[test]
+fn reduce_by_non_prefix_keys() {
+    timely::execute_directly(move |worker| {
+
+        // Records are `(a, b, c)`, grouped by `(c, a)` so that `b` is the only value column.
+        let totals =
+        Plan::source("records")
+            .reduce(vec![2, 0], vec![
+                Aggregate::Count,
+                Aggregate::Sum(1),
+                Aggregate::Min(1),
+                Aggregate::Max(1),
+                Aggregate::ArgMax(1, 0),
+                Aggregate::Min(2),
+            ]);
+
+        let mut manager = Manager::<Value>::new();
+        let records = vec![row(&[0, 5, 1]), row(&[0, 7, 1]), row(&[0, 7, 1]), row(&[1, 3, 1]), row(&[0, 2, 2])];
+        Command::CreateInput("records".to_string(), records).execute(&mut manager, worker);
+        Command::from(totals.clone().into_rule("totals")).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+
+        let expected = vec![
+            (row(&[1, 0, 3, 19, 5, 7, 0, 1]), 1),
+            (row(&[1, 1, 1, 3, 3, 3, 1, 1]), 1),
+            (row(&[2, 0, 1, 2, 2, 2, 0, 2]), 1),
+        ];
+        assert_eq!(contents(&mut manager, "totals"), expected);
+
+        // A second rule with the same reduction imports its output arrangement, and keeps it alive.
+        assert!(manager.traces.contains_keyed(&totals, &[0, 1]));
+        Command::from(totals.into_rule("again")).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(2)).execute(&mut manager, worker);
+        assert_eq!(contents(&mut manager, "again"), expected);
+
+        Command::DropQuery("totals".to_string()).execute(&mut manager, worker);
+        assert_eq!(manager.dataflows.len(), 3);
+        assert_eq!(contents(&mut manager, "again"), expected);
+
+        manager.shutdown(worker);
+    });
+}
+
+#[test]
+fn mutually_recursive_rules() {
+    timely::execute_directly(move |worker| {
+
+        // Nodes reachable from the roots, where each step follows an edge from a reached node.
+        let query =
+        Query::new()
+            .add_rule(Plan::source("reach").join(Plan::source("edge"), vec![(0, 0)]).project(vec![1]).into_rule("step"))
+            .add_rule(Plan::source("root").concat(Plan::source("step")).distinct().into_rule("reach"));
+
+        let mut manager = Manager::<Value>::new();
+        Command::CreateInput("edge".to_string(), vec![row(&[0, 1]), row(&[1, 2]), row(&[2, 0]), row(&[5, 6])]).execute(&mut manager, worker);
+        Command::CreateInput("root".to_string(), vec![row(&[0])]).execute(&mut manager, worker);
+        Command::Query(query).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+        assert_eq!(contents(&mut manager, "reach"), vec![(row(&[0]), 1), (row(&[1]), 1), (row(&[2]), 1)]);
+
+        // A new edge extends the fixed point.
+        Command::UpdateInput("edge".to_string(), vec![(row(&[2, 5]), Duration::from_secs(1), 1)]).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(2)).execute(&mut manager, worker);
+        let expected = vec![(row(&[0]), 1), (row(&[1]), 1), (row(&[2]), 1), (row(&[5]), 1), (row(&[6]), 1)];
+        assert_eq!(contents(&mut manager, "reach"), expected);
+
+        manager.shutdown(worker);
+    });
+}
+
+#[test]
+fn reject_iterate_within_recursion() {
+    timely::execute_directly(move |worker| {
+
+        // The rule refers to itself from within a nested loop, which cannot be rendered.
+        let inner = Plan::source("outer").concat(Plan::source("inner")).distinct().into_rule("inner");
+        let query = Query::new().add_rule(Plan::iterate(vec![inner], "inner").into_rule("outer"));
+        assert!(query.recursive_rules()[0].plan.validate().is_err());
+
+        let mut manager = Manager::<Value>::new();
+        Command::Query(query).execute(&mut manager, worker);
+        assert!(manager.dataflows.is_empty());
+        assert!(manager.traces.get_unkeyed(&Plan::source("outer")).is_none());
+
+        // Loops that do not refer to enclosing rules are accepted.
+        let inner = Plan::source("inner").concat(Plan::source("inner")).distinct().into_rule("inner");
+        assert_eq!(Plan::iterate(vec![inner], "inner").validate(), Ok(()));
+
+        manager.shutdown(worker);
+    });
+}
+
+/// Pairs `a(x, y)` with both `b(y, z)` and `c(y, w)`, so that either may be joined first.
+fn star() -> MultiwayJoin<Value> {
+    MultiwayJoin {
+        results: vec![(0, 0), (1, 0), (1, 1), (1, 2)],
+        sources: vec![Plan::source("a"), Plan::source("b"), Plan::source("c")],
+        equalities: vec![vec![(1, 0), (0, 1), (0, 2)]],
+    }
+}
+
+#[test]
+fn delta_order_by_fanout() {
+    timely::execute_directly(move |worker| {
+
+        let mut manager = Manager::<Value>::new();
+        Command::CreateInput("a".to_string(), vec![row(&[0, 0])]).execute(&mut manager, worker);
+        Command::CreateInput("b".to_string(), (0 .. 4).map(|z| row(&[0, z])).collect()).execute(&mut manager, worker);
+        Command::CreateInput("c".to_string(), vec![row(&[0, 0])]).execute(&mut manager, worker);
+        assert_eq!(star().delta_order(0, &manager.traces), vec![0, 1, 2]);
+
+        // Rendering the join arranges both relations and maintains statistics for them.
+        Command::from(Plan::MultiwayJoin(star()).into_rule("star")).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+        assert_eq!(contents(&mut manager, "star").len(), 4);
+
+        // Each change to `a` matches four records of `b`, but only one of `c`.
+        assert_eq!(star().delta_order(0, &manager.traces), vec![0, 2, 1]);
+
+        manager.shutdown(worker);
+    });
+}
+
+#[test]
+fn delta_order_by_reuse() {
+    timely::execute_directly(move |worker| {
+
+        let mut manager = Manager::<Value>::new();
+        for name in ["a", "b", "c"].iter() {
+            Command::CreateInput(name.to_string(), vec![row(&[0, 0])]).execute(&mut manager, worker);
+        }
+        assert_eq!(star().delta_order(0, &manager.traces), vec![0, 1, 2]);
+
+        // A binary join arranges `c` as the multiway join would, without statistics.
+        let join = Plan::source("a").join(Plan::source("c").project(vec![0, 1]), vec![(1, 0)]);
+        Command::from(join.into_rule("pairs")).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+        assert!(manager.traces.contains_keyed(&Plan::source("c").project(vec![0, 1]), &[0]));
+        assert_eq!(star().delta_order(0, &manager.traces), vec![0, 2, 1]);
+
+        manager.shutdown(worker);
+    });
+}
+
+#[test]
+fn triangles_match_binary_joins() {
+    timely::execute_directly(move |worker| {
+
+        // Triangles `(x, y, z)` with edges `x -> y`, `y -> z`, and `x -> z`.
+        let triangles = MultiwayJoin {
+            results: vec![(0, 0), (1, 0), (1, 1)],
+            sources: vec![Plan::source("edges"), Plan::source("edges"), Plan::source("edges")],
+            equalities: vec![vec![(0, 0), (0, 2)], vec![(1, 0), (0, 1)], vec![(1, 1), (1, 2)]],
+        };
+        assert!(triangles.is_cyclic());
+
+        // The repeated edge contributes its multiplicity to each of its triangles.
+        let edges = vec![row(&[0, 1]), row(&[0, 1]), row(&[1, 2]), row(&[0, 2]), row(&[1, 3]), row(&[0, 3]), row(&[2, 3]), row(&[3, 4])];
+        let mut manager = Manager::<Value>::new();
+        Command::CreateInput("edges".to_string(), edges).execute(&mut manager, worker);
+        Command::from(Plan::MultiwayJoin(triangles.clone()).into_rule("triangles")).execute(&mut manager, worker);
+        Command::from(triangles.binary_joins().into_rule("binary")).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(1)).execute(&mut manager, worker);
+
+        let expected = vec![(row(&[0, 1, 2]), 2), (row(&[0, 1, 3]), 2), (row(&[0, 2, 3]), 1), (row(&[1, 2, 3]), 1)];
+        assert_eq!(contents(&mut manager, "triangles"), expected);
+        assert_eq!(contents(&mut manager, "binary"), expected);
+
+        // Changes to the edges of several triangles at once.
+        let updates = vec![
+            (row(&[1, 2]), Duration::from_secs(1), -1),
+            (row(&[2, 4]), Duration::from_secs(1), 1),
+            (row(&[1, 4]), Duration::from_secs(1), 1),
+        ];
+        Command::UpdateInput("edges".to_string(), updates).execute(&mut manager, worker);
+        Command::AdvanceTime(Duration::from_secs(2)).execute(&mut manager, worker);
+
+        let expected = vec![(row(&[0, 1, 3]), 2), (row(&[0, 2, 3]), 1), (row(&[1, 3, 4]), 1), (row(&[2, 3, 4]), 1)];
+        assert_eq!(contents(&mut manager, "triangles"), expected);
+        assert_eq!(contents(&mut manager, "binary"), expected);
+
+        manager.shutdown(worker);
+    });
+}
@@ -0,0 +1,148 @@
+extern crate interactive;
+
+use std::time::Duration;
+
+use interactive::{Command, Plan};
+use interactive::concrete::Value;
+use interactive::plan::{Aggregate, Predicate};
+use interactive::plan::filter::SecondArgument;
+use interactive::sql::{Catalog, parse};
+
+fn catalog() -> Catalog {
+    let mut catalog = Catalog::new();
+    catalog.define("edges", vec!["src".to_string(), "dst".to_string()]);
+    catalog.define("nodes", vec!["id".to_string(), "name".to_string()]);
+    catalog
+}
+
+fn compiles_to(text: &str, plan: Plan<Value>) {
+    assert_eq!(catalog().compile("q", text), Ok(plan.into_rule("q").into_query()));
+}
+
+fn fails_with(text: &str, error: &str) {
+    match catalog().compile("q", text) {
+        Ok(query) => panic!("{:?} compiled to {:?}", text, query),
+        Err(message) => assert!(message.contains(error), "{:?} failed with {:?}", text, message),
+    }
+}
+
+#[test]
+fn select() {
+    compiles_to("SELECT * FROM edges", Plan::source("edges"));
+    compiles_to("SELECT dst FROM edges;", Plan::source("edges").project(vec![1]));
+    compiles_to(
+        "SELECT DISTINCT dst FROM edges WHERE src = 3 AND NOT dst < 2",
+        Plan::source("edges")
+            .filter(Predicate::All(vec![
+                Predicate::Equal(0, SecondArgument::Constant(Value::Usize(3))),
+                Predicate::Not(Box::new(Predicate::LessThan(1, SecondArgument::Constant(Value::Usize(2))))),
+            ]))
+            .project(vec![1])
+            .distinct(),
+    );
+    // Literals on the left flip the comparison.
+    compiles_to(
+        "SELECT * FROM edges WHERE 3 < src",
+        Plan::source("edges").filter(Predicate::GreaterThan(0, SecondArgument::Constant(Value::Usize(3)))),
+    );
+}
+
+#[test]
+fn join() {
+    let joined = Plan::multiway_join(
+        vec![Plan::source("edges"), Plan::source("nodes")],
+        vec![vec![(1, 0), (0, 1)]],
+        vec![(0, 0), (1, 0), (0, 1), (1, 1)],
+    );
+    compiles_to(
+        "SELECT e.src, n.name FROM edges e JOIN nodes AS n ON e.dst = n.id WHERE e.src > 3",
+        joined.clone()
+            .filter(Predicate::GreaterThan(0, SecondArgument::Constant(Value::Usize(3))))
+            .project(vec![0, 3]),
+    );
+    compiles_to("SELECT src, name FROM edges, nodes WHERE dst = id", joined.project(vec![0, 3]));
+
+    // Equalities between a table and itself are predicates, even among join constraints.
+    compiles_to(
+        "SELECT a.src FROM edges a JOIN edges b ON a.dst = b.src AND a.src = b.src",
+        Plan::multiway_join(
+            vec![Plan::source("edges"), Plan::source("edges")],
+            vec![vec![(0, 0), (1, 0), (0, 1)]],
+            vec![(0, 0), (1, 0), (0, 1), (1, 1)],
+        )
+        .filter(Predicate::Equal(1, SecondArgument::Position(0)))
+        .project(vec![0]),
+    );
+}
+
+#[test]
+fn group_by() {
+    compiles_to(
+        "SELECT src, COUNT(*) AS degree, MAX(dst) FROM edges GROUP BY src",
+        Plan::source("edges")
+            .reduce(vec![0], vec![Aggregate::Count, Aggregate::Max(1)])
+            .project(vec![0, 1, 2]),
+    );
+    compiles_to(
+        "SELECT SUM(dst), src FROM edges GROUP BY src",
+        Plan::source("edges")
+            .reduce(vec![0], vec![Aggregate::Sum(1)])
+            .project(vec![1, 0]),
+    );
+    fails_with("SELECT dst, COUNT(*) FROM edges GROUP BY src", "not in GROUP BY");
+    fails_with("SELECT * FROM edges GROUP BY src", "Cannot select *");
+}
+
+#[test]
+fn set_operations() {
+    let srcs = Plan::source("edges").project(vec![0]);
+    let ids = Plan::source("nodes").project(vec![0]);
+    compiles_to("SELECT src FROM edges UNION ALL SELECT id FROM nodes", srcs.clone().concat(ids.clone()));
+    compiles_to("SELECT src FROM edges UNION SELECT id FROM nodes", srcs.clone().concat(ids.clone()).distinct());
+    compiles_to(
+        "SELECT src FROM edges EXCEPT SELECT id FROM nodes",
+        srcs.clone().distinct().concat(srcs.distinct().join(ids.distinct(), vec![(0, 0)]).negate()),
+    );
+    fails_with("SELECT src FROM edges UNION SELECT * FROM nodes", "Set operation inputs have 1 and 2 columns");
+}
+
+#[test]
+fn errors() {
+    fails_with("SELECT * FROM edges, nodes", "Cross joins are not supported");
+    fails_with("SELECT * FROM edges a, nodes b, edges c WHERE a.dst = b.id", "Cross joins are not supported");
+    fails_with("SELECT * FROM edges )", "Unexpected trailing input");
+    fails_with("SELECT * FROM edges; SELECT * FROM nodes", "Unexpected trailing input");
+    fails_with("SELECT * FROM paths", "Unknown table: paths");
+    fails_with("SELECT weight FROM edges", "Unknown column");
+    fails_with("SELECT src FROM edges a, edges b WHERE a.dst = b.src", "Ambiguous column: src");
+    fails_with("SELECT * FROM edges a, nodes a WHERE a.dst = a.id", "Duplicate table name: a");
+    fails_with("SELECT * FROM edges WHERE 1 = 2", "Comparisons must reference a column");
+    fails_with("SELECT AVG(src) FROM edges", "Unknown function: avg");
+    fails_with("SELECT * FROM edges WHERE src = 'unterminated", "Unterminated string");
+}
+
+#[test]
+fn execute() {
+    let mut catalog = Catalog::new();
+    let time = Duration::from_secs(1);
+    let mut statements = parse("CREATE TABLE t (a, b); INSERT INTO t VALUES (1, 'x'), (2, TRUE); CREATE VIEW v AS SELECT b FROM t").unwrap().into_iter();
+
+    assert_eq!(catalog.execute(statements.next().unwrap(), time), Ok(vec![Command::CreateInput("t".to_string(), Vec::new())]));
+    assert_eq!(
+        catalog.execute(statements.next().unwrap(), time),
+        Ok(vec![Command::UpdateInput("t".to_string(), vec![
+            (vec![Value::Usize(1), Value::String("x".to_string())], time, 1),
+            (vec![Value::Usize(2), Value::Bool(true)], time, 1),
+        ])]),
+    );
+    assert_eq!(
+        catalog.execute(statements.next().unwrap(), time),
+        Ok(vec![Plan::source("t").project(vec![1]).into_rule("v").into_query().into_command()]),
+    );
+    assert!(statements.next().is_none());
+
+    // Views are recorded in the catalog, and inserted rows must match their tables.
+    assert_eq!(catalog.compile("w", "SELECT b FROM v"), Ok(Plan::source("v").into_rule("w").into_query()));
+    let statement = parse("INSERT INTO t VALUES (1)").unwrap().pop().unwrap();
+    assert!(catalog.execute(statement, time).is_err());
+}
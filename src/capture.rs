@@ -739,6 +739,175 @@ pub mod sink {
     }
 }
 
+/// Methods for exporting arrangements to, and importing them from, other timely clusters.
+///
+/// A `TraceAgent` describes its contents to readers as a sequence of `TraceReplayInstruction`s,
+/// batches of updates and advances of its upper frontier. These methods record that sequence as
+/// CDC v2 `Updates` and `Progress` messages, and replay the messages into a new trace elsewhere.
+/// The imported trace is an ordinary arrangement, and may be shared among the dataflows of the
+/// importing cluster like any other.
+///
+/// The messages carry updates and frontiers, but not the compaction frontiers of either trace.
+/// Updates are exported at their times in the exporting trace, which the trace may already have
+/// advanced by its logical compaction frontier; `export` returns that frontier, and the caller must
+/// convey it to `import`, which starts the imported trace's logical compaction there. Beyond this,
+/// the two traces compact independently: the exporter compacts through the updates it has written,
+/// and the importer compacts as its own readers allow.
+pub mod arrangement {
+
+    use std::cell::RefCell;
+    use std::hash::Hash;
+    use std::rc::Weak;
+
+    use serde::{Deserialize, Serialize};
+
+    use timely::dataflow::Scope;
+    use timely::dataflow::channels::pact::Pipeline;
+    use timely::dataflow::operators::CapabilitySet;
+    use timely::dataflow::operators::generic::Operator;
+    use timely::progress::{Antichain, Timestamp, frontier::AntichainRef};
+    use timely::scheduling::SyncActivator;
+
+    use crate::{lattice::Lattice, AsCollection, ExchangeData};
+    use crate::consolidation::consolidate_updates;
+    use crate::difference::Semigroup;
+    use crate::operators::arrange::{Arrange, Arranged, TraceAgent};
+    use crate::trace::{Batch, BatchReader, Cursor, Trace, TraceReader};
+    use super::{Message, Writer};
+
+    /// Records the contents of `trace` to a pair of CDC v2 writers.
+    ///
+    /// The trace is imported into `scope`, and each update is written once the frontier of the
+    /// import passes its time, consolidated with all other updates at that time. As updates are
+    /// written the compaction frontiers of the exporting handle advance to match, so the trace
+    /// need only retain distinctions that have not yet been sent, and the caller may drop its own
+    /// handle once the export is built. Each worker writes the updates it holds, and the worker
+    /// indicated by `sink_hash` writes the progress statements.
+    ///
+    /// Returns the logical compaction frontier of the exported updates, before which their times
+    /// may not be distinguished. This frontier should be supplied to `import`.
+    pub fn export<G, Tr, BS>(
+        scope: &G,
+        trace: &mut TraceAgent<Tr>,
+        sink_hash: u64,
+        updates_sink: Weak<RefCell<BS>>,
+        progress_sink: Weak<RefCell<BS>>,
+    ) -> Antichain<Tr::Time>
+    where
+        G: Scope<Timestamp = Tr::Time>,
+        Tr: TraceReader + 'static,
+        Tr::Key: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a>,
+        Tr::Val: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a>,
+        Tr::Time: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a> + Timestamp + Lattice,
+        Tr::R: ExchangeData + Hash + Serialize + for<'a> Deserialize<'a> + Semigroup,
+        BS: Writer<Message<(Tr::Key, Tr::Val), Tr::Time, Tr::R>> + 'static,
+    {
+        let arranged = trace.import_named(scope, "ExportedTrace");
+        let stream = arranged.stream;
+        let mut reader = arranged.trace;
+        let since = reader.get_logical_compaction().to_owned();
+
+        let updates = stream.unary_frontier(Pipeline, "TraceExport", move |capability, _info| {
+
+            // Capabilities for the times of pending updates, initially able to cover any time.
+            let mut capabilities = CapabilitySet::new();
+            capabilities.insert(capability);
+            // Updates at times the input frontier has not yet passed.
+            let mut pending = Vec::new();
+            // The frontier through which updates have been written.
+            let mut exported = Antichain::from_elem(<Tr::Time as Timestamp>::minimum());
+
+            move |input, output| {
+
+                input.for_each(|capability, batches| {
+                    capabilities.insert(capability.retain());
+                    for batch in batches.iter() {
+                        let mut cursor = batch.cursor();
+                        while let Some(key) = cursor.get_key(batch) {
+                            while let Some(val) = cursor.get_val(batch) {
+                                cursor.map_times(batch, |time, diff| {
+                                    pending.push(((key.clone(), val.clone()), time.clone(), diff.clone()));
+                                });
+                                cursor.step_val(batch);
+                            }
+                            cursor.step_key(batch);
+                        }
+                    }
+                });
+
+                let frontier = input.frontier().frontier();
+                if exported.elements() != &frontier[..] {
+
+                    // Updates at times the frontier has passed are complete, and may be written.
+                    consolidate_updates(&mut pending);
+                    let (mut ready, retained): (Vec<_>, Vec<_>) =
+                    pending
+                        .drain(..)
+                        .partition(|&(_, ref time, _)| !frontier.less_equal(time));
+                    pending = retained;
+
+                    // Ship ready updates grouped by time, each group under a capability for its time.
+                    ready.sort_by(|x, y| x.1.cmp(&y.1));
+                    let mut lower = 0;
+                    while lower < ready.len() {
+                        let mut upper = lower + 1;
+                        while upper < ready.len() && ready[upper].1 == ready[lower].1 {
+                            upper += 1;
+                        }
+                        let capability = capabilities.delayed(&ready[lower].1);
+                        output.session(&capability).give_iterator(ready[lower .. upper].iter().cloned());
+                        lower = upper;
+                    }
+
+                    // The importing side has all updates not beyond `frontier`, and we needn't retain their distinctions.
+                    // This does not depend on how the importing side compacts, as it keeps its own copy of them.
+                    capabilities.downgrade(&frontier[..]);
+                    reader.set_logical_compaction(frontier);
+                    reader.set_physical_compaction(frontier);
+                    exported = frontier.to_owned();
+                }
+            }
+        });
+
+        super::sink::build(&updates, sink_hash, updates_sink, progress_sink);
+        since
+    }
+
+    /// Reconstructs an arrangement from a source of CDC v2 messages.
+    ///
+    /// The messages are those written by `export`, perhaps by another timely cluster, and they are
+    /// arranged into a new trace of type `Tr`. The returned arrangement's `trace` may be imported into
+    /// other dataflows of this cluster as usual. The source continues to run until the returned token
+    /// is dropped, or until the messages report that the exported trace is complete.
+    ///
+    /// The `since` argument should be the frontier returned by `export`. The returned trace's
+    /// logical compaction starts at this frontier, so that its readers do not distinguish times
+    /// the exporter had already compacted.
+    pub fn import<G, B, I, Tr>(
+        scope: &G,
+        name: &str,
+        since: AntichainRef<Tr::Time>,
+        source_builder: B,
+    ) -> (Box<dyn std::any::Any>, Arranged<G, TraceAgent<Tr>>)
+    where
+        G: Scope<Timestamp = Tr::Time>,
+        B: FnOnce(SyncActivator) -> I,
+        I: Iterator<Item = Message<(Tr::Key, Tr::Val), Tr::Time, Tr::R>> + 'static,
+        Tr: Trace + TraceReader + 'static,
+        Tr::Key: ExchangeData + Hash,
+        Tr::Val: ExchangeData + Hash,
+        Tr::Time: ExchangeData + Hash + Timestamp + Lattice,
+        Tr::R: ExchangeData + Hash + Semigroup,
+        Tr::Batch: Batch<Tr::Key, Tr::Val, Tr::Time, Tr::R>,
+        Tr::Cursor: Cursor<Tr::Key, Tr::Val, Tr::Time, Tr::R>,
+    {
+        let (token, updates) = super::source::build(scope.clone(), source_builder);
+        let mut arranged = updates.as_collection().arrange_named::<Tr>(name);
+        arranged.trace.set_logical_compaction(since);
+        (token, arranged)
+    }
+}
+
 // pub mod kafka {
 
 //     use serde::{Serialize, Deserialize};
@@ -6,14 +6,32 @@
 //! timely dataflow capabilities, exposing more concurrency to the operator implementations
 //! than are evident from the logical times, which appear to execute in sequence.
 
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
 use timely::progress::Timestamp;
+use timely::dataflow::Scope;
 use timely::dataflow::operators::Input as TimelyInput;
 use timely::dataflow::operators::input::Handle;
+use timely::dataflow::operators::generic::source;
 use timely::dataflow::scopes::ScopeParent;
+use timely::dataflow::scopes::child::Iterative;
+use timely::order::{PartialOrder, Product, TotalOrder};
+use timely::progress::{Antichain, PathSummary};
+use timely::progress::timestamp::Refines;
+use timely::progress::frontier::AntichainRef;
+use timely::scheduling::Activator;
 
-use ::Data;
+use ::{Data, ExchangeData, Hashable};
 use ::difference::Semigroup;
 use collection::{Collection, AsCollection};
+use consolidation::consolidate_updates;
+use operators::arrange::{Arranged, MemoryBudget, TraceAgent, TraceReplayInstruction};
+use operators::arrange::upsert::{arrange_from_upsert, arrange_from_upsert_with_ttl, arrange_from_upsert_partial};
+use trace::{Trace, Batch, BatchReader, Builder, Cursor, TraceReader};
+use trace::implementations::ord::OrdValSpine;
+use trace::wrappers::frontier::TraceFrontier;
 
 /// Create a new collection and input handle to control the collection.
 pub trait Input : TimelyInput {
@@ -190,6 +208,7 @@ pub struct InputSession<T: Timestamp+Clone, D: Data, R: Semigroup> {
     time: T,
     buffer: Vec<(D, T, R)>,
     handle: Handle<T,(D,T,R)>,
+    budget: Option<MemoryBudget>,
 }
 
 impl<T: Timestamp+Clone, D: Data> InputSession<T, D, isize> {
@@ -232,6 +251,7 @@ impl<T: Timestamp+Clone, D: Data, R: Semigroup> InputSession<T, D, R> {
             time: handle.time().clone(),
             buffer: Vec::new(),
             handle,
+            budget: None,
         }
     }
 
@@ -241,6 +261,7 @@ impl<T: Timestamp+Clone, D: Data, R: Semigroup> InputSession<T, D, R> {
             time: handle.time().clone(),
             buffer: Vec::new(),
             handle,
+            budget: None,
         }
     }
 
@@ -292,6 +313,27 @@ impl<T: Timestamp+Clone, D: Data, R: Semigroup> InputSession<T, D, R> {
         self.time = time;
     }
 
+    /// Associates a memory budget with the session, which `try_advance_to` observes.
+    pub fn set_budget(&mut self, budget: MemoryBudget) {
+        self.budget = Some(budget);
+    }
+
+    /// Advances the logical time for future records, unless the session's budget is exceeded.
+    ///
+    /// Returns `false` without advancing the time if the session has a memory budget that is under
+    /// pressure, in which case the caller should step the worker (allowing arrangements to compact)
+    /// before trying again. Advancing to the current time always succeeds.
+    pub fn try_advance_to(&mut self, time: T) -> bool {
+        let pressure = self.budget.as_ref().map(|b| b.under_pressure()).unwrap_or(false);
+        if pressure && time != self.time {
+            false
+        }
+        else {
+            self.advance_to(time);
+            true
+        }
+    }
+
     /// Reveals the current time of the session.
     pub fn epoch(&self) -> &T { &self.time }
     /// Reveals the current time of the session.
@@ -306,3 +348,665 @@ impl<T: Timestamp+Clone, D: Data, R: Semigroup> Drop for InputSession<T, D, R> {
         self.flush();
     }
 }
+
+/// A scenario coordinate, used to distinguish hypothetical branches of a computation.
+///
+/// Scenarios form a "flat" lattice: the base scenario is less than all others, distinct
+/// scenarios are incomparable, and the join of distinct scenarios is a top element greater
+/// than all others. When used as the inner coordinate of a `Product` time, updates at the
+/// base scenario are visible to every branch, while updates at a specific scenario are only
+/// visible to that branch.
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq, Ord, PartialOrd, Abomonation, Serialize, Deserialize)]
+pub struct Scenario {
+    id: usize,
+}
+
+impl Scenario {
+    /// The base scenario, whose updates are visible in every branch.
+    pub fn base() -> Self { Scenario { id: 0 } }
+    /// The top element, greater than or equal to every scenario.
+    pub fn top() -> Self { Scenario { id: usize::max_value() } }
+    /// The identifier of the scenario, with zero indicating the base.
+    pub fn id(&self) -> usize { self.id }
+}
+
+impl PartialOrder for Scenario {
+    #[inline]
+    fn less_equal(&self, other: &Self) -> bool {
+        self.id == 0 || self.id == other.id || other.id == usize::max_value()
+    }
+}
+
+impl Lattice for Scenario {
+    #[inline]
+    fn join(&self, other: &Self) -> Self {
+        if self.less_equal(other) { *other }
+        else if other.less_equal(self) { *self }
+        else { Scenario::top() }
+    }
+    #[inline]
+    fn meet(&self, other: &Self) -> Self {
+        if self.less_equal(other) { *self }
+        else if other.less_equal(self) { *other }
+        else { Scenario::base() }
+    }
+}
+
+impl PathSummary<Scenario> for () {
+    fn results_in(&self, timestamp: &Scenario) -> Option<Scenario> { Some(*timestamp) }
+    fn followed_by(&self, other: &Self) -> Option<Self> { Some(*other) }
+}
+
+impl Timestamp for Scenario {
+    fn minimum() -> Self { Scenario::base() }
+    type Summary = ();
+}
+
+impl Refines<()> for Scenario {
+    fn to_inner(_outer: ()) -> Self { Scenario::base() }
+    fn to_outer(self) -> () { () }
+    fn summarize(_summary: <Self as Timestamp>::Summary) -> () { () }
+}
+
+/// An input session whose updates can be forked into hypothetical scenarios.
+///
+/// Updates introduced through `update` form the base collection, and are visible in every
+/// scenario. Updates introduced through `update_scenario` are visible only in the indicated
+/// scenario, which allows "what-if" questions to be asked of a computation without disturbing
+/// its base results. The collection is introduced at the root scope tagged by scenario, and
+/// should be brought into an iterative scope with `Scenario` coordinates using `enter`, where
+/// the time of each update is `Product::new(time, scenario)`.
+///
+/// # Examples
+///
+/// ```
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use timely::Config;
+/// use timely::dataflow::Scope;
+/// use timely::order::Product;
+/// use differential_dataflow::input::{Scenario, ScenarioSession};
+/// use differential_dataflow::operators::Count;
+/// use differential_dataflow::operators::arrange::ArrangeBySelf;
+///
+/// fn main() {
+///     ::timely::execute(Config::thread(), |worker| {
+///
+///         let mut session = ScenarioSession::<usize, u32, isize>::new();
+///         let (mut trace, probe) = worker.dataflow(|scope| {
+///             let data = session.to_collection(scope);
+///             scope.iterative::<Scenario,_,_>(|child| {
+///                 let arranged =
+///                 ScenarioSession::enter(&data, child)
+///                     .map(|x| x % 2)
+///                     .count()
+///                     .arrange_by_self();
+///                 (arranged.trace, arranged.stream.probe())
+///             })
+///         });
+///
+///         session.insert(1);
+///         session.insert(2);
+///         let branch = session.fork();
+///         session.update_scenario(branch, 3, 1);
+///         session.advance_to(1);
+///         session.flush();
+///
+///         while probe.less_than(&Product::new(*session.time(), Scenario::base())) {
+///             worker.step();
+///         }
+///
+///         let base = session.read(&mut trace, Scenario::base(), 0);
+///         assert_eq!(base, vec![((0, 1), (), 1), ((1, 1), (), 1)]);
+///         let other = session.read(&mut trace, branch, 0);
+///         assert_eq!(other, vec![((0, 1), (), 1), ((1, 2), (), 1)]);
+///
+///     }).unwrap();
+/// }
+/// ```
+pub struct ScenarioSession<T: Timestamp+Clone, D: Data, R: Semigroup> {
+    session: InputSession<T, (D, Scenario), R>,
+    scenarios: usize,
+}
+
+impl<T: Timestamp+Clone+Lattice, D: Data> ScenarioSession<T, D, isize> {
+    /// Adds an element to the base collection.
+    pub fn insert(&mut self, element: D) { self.update(element, 1); }
+    /// Removes an element from the base collection.
+    pub fn remove(&mut self, element: D) { self.update(element,-1); }
+}
+
+impl<T: Timestamp+Clone+Lattice, D: Data, R: Semigroup> ScenarioSession<T, D, R> {
+
+    /// Allocates a new scenario session.
+    pub fn new() -> Self {
+        Self::from(InputSession::new())
+    }
+
+    /// Creates a scenario session from an input session of scenario-tagged data.
+    pub fn from(session: InputSession<T, (D, Scenario), R>) -> Self {
+        ScenarioSession {
+            session,
+            scenarios: 0,
+        }
+    }
+
+    /// Introduces the session as a collection of scenario-tagged data.
+    pub fn to_collection<G: TimelyInput>(&mut self, scope: &mut G) -> Collection<G, (D, Scenario), R>
+    where
+        G: ScopeParent<Timestamp=T>,
+    {
+        self.session.to_collection(scope)
+    }
+
+    /// Brings scenario-tagged data into a scope whose inner coordinate indicates the scenario.
+    pub fn enter<'a, G>(collection: &Collection<G, (D, Scenario), R>, child: &Iterative<'a, G, Scenario>) -> Collection<Iterative<'a, G, Scenario>, D, R>
+    where
+        G: ::timely::dataflow::Scope<Timestamp=T>,
+        T: ::std::hash::Hash,
+    {
+        collection
+            .enter_at(child, |(_, scenario)| *scenario)
+            .map(|(data, _)| data)
+    }
+
+    /// Allocates a new scenario, distinct from all previously allocated scenarios.
+    pub fn fork(&mut self) -> Scenario {
+        self.scenarios += 1;
+        assert!(self.scenarios < usize::max_value());
+        Scenario { id: self.scenarios }
+    }
+
+    /// Adds to the weight of an element in the base collection.
+    pub fn update(&mut self, element: D, change: R) {
+        self.session.update((element, Scenario::base()), change);
+    }
+
+    /// Adds to the weight of an element in the collection of one scenario only.
+    pub fn update_scenario(&mut self, scenario: Scenario, element: D, change: R) {
+        assert!(scenario != Scenario::top());
+        self.session.update((element, scenario), change);
+    }
+
+    /// Forces buffered data into the timely dataflow input, and advances its time to match that of the session.
+    pub fn flush(&mut self) { self.session.flush(); }
+
+    /// Advances the logical time for future records, in all scenarios.
+    pub fn advance_to(&mut self, time: T) { self.session.advance_to(time); }
+
+    /// Reveals the current time of the session.
+    pub fn time(&self) -> &T { self.session.time() }
+
+    /// Reads the accumulated contents of a trace in `scenario` as of `time`.
+    ///
+    /// The trace should be an arrangement of a computation applied to the scenario collection, and the
+    /// computation should be complete through `Product::new(time, scenario)`. The trace is viewed through
+    /// a `TraceFrontier` at that time, so that the results do not depend on how it has been compacted.
+    /// Results are sorted by key and value, and include only non-zero accumulations.
+    pub fn read<Tr>(&self, trace: &mut Tr, scenario: Scenario, time: T) -> Vec<(Tr::Key, Tr::Val, Tr::R)>
+    where
+        Tr: TraceReader<Time=Product<T, Scenario>>+Clone,
+        Tr::Batch: Clone,
+        Tr::Key: Clone+'static,
+        Tr::Val: Clone+'static,
+        Tr::R: Semigroup,
+    {
+        let query = Product::new(time, scenario);
+        let frontier = [query.clone()];
+        let mut view = TraceFrontier::make_from(trace.clone(), AntichainRef::new(&frontier));
+
+        let mut results = Vec::new();
+        let (mut cursor, storage) = view.cursor();
+        while let Some(key) = cursor.get_key(&storage) {
+            while let Some(val) = cursor.get_val(&storage) {
+                let mut sum: Option<Tr::R> = None;
+                cursor.map_times(&storage, |time, diff| {
+                    if time.less_equal(&query) {
+                        match sum.as_mut() {
+                            Some(sum) => sum.plus_equals(diff),
+                            None => sum = Some(diff.clone()),
+                        }
+                    }
+                });
+                if let Some(sum) = sum {
+                    if !sum.is_zero() {
+                        results.push((key.clone(), val.clone(), sum));
+                    }
+                }
+                cursor.step_val(&storage);
+            }
+            cursor.step_key(&storage);
+        }
+        results
+    }
+
+    /// Closes the input, flushing and sealing the wrapped timely input.
+    pub fn close(self) { }
+}
+
+/// An input session that installs updates directly as batches in an arrangement.
+///
+/// Updates supplied to a `BulkSession` do not pass through timely dataflow channels, nor through
+/// the arrangement's batcher. Instead, they are buffered until the session's time advances and is
+/// flushed, at which point they are sorted, consolidated, and assembled into a single batch that
+/// is installed in the trace and sent along the arrangement's stream. This makes the cost of a
+/// large initial load roughly the cost of sorting it, and if the updates are supplied already
+/// sorted and consolidated through `load_sorted` even the sort is avoided.
+///
+/// Because updates are not exchanged, each worker must supply exactly those updates whose keys
+/// it would be assigned by the `arrange` operator, namely those for which `key.hashed()` modulo
+/// the number of workers equals the worker index. The resulting arrangement can then be used
+/// interchangeably with arrangements produced by `arrange`.
+///
+/// # Examples
+///
+/// ```
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use timely::Config;
+/// use differential_dataflow::input::BulkSession;
+/// use differential_dataflow::trace::TraceReader;
+/// use differential_dataflow::trace::implementations::ord::OrdValSpine;
+///
+/// fn main() {
+///     ::timely::execute(Config::thread(), |worker| {
+///
+///         let mut session = BulkSession::<OrdValSpine<u64, u64, usize, isize>>::new();
+///         let probe = worker.dataflow(|scope| {
+///             session.to_arrangement(scope)
+///                    .as_collection(|k,v| (*k,*v))
+///                    .probe()
+///         });
+///
+///         // Load a snapshot, already sorted and consolidated.
+///         session.load_sorted((0 .. 1000).map(|x| ((x, x * x), 0, 1)).collect());
+///         session.advance_to(1);
+///         session.flush();
+///
+///         while probe.less_than(session.time()) {
+///             worker.step();
+///         }
+///
+///     }).unwrap();
+/// }
+/// ```
+pub struct BulkSession<Tr>
+where
+    Tr: Trace+'static,
+    Tr::Key: Data,
+    Tr::Val: Data,
+    Tr::Time: Timestamp+Lattice,
+    Tr::R: Semigroup,
+    Tr::Batch: Batch<Tr::Key, Tr::Val, Tr::Time, Tr::R>,
+{
+    /// Lower bound of buffered updates; the upper bound of the most recently sealed batch.
+    lower: Antichain<Tr::Time>,
+    /// Logical time for future records.
+    time: Tr::Time,
+    /// Buffered updates, and whether they are known to be sorted and consolidated.
+    buffer: Vec<((Tr::Key, Tr::Val), Tr::Time, Tr::R)>,
+    sorted: bool,
+    /// Instructions for the arrangement operator, and the means to awaken it.
+    queue: Rc<RefCell<VecDeque<TraceReplayInstruction<Tr>>>>,
+    activator: Option<Activator>,
+}
+
+impl<Tr> BulkSession<Tr>
+where
+    Tr: Trace+'static,
+    Tr::Key: Data,
+    Tr::Val: Data,
+    Tr::Time: Timestamp+Lattice,
+    Tr::R: Semigroup,
+    Tr::Batch: Batch<Tr::Key, Tr::Val, Tr::Time, Tr::R>,
+{
+    /// Allocates a new bulk loading session.
+    pub fn new() -> Self {
+        BulkSession {
+            lower: Antichain::from_elem(Tr::Time::minimum()),
+            time: Tr::Time::minimum(),
+            buffer: Vec::new(),
+            sorted: true,
+            queue: Rc::new(RefCell::new(VecDeque::new())),
+            activator: None,
+        }
+    }
+
+    /// Introduces the session as an arrangement in `scope`.
+    ///
+    /// This method should be called at most once, as the session installs its batches in a single trace.
+    pub fn to_arrangement<G>(&mut self, scope: &mut G) -> Arranged<G, TraceAgent<Tr>>
+    where
+        G: Scope<Timestamp=Tr::Time>,
+    {
+        let scope = &*scope;
+        assert!(self.activator.is_none(), "BulkSession::to_arrangement called more than once");
+
+        let mut reader: Option<TraceAgent<Tr>> = None;
+
+        let stream = {
+
+            let reader = &mut reader;
+            let activator = &mut self.activator;
+            let queue = self.queue.clone();
+
+            source(scope, "BulkLoad", move |capability, info| {
+
+                // Acquire a logger for arrange events.
+                let logger = {
+                    let register = scope.log_register();
+                    register.get::<::logging::DifferentialEvent>("differential/arrange")
+                };
+
+                *activator = Some(scope.activator_for(&info.address[..]));
+
+                let empty_trace = Tr::new(info.clone(), logger.clone(), None);
+                let (reader_local, mut writer) = TraceAgent::new(empty_trace, info, logger);
+                *reader = Some(reader_local);
+
+                let mut capability = Some(capability);
+
+                move |output| {
+                    let mut borrow = queue.borrow_mut();
+                    for instruction in borrow.drain(..) {
+                        match instruction {
+                            TraceReplayInstruction::Batch(batch, hint) => {
+                                // The session's times form a chain, and so its batches have at most one upper element.
+                                assert!(batch.upper().elements().len() <= 1, "BulkSession batch upper has multiple elements");
+                                let next = batch.upper().elements().get(0).cloned();
+                                writer.insert(batch.clone(), hint);
+                                if let Some(cap) = capability.as_mut() {
+                                    output.session(cap).give(batch);
+                                }
+                                // An empty upper bound concludes the input.
+                                match next {
+                                    Some(next) => { capability.as_mut().map(|cap| cap.downgrade(&next)); },
+                                    None => { capability = None; },
+                                }
+                            },
+                            TraceReplayInstruction::Frontier(frontier) => {
+                                writer.seal(frontier);
+                            },
+                        }
+                    }
+                }
+            })
+        };
+
+        Arranged { stream, trace: reader.unwrap() }
+    }
+
+    /// Adds an update to the arrangement at the current time.
+    pub fn update(&mut self, element: (Tr::Key, Tr::Val), change: Tr::R) {
+        let time = self.time.clone();
+        self.update_at(element, time, change);
+    }
+
+    /// Adds an update to the arrangement at a future time.
+    pub fn update_at(&mut self, element: (Tr::Key, Tr::Val), time: Tr::Time, change: Tr::R) {
+        assert!(self.time.less_equal(&time));
+        self.buffer.push((element, time, change));
+        self.sorted = false;
+    }
+
+    /// Adds a sequence of updates in arbitrary order, leaving `updates` empty.
+    ///
+    /// All update times must be greater or equal to the session's current time.
+    pub fn load(&mut self, updates: &mut Vec<((Tr::Key, Tr::Val), Tr::Time, Tr::R)>) {
+        assert!(updates.iter().all(|(_, time, _)| self.time.less_equal(time)));
+        if self.buffer.is_empty() {
+            ::std::mem::swap(&mut self.buffer, updates);
+        }
+        else {
+            self.buffer.extend(updates.drain(..));
+        }
+        self.sorted = false;
+    }
+
+    /// Adds a sequence of updates sorted by `((key, val), time)` and with no repeated `((key, val), time)`.
+    ///
+    /// If no other updates are buffered, the sort that would otherwise happen when the batch is formed
+    /// is avoided. All update times must be greater or equal to the session's current time.
+    pub fn load_sorted(&mut self, updates: Vec<((Tr::Key, Tr::Val), Tr::Time, Tr::R)>) {
+        debug_assert!(updates.windows(2).all(|w| (&w[0].0, &w[0].1) < (&w[1].0, &w[1].1)));
+        assert!(updates.iter().all(|(_, time, _)| self.time.less_equal(time)));
+        if self.buffer.is_empty() {
+            self.buffer = updates;
+        }
+        else {
+            self.buffer.extend(updates);
+            self.sorted = false;
+        }
+    }
+
+    /// Forms a batch of buffered updates up through the current time, and installs it in the arrangement.
+    ///
+    /// Buffered updates at times greater or equal to the current time are retained for a future batch.
+    /// It is important to call `flush` before expecting timely dataflow to report progress.
+    pub fn flush(&mut self) {
+        if self.lower.elements().iter().any(|lower| lower.less_than(&self.time)) {
+            let upper = Antichain::from_elem(self.time.clone());
+            self.seal(upper);
+        }
+    }
+
+    /// Assembles buffered updates not greater or equal to `upper` into a batch, and enqueues it.
+    fn seal(&mut self, upper: Antichain<Tr::Time>) {
+
+        if !self.sorted {
+            consolidate_updates(&mut self.buffer);
+        }
+
+        // Retain updates not yet complete; the sorted order is preserved in both parts.
+        let (ready, pending): (Vec<_>, Vec<_>) = if self.buffer.iter().all(|(_, time, _)| !upper.less_equal(time)) {
+            (::std::mem::replace(&mut self.buffer, Vec::new()), Vec::new())
+        }
+        else {
+            self.buffer.drain(..).partition(|(_, time, _)| !upper.less_equal(time))
+        };
+        self.sorted = true;
+        self.buffer = pending;
+
+        let mut builder = <Tr::Batch as Batch<Tr::Key, Tr::Val, Tr::Time, Tr::R>>::Builder::with_capacity(ready.len());
+        for ((key, val), time, diff) in ready {
+            builder.push((key, val, time, diff));
+        }
+        let lower = ::std::mem::replace(&mut self.lower, upper.clone());
+        let hint = lower.elements().get(0).cloned();
+        let batch = builder.done(lower, upper, Antichain::from_elem(Tr::Time::minimum()));
+        let hint = if batch.is_empty() { None } else { hint };
+
+        self.queue.borrow_mut().push_back(TraceReplayInstruction::Batch(batch, hint));
+        if let Some(activator) = self.activator.as_ref() {
+            activator.activate();
+        }
+    }
+
+    /// Advances the logical time for future records.
+    ///
+    /// As with `InputSession`, the arrangement is not informed of the change until the session is flushed.
+    pub fn advance_to(&mut self, time: Tr::Time) {
+        assert!(self.time.less_equal(&time));
+        self.time = time;
+    }
+
+    /// Reveals the current time of the session.
+    pub fn time(&self) -> &Tr::Time { &self.time }
+
+    /// Closes the input, flushing and sealing the arrangement.
+    pub fn close(self) { }
+}
+
+impl<Tr> Drop for BulkSession<Tr>
+where
+    Tr: Trace+'static,
+    Tr::Key: Data,
+    Tr::Val: Data,
+    Tr::Time: Timestamp+Lattice,
+    Tr::R: Semigroup,
+    Tr::Batch: Batch<Tr::Key, Tr::Val, Tr::Time, Tr::R>,
+{
+    fn drop(&mut self) {
+        // Conclude the arrangement with all remaining updates.
+        self.seal(Antichain::new());
+    }
+}
+
+/// An input session for keyed upserts, whose contents are presented as an arrangement.
+///
+/// Each upsert either replaces the value associated with a key, or removes the key. The session
+/// batches upserts with their logical times, as `InputSession` does for updates, and introduces
+/// them to an upsert operator which maintains the arrangement and determines the retractions of
+/// replaced values.
+///
+/// # Examples
+///
+/// ```
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use timely::Config;
+/// use differential_dataflow::input::UpsertSession;
+///
+/// fn main() {
+///     ::timely::execute(Config::thread(), |worker| {
+///
+///         let mut session = UpsertSession::<String, String, u64>::new();
+///         let probe = worker.dataflow(|scope| {
+///             session.to_arrangement(scope)
+///                    .as_collection(|k,v| (k.clone(), v.clone()))
+///                    .inspect(|x| println!("Observed: {:?}", x))
+///                    .probe()
+///         });
+///
+///         session.upsert("frank".to_string(), "mcsherry".to_string());
+///         session.advance_to(1);
+///         session.upsert("frank".to_string(), "zappa".to_string());
+///         session.advance_to(2);
+///         session.delete("frank".to_string());
+///         session.advance_to(3);
+///         session.flush();
+///
+///         while probe.less_than(session.time()) {
+///             worker.step();
+///         }
+///
+///     }).unwrap();
+/// }
+/// ```
+pub struct UpsertSession<K: ExchangeData, V: ExchangeData, T: Timestamp+Clone> {
+    time: T,
+    buffer: Vec<(K, Option<V>, T)>,
+    handle: Handle<T,(K, Option<V>, T)>,
+}
+
+impl<K, V, T> UpsertSession<K, V, T>
+where
+    K: ExchangeData+Hashable+::std::hash::Hash,
+    V: ExchangeData,
+    T: Timestamp+Lattice+ExchangeData,
+{
+    /// Allocates a new upsert session.
+    pub fn new() -> Self {
+        let handle: Handle<T,_> = Handle::new();
+        UpsertSession {
+            time: handle.time().clone(),
+            buffer: Vec::new(),
+            handle,
+        }
+    }
+
+    /// Introduces the session as an arrangement, for totally ordered timestamps.
+    pub fn to_arrangement<G: TimelyInput>(&mut self, scope: &mut G) -> Arranged<G, TraceAgent<OrdValSpine<K, V, T, isize>>>
+    where
+        G: ScopeParent<Timestamp=T>,
+        T: TotalOrder,
+    {
+        let stream = scope.input_from(&mut self.handle);
+        arrange_from_upsert(&stream, "UpsertSession")
+    }
+
+    /// Introduces the session as an arrangement whose keys expire `ttl` after their last write.
+    ///
+    /// See `arrange_from_upsert_with_ttl` for the precise expiry semantics.
+    pub fn to_arrangement_with_ttl<G: TimelyInput>(&mut self, scope: &mut G, ttl: T::Summary) -> Arranged<G, TraceAgent<OrdValSpine<K, V, T, isize>>>
+    where
+        G: ScopeParent<Timestamp=T>,
+        T: TotalOrder,
+    {
+        let stream = scope.input_from(&mut self.handle);
+        arrange_from_upsert_with_ttl(&stream, "UpsertSession", ttl)
+    }
+
+    /// Introduces the session as an arrangement, for partially ordered timestamps.
+    ///
+    /// This method retains upserts in addition to the arrangement; see `arrange_from_upsert_partial`.
+    pub fn to_arrangement_partial<G: TimelyInput>(&mut self, scope: &mut G) -> Arranged<G, TraceAgent<OrdValSpine<K, V, T, isize>>>
+    where
+        G: ScopeParent<Timestamp=T>,
+    {
+        let stream = scope.input_from(&mut self.handle);
+        arrange_from_upsert_partial(&stream, "UpsertSession")
+    }
+
+    /// Sets the value associated with `key` to `val`.
+    pub fn upsert(&mut self, key: K, val: V) {
+        let time = self.time.clone();
+        self.upsert_at(key, Some(val), time);
+    }
+
+    /// Removes `key` and any value associated with it.
+    pub fn delete(&mut self, key: K) {
+        let time = self.time.clone();
+        self.upsert_at(key, None, time);
+    }
+
+    /// Sets or removes the value associated with `key` at a future time.
+    pub fn upsert_at(&mut self, key: K, val: Option<V>, time: T) {
+        assert!(self.time.less_equal(&time));
+        if self.buffer.len() == self.buffer.capacity() {
+            if self.buffer.len() > 0 {
+                self.handle.send_batch(&mut self.buffer);
+            }
+            // TODO : This is a fairly arbitrary choice; should probably use `Context::default_size()` or such.
+            self.buffer.reserve(1024);
+        }
+        self.buffer.push((key, val, time));
+    }
+
+    /// Forces buffered data into the timely dataflow input, and advances its time to match that of the session.
+    ///
+    /// It is important to call `flush` before expecting timely dataflow to report progress.
+    pub fn flush(&mut self) {
+        self.handle.send_batch(&mut self.buffer);
+        if self.handle.epoch().less_than(&self.time) {
+            self.handle.advance_to(self.time.clone());
+        }
+    }
+
+    /// Advances the logical time for future upserts.
+    ///
+    /// As with `InputSession`, timely dataflow is not informed of the change until the session is flushed.
+    pub fn advance_to(&mut self, time: T) {
+        assert!(self.handle.epoch().less_equal(&time));
+        assert!(&self.time.less_equal(&time));
+        self.time = time;
+    }
+
+    /// Reveals the current time of the session.
+    pub fn time(&self) -> &T { &self.time }
+
+    /// Closes the input, flushing and sealing the wrapped timely input.
+    pub fn close(self) { }
+}
+
+impl<K: ExchangeData, V: ExchangeData, T: Timestamp+Clone> Drop for UpsertSession<K, V, T> {
+    fn drop(&mut self) {
+        self.handle.send_batch(&mut self.buffer);
+        if self.handle.epoch().less_than(&self.time) {
+            self.handle.advance_to(self.time.clone());
+        }
+    }
+}
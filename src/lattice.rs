@@ -317,4 +317,167 @@ impl<T: Lattice+Clone> Lattice for Antichain<T> {
         }
         upper
     }
-}
\ No newline at end of file
+}
+/// A product-order timestamp over more than two coordinates.
+///
+/// Timely dataflow's `Product<T1, T2>` only has two coordinates, and nesting it to obtain more
+/// dimensions makes summaries and `enter_at` awkward. Tuples and arrays cannot be used directly,
+/// as timely's `PartialOrder` is either absent for them or (for pairs) lexicographic. This type
+/// wraps a tuple of up to six coordinates, or a fixed-size array of coordinates, and orders it by
+/// the product order: one time is less or equal to another if each coordinate is.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate timely;
+/// # extern crate differential_dataflow;
+/// # use timely::PartialOrder;
+/// # use differential_dataflow::lattice::{Lattice, MultiProduct};
+/// # fn main() {
+///
+/// let time1 = MultiProduct::new((3, 7, 1));
+/// let time2 = MultiProduct::new((4, 6, 1));
+/// assert!(!time1.less_equal(&time2));
+/// assert_eq!(time1.join(&time2), MultiProduct::new((4, 7, 1)));
+/// assert_eq!(time1.meet(&time2), MultiProduct::new((3, 6, 1)));
+///
+/// let time3 = MultiProduct::new([3, 7, 1, 0]);
+/// let time4 = MultiProduct::new([4, 6, 1, 2]);
+/// assert_eq!(time3.join(&time4), MultiProduct::new([4, 7, 1, 2]));
+/// # }
+/// ```
+#[derive(Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Abomonation, Serialize, Deserialize)]
+pub struct MultiProduct<T> {
+    /// The coordinates, either a tuple or a fixed-size array.
+    pub coords: T,
+}
+
+impl<T> MultiProduct<T> {
+    /// Creates a new product-order time from its coordinates.
+    pub fn new(coords: T) -> Self {
+        MultiProduct { coords }
+    }
+}
+
+/// Debug implementation to avoid seeing fully qualified path names.
+impl<T: ::std::fmt::Debug> ::std::fmt::Debug for MultiProduct<T> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        self.coords.fmt(f)
+    }
+}
+
+use timely::progress::{PathSummary, Timestamp};
+use timely::progress::timestamp::Refines;
+
+macro_rules! implement_multi_product {
+    ($($name:ident: $index:tt),+) => (
+
+        impl<$($name: Default),+> Default for MultiProduct<($($name,)+)> {
+            fn default() -> Self { MultiProduct::new(($($name::default(),)+)) }
+        }
+
+        impl<$($name: PartialOrder),+> PartialOrder for MultiProduct<($($name,)+)> {
+            #[inline]
+            fn less_equal(&self, other: &Self) -> bool {
+                $(self.coords.$index.less_equal(&other.coords.$index))&&+
+            }
+        }
+
+        impl<$($name: Lattice),+> Lattice for MultiProduct<($($name,)+)> {
+            #[inline]
+            fn join(&self, other: &Self) -> Self {
+                MultiProduct::new(($(self.coords.$index.join(&other.coords.$index),)+))
+            }
+            #[inline]
+            fn meet(&self, other: &Self) -> Self {
+                MultiProduct::new(($(self.coords.$index.meet(&other.coords.$index),)+))
+            }
+        }
+
+        impl<$($name: Timestamp),+> PathSummary<MultiProduct<($($name,)+)>> for MultiProduct<($($name::Summary,)+)> {
+            #[inline]
+            fn results_in(&self, timestamp: &MultiProduct<($($name,)+)>) -> Option<MultiProduct<($($name,)+)>> {
+                Some(MultiProduct::new(($(self.coords.$index.results_in(&timestamp.coords.$index)?,)+)))
+            }
+            #[inline]
+            fn followed_by(&self, other: &Self) -> Option<Self> {
+                Some(MultiProduct::new(($(self.coords.$index.followed_by(&other.coords.$index)?,)+)))
+            }
+        }
+
+        impl<$($name: Timestamp),+> Timestamp for MultiProduct<($($name,)+)> {
+            fn minimum() -> Self { MultiProduct::new(($($name::minimum(),)+)) }
+            type Summary = MultiProduct<($($name::Summary,)+)>;
+        }
+
+        impl<$($name: Timestamp),+> Refines<()> for MultiProduct<($($name,)+)> {
+            fn to_inner(_outer: ()) -> Self { Self::minimum() }
+            fn to_outer(self) -> () { () }
+            fn summarize(_summary: <Self as Timestamp>::Summary) -> () { () }
+        }
+    )
+}
+
+implement_multi_product!(T1: 0, T2: 1, T3: 2);
+implement_multi_product!(T1: 0, T2: 1, T3: 2, T4: 3);
+implement_multi_product!(T1: 0, T2: 1, T3: 2, T4: 3, T5: 4);
+implement_multi_product!(T1: 0, T2: 1, T3: 2, T4: 3, T5: 4, T6: 5);
+
+impl<T: Default, const N: usize> Default for MultiProduct<[T; N]> {
+    fn default() -> Self { MultiProduct::new(::std::array::from_fn(|_| T::default())) }
+}
+
+impl<T: PartialOrder, const N: usize> PartialOrder for MultiProduct<[T; N]> {
+    #[inline]
+    fn less_equal(&self, other: &Self) -> bool {
+        self.coords.iter().zip(other.coords.iter()).all(|(t1, t2)| t1.less_equal(t2))
+    }
+}
+
+impl<T: Lattice, const N: usize> Lattice for MultiProduct<[T; N]> {
+    #[inline]
+    fn join(&self, other: &Self) -> Self {
+        MultiProduct::new(::std::array::from_fn(|index| self.coords[index].join(&other.coords[index])))
+    }
+    #[inline]
+    fn meet(&self, other: &Self) -> Self {
+        MultiProduct::new(::std::array::from_fn(|index| self.coords[index].meet(&other.coords[index])))
+    }
+}
+
+impl<T: Timestamp, const N: usize> PathSummary<MultiProduct<[T; N]>> for MultiProduct<[T::Summary; N]> {
+    #[inline]
+    fn results_in(&self, timestamp: &MultiProduct<[T; N]>) -> Option<MultiProduct<[T; N]>> {
+        let mut result = timestamp.clone();
+        for (summary, time) in self.coords.iter().zip(result.coords.iter_mut()) {
+            *time = summary.results_in(time)?;
+        }
+        Some(result)
+    }
+    #[inline]
+    fn followed_by(&self, other: &Self) -> Option<Self> {
+        let mut result = self.clone();
+        for (summary, next) in result.coords.iter_mut().zip(other.coords.iter()) {
+            *summary = summary.followed_by(next)?;
+        }
+        Some(result)
+    }
+}
+
+// Abomonation is only implemented for arrays of specific lengths, which the bound reflects.
+impl<T: Timestamp, const N: usize> Timestamp for MultiProduct<[T; N]>
+where
+    [T; N]: ::abomonation::Abomonation,
+{
+    fn minimum() -> Self { MultiProduct::new(::std::array::from_fn(|_| T::minimum())) }
+    type Summary = MultiProduct<[T::Summary; N]>;
+}
+
+impl<T: Timestamp, const N: usize> Refines<()> for MultiProduct<[T; N]>
+where
+    [T; N]: ::abomonation::Abomonation,
+{
+    fn to_inner(_outer: ()) -> Self { Self::minimum() }
+    fn to_outer(self) -> () { () }
+    fn summarize(_summary: <Self as Timestamp>::Summary) -> () { () }
+}
@@ -122,7 +122,7 @@ pub struct Config {
     /// cause these operators to reschedule themselves as long as their arrangemnt has not
     /// reached a compact representation, and each scheduling quantum they will perform
     /// compaction work as if `effort` records had been added to the arrangement.
-    pub idle_merge_effort: Option<isize>
+    pub idle_merge_effort: Option<isize>,
 }
 
 impl Config {
@@ -139,3 +139,12 @@ pub fn configure(config: &mut timely::WorkerConfig, options: &Config) {
         config.set("differential/idle_merge_effort".to_string(), effort);
     }
 }
+
+/// Enables or disables the logging of statistics about each activation of differential operators.
+///
+/// When enabled, the operators log a `DifferentialEvent::Activation` for each activation, with
+/// the numbers of tuples received and produced and the time spent. Instrumentation is disabled
+/// by default, which avoids reading the clock in each activation.
+pub fn instrument_operators(config: &mut timely::WorkerConfig, instrument: bool) {
+    config.set("differential/instrument_operators".to_string(), instrument);
+}
@@ -0,0 +1,261 @@
+//! Loggers and logging events for differential dataflow.
+
+pub mod prometheus;
+
+/// Logger for differential dataflow events.
+pub type Logger = ::timely::logging::Logger<DifferentialEvent>;
+
+/// Enables logging of differential dataflow events.
+pub fn enable<A, W>(worker: &mut timely::worker::Worker<A>, writer: W) -> Option<Box<dyn std::any::Any+'static>>
+where
+    A: timely::communication::Allocate,
+    W: std::io::Write+'static,
+{
+    let writer = ::timely::dataflow::operators::capture::EventWriter::new(writer);
+    let mut logger = ::timely::logging::BatchLogger::new(writer);
+    worker
+        .log_register()
+        .insert::<DifferentialEvent,_>("differential/arrange", move |time, data| logger.publish_batch(time, data))
+}
+
+/// Possible different differential events.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub enum DifferentialEvent {
+    /// Batch creation.
+    Batch(BatchEvent),
+    /// Merge start and stop events.
+    Merge(MergeEvent),
+    /// Batch dropped when trace dropped.
+    Drop(DropEvent),
+    /// A merge failed to complete in time.
+    MergeShortfall(MergeShortfall),
+    /// Trace sharing event.
+    TraceShare(TraceShare),
+    /// Change in the memory held by a trace.
+    Size(SizeEvent),
+    /// A handle was registered with, or removed from, the holders of a shared trace.
+    Holder(HolderEvent),
+    /// Description of a shared trace, logged on request.
+    Introspection(IntrospectionEvent),
+    /// Logical compaction of a shared trace has not kept pace with the trace.
+    CompactionLag(CompactionLag),
+    /// The kind of an instrumented operator, logged before its activations.
+    OperatorName(OperatorName),
+    /// Statistics about an activation of an operator.
+    Activation(ActivationEvent),
+}
+
+/// Either the start or end of a merge event.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct BatchEvent {
+    /// Operator identifier.
+    pub operator: usize,
+    /// Which order of magnitude.
+    pub length: usize,
+}
+
+impl From<BatchEvent> for DifferentialEvent { fn from(e: BatchEvent) -> Self { DifferentialEvent::Batch(e) } }
+
+
+/// Either the start or end of a merge event.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct DropEvent {
+    /// Operator identifier.
+    pub operator: usize,
+    /// Which order of magnitude.
+    pub length: usize,
+}
+
+impl From<DropEvent> for DifferentialEvent { fn from(e: DropEvent) -> Self { DifferentialEvent::Drop(e) } }
+
+/// Either the start or end of a merge event.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct MergeEvent {
+    /// Operator identifier.
+    pub operator: usize,
+    /// Which order of magnitude.
+    pub scale: usize,
+    /// Length of first trace.
+    pub length1: usize,
+    /// Length of second trace.
+    pub length2: usize,
+    /// None implies a start.
+    pub complete: Option<usize>,
+}
+
+impl From<MergeEvent> for DifferentialEvent { fn from(e: MergeEvent) -> Self { DifferentialEvent::Merge(e) } }
+
+/// A merge failed to complete in time.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct MergeShortfall {
+    /// Operator identifer.
+    pub operator: usize,
+    /// Which order of magnitude.
+    pub scale: usize,
+    /// By how much were we short.
+    pub shortfall: usize,
+}
+
+impl From<MergeShortfall> for DifferentialEvent { fn from(e: MergeShortfall) -> Self { DifferentialEvent::MergeShortfall(e) } }
+
+/// Either the start or end of a merge event.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct TraceShare {
+    /// Operator identifier.
+    pub operator: usize,
+    /// Change in number of shares.
+    pub diff: isize,
+}
+
+impl From<TraceShare> for DifferentialEvent { fn from(e: TraceShare) -> Self { DifferentialEvent::TraceShare(e) } }
+
+/// A change in the heap memory held by a trace, due to batch creation, merging, or dropping.
+///
+/// Accumulating the changes for an operator yields the memory held by its trace.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct SizeEvent {
+    /// Operator identifier.
+    pub operator: usize,
+    /// Change in allocated bytes.
+    pub allocated: isize,
+    /// Change in used bytes.
+    pub used: isize,
+}
+
+impl From<SizeEvent> for DifferentialEvent { fn from(e: SizeEvent) -> Self { DifferentialEvent::Size(e) } }
+
+/// A handle was registered with, or removed from, the holders of a shared trace.
+///
+/// Relabeling a handle is reported as the removal of its old label and registration of its new label.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct HolderEvent {
+    /// Operator identifier.
+    pub operator: usize,
+    /// Identifier of the handle among the holders of the trace.
+    pub holder: usize,
+    /// Label of the handle.
+    pub label: String,
+    /// Registration (1) or removal (-1).
+    pub diff: isize,
+}
+
+impl From<HolderEvent> for DifferentialEvent { fn from(e: HolderEvent) -> Self { DifferentialEvent::Holder(e) } }
+
+/// A description of a shared trace, with frontiers rendered as text.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct IntrospectionEvent {
+    /// Operator identifier.
+    pub operator: usize,
+    /// For each holder, its identifier, label, logical compaction frontier, and physical compaction frontier.
+    pub holders: Vec<(usize, String, String, String)>,
+    /// The joint logical compaction frontier.
+    pub logical_compaction: String,
+    /// The joint physical compaction frontier.
+    pub physical_compaction: String,
+    /// For each layer, its level, whether it is merging, and the lower, upper, since, and length of its batches.
+    pub layers: Vec<(Option<usize>, bool, Vec<(String, String, String, usize)>)>,
+}
+
+impl From<IntrospectionEvent> for DifferentialEvent { fn from(e: IntrospectionEvent) -> Self { DifferentialEvent::Introspection(e) } }
+
+/// Logical compaction of a shared trace has not kept pace with the trace.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct CompactionLag {
+    /// Operator identifier.
+    pub operator: usize,
+    /// Milliseconds for which the logical compaction frontier has lagged without change.
+    pub duration_ms: u64,
+    /// The joint logical compaction frontier.
+    pub frontier: String,
+    /// The upper frontier of the trace.
+    pub upper: String,
+    /// Identifiers and labels of the holders pinning the logical compaction frontier.
+    pub holders: Vec<(usize, String)>,
+}
+
+impl From<CompactionLag> for DifferentialEvent { fn from(e: CompactionLag) -> Self { DifferentialEvent::CompactionLag(e) } }
+
+/// The kind of an instrumented operator, logged once before its activations.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct OperatorName {
+    /// Operator identifier.
+    pub operator: usize,
+    /// The kind of operator, e.g. `"Join"`.
+    pub name: String,
+}
+
+impl From<OperatorName> for DifferentialEvent { fn from(e: OperatorName) -> Self { DifferentialEvent::OperatorName(e) } }
+
+/// Statistics about an activation of an operator.
+///
+/// The kind of the operator is reported by an `OperatorName` event.
+#[derive(Debug, Clone, Abomonation, Ord, PartialOrd, Eq, PartialEq)]
+pub struct ActivationEvent {
+    /// Operator identifier.
+    pub operator: usize,
+    /// Number of input tuples received in the activation.
+    pub input: usize,
+    /// Number of output tuples produced in the activation.
+    pub output: usize,
+    /// Nanoseconds spent in the activation.
+    pub elapsed_ns: u64,
+}
+
+impl From<ActivationEvent> for DifferentialEvent { fn from(e: ActivationEvent) -> Self { DifferentialEvent::Activation(e) } }
+
+/// Records the activations of an operator, if instrumentation is enabled.
+///
+/// Instrumentation is enabled by the `differential/instrument_operators` configuration key, set
+/// through `instrument_operators`, and requires a logger for differential events. When it is not
+/// enabled the methods of the logger do nothing, and in particular do not read the clock.
+pub struct ActivationLogger {
+    logger: Option<Logger>,
+    operator: usize,
+    input: usize,
+    output: usize,
+    start: Option<::std::time::Instant>,
+}
+
+impl ActivationLogger {
+    /// Creates a logger for the activations of operator `operator` in `scope`.
+    pub fn new<G: ::timely::dataflow::Scope>(scope: &G, operator: usize, name: &'static str) -> Self {
+        let enabled = scope.config().get::<bool>("differential/instrument_operators").cloned().unwrap_or(false);
+        let logger = if enabled {
+            scope.log_register().get::<DifferentialEvent>("differential/arrange")
+        }
+        else {
+            None
+        };
+        if let Some(logger) = &logger {
+            logger.log(OperatorName { operator, name: name.to_string() });
+        }
+        ActivationLogger { logger, operator, input: 0, output: 0, start: None }
+    }
+    /// Marks the start of an activation.
+    #[inline]
+    pub fn start(&mut self) {
+        if self.logger.is_some() {
+            self.input = 0;
+            self.output = 0;
+            self.start = Some(::std::time::Instant::now());
+        }
+    }
+    /// Accounts for `count` tuples received by the activation.
+    #[inline]
+    pub fn input(&mut self, count: usize) { self.input += count; }
+    /// Accounts for `count` tuples produced by the activation.
+    #[inline]
+    pub fn output(&mut self, count: usize) { self.output += count; }
+    /// Marks the end of an activation, and logs its statistics.
+    pub fn finish(&mut self) {
+        if let (Some(logger), Some(start)) = (&self.logger, self.start.take()) {
+            let elapsed = start.elapsed();
+            logger.log(ActivationEvent {
+                operator: self.operator,
+                input: self.input,
+                output: self.output,
+                elapsed_ns: elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64,
+            });
+        }
+    }
+}
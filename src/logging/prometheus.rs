@@ -0,0 +1,247 @@
+//! Metrics aggregated from differential dataflow events, served in the Prometheus text format.
+//!
+//! A `PrometheusMetrics` accumulates the `DifferentialEvent`s of any number of workers into
+//! counters and gauges for each worker and operator, and can serve the current values over HTTP
+//! from a local address for scraping by monitoring systems.
+//!
+//! # Examples
+//!
+//! ```
+//! extern crate timely;
+//! extern crate differential_dataflow;
+//!
+//! use differential_dataflow::input::Input;
+//! use differential_dataflow::operators::arrange::ArrangeBySelf;
+//! use differential_dataflow::logging::prometheus::PrometheusMetrics;
+//!
+//! fn main() {
+//!
+//!     let metrics = PrometheusMetrics::new();
+//!     let address = metrics.serve("127.0.0.1:0").expect("failed to bind");
+//!     println!("serving metrics at http://{}/metrics", address);
+//!
+//!     let worker_metrics = metrics.clone();
+//!     timely::execute_directly(move |worker| {
+//!         worker_metrics.enable(worker);
+//!         worker.dataflow::<u64,_,_>(|scope| {
+//!             scope.new_collection_from(0 .. 10u64).1.arrange_by_self();
+//!         });
+//!     });
+//!
+//!     assert!(metrics.render().contains("differential_batches_total"));
+//! }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as FmtWrite;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::DifferentialEvent;
+
+/// The time allowed to a client to send its request, and to accept each write of the response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shared counters and gauges describing the arrangements of a computation.
+///
+/// Cloning the metrics produces another handle to the same values.
+#[derive(Clone, Default)]
+pub struct PrometheusMetrics {
+    state: Arc<Mutex<MetricsState>>,
+}
+
+#[derive(Default)]
+struct MetricsState {
+    /// Metrics for each (worker, operator) pair.
+    operators: BTreeMap<(usize, usize), OperatorMetrics>,
+    /// Start times of in-progress merges, indexed by (worker, operator, scale).
+    merges: HashMap<(usize, usize, usize), Duration>,
+}
+
+/// Metrics for a single arrangement operator.
+#[derive(Default)]
+struct OperatorMetrics {
+    batches: u64,
+    batch_records: u64,
+    merges: u64,
+    merge_seconds: f64,
+    merge_shortfalls: u64,
+    merge_shortfall_fuel: u64,
+    records: i64,
+    shares: i64,
+    allocated_bytes: i64,
+    used_bytes: i64,
+    activations: u64,
+    activation_seconds: f64,
+    activation_input: u64,
+    activation_output: u64,
+}
+
+impl MetricsState {
+    fn observe(&mut self, time: Duration, worker: usize, event: DifferentialEvent) {
+        match event {
+            DifferentialEvent::Batch(event) => {
+                let metrics = self.operators.entry((worker, event.operator)).or_insert_with(Default::default);
+                metrics.batches += 1;
+                metrics.batch_records += event.length as u64;
+                metrics.records += event.length as i64;
+            },
+            DifferentialEvent::Merge(event) => {
+                let key = (worker, event.operator, event.scale);
+                match event.complete {
+                    None => { self.merges.insert(key, time); },
+                    Some(length) => {
+                        let started = self.merges.remove(&key);
+                        let metrics = self.operators.entry((worker, event.operator)).or_insert_with(Default::default);
+                        metrics.merges += 1;
+                        metrics.records += length as i64 - event.length1 as i64 - event.length2 as i64;
+                        if let Some(started) = started {
+                            let elapsed = if time > started { time - started } else { Duration::from_secs(0) };
+                            metrics.merge_seconds += elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0;
+                        }
+                    },
+                }
+            },
+            DifferentialEvent::Drop(event) => {
+                let metrics = self.operators.entry((worker, event.operator)).or_insert_with(Default::default);
+                metrics.records -= event.length as i64;
+            },
+            DifferentialEvent::MergeShortfall(event) => {
+                let metrics = self.operators.entry((worker, event.operator)).or_insert_with(Default::default);
+                metrics.merge_shortfalls += 1;
+                metrics.merge_shortfall_fuel += event.shortfall as u64;
+            },
+            DifferentialEvent::TraceShare(event) => {
+                let metrics = self.operators.entry((worker, event.operator)).or_insert_with(Default::default);
+                metrics.shares += event.diff as i64;
+            },
+            DifferentialEvent::Size(event) => {
+                let metrics = self.operators.entry((worker, event.operator)).or_insert_with(Default::default);
+                metrics.allocated_bytes += event.allocated as i64;
+                metrics.used_bytes += event.used as i64;
+            },
+            DifferentialEvent::Holder(_) => { },
+            DifferentialEvent::Introspection(_) => { },
+            DifferentialEvent::CompactionLag(_) => { },
+            DifferentialEvent::OperatorName(_) => { },
+            DifferentialEvent::Activation(event) => {
+                let metrics = self.operators.entry((worker, event.operator)).or_insert_with(Default::default);
+                metrics.activations += 1;
+                metrics.activation_seconds += event.elapsed_ns as f64 / 1_000_000_000.0;
+                metrics.activation_input += event.input as u64;
+                metrics.activation_output += event.output as u64;
+            },
+        }
+    }
+
+    fn render(&self) -> String {
+
+        let mut text = String::new();
+
+        {
+            let mut family = |name: &str, kind: &str, help: &str, value: &dyn Fn(&OperatorMetrics) -> String| {
+                writeln!(text, "# HELP {} {}", name, help).unwrap();
+                writeln!(text, "# TYPE {} {}", name, kind).unwrap();
+                for (&(worker, operator), metrics) in self.operators.iter() {
+                    writeln!(text, "{}{{worker=\"{}\",operator=\"{}\"}} {}", name, worker, operator, value(metrics)).unwrap();
+                }
+            };
+
+            family("differential_batches_total", "counter", "Batches introduced into the trace.", &|m| m.batches.to_string());
+            family("differential_batch_records_total", "counter", "Updates in batches introduced into the trace.", &|m| m.batch_records.to_string());
+            family("differential_merges_total", "counter", "Completed merges of batches.", &|m| m.merges.to_string());
+            family("differential_merge_seconds_total", "counter", "Seconds between the start and completion of merges.", &|m| m.merge_seconds.to_string());
+            family("differential_merge_shortfalls_total", "counter", "Merges that failed to complete in time.", &|m| m.merge_shortfalls.to_string());
+            family("differential_merge_shortfall_fuel_total", "counter", "Fuel by which merges fell short of completing.", &|m| m.merge_shortfall_fuel.to_string());
+            family("differential_records", "gauge", "Updates held by the trace.", &|m| m.records.to_string());
+            family("differential_trace_shares", "gauge", "Handles sharing the trace.", &|m| m.shares.to_string());
+            family("differential_heap_allocated_bytes", "gauge", "Heap bytes allocated by the trace.", &|m| m.allocated_bytes.to_string());
+            family("differential_heap_used_bytes", "gauge", "Heap bytes used by the trace.", &|m| m.used_bytes.to_string());
+            family("differential_activations_total", "counter", "Instrumented activations of the operator.", &|m| m.activations.to_string());
+            family("differential_activation_seconds_total", "counter", "Seconds spent in instrumented activations.", &|m| m.activation_seconds.to_string());
+            family("differential_activation_input_total", "counter", "Tuples received by instrumented activations.", &|m| m.activation_input.to_string());
+            family("differential_activation_output_total", "counter", "Tuples produced by instrumented activations.", &|m| m.activation_output.to_string());
+        }
+
+        text
+    }
+}
+
+impl PrometheusMetrics {
+    /// Creates an empty set of metrics.
+    pub fn new() -> Self { Default::default() }
+
+    /// Aggregates the differential events of `worker` into the metrics.
+    ///
+    /// This replaces any logger previously registered for `"differential/arrange"` events.
+    pub fn enable<A: ::timely::communication::Allocate>(&self, worker: &mut ::timely::worker::Worker<A>) {
+        let state = self.state.clone();
+        worker
+            .log_register()
+            .insert::<DifferentialEvent,_>("differential/arrange", move |_time, data| {
+                let mut state = state.lock().expect("metrics lock poisoned");
+                for (time, worker, event) in data.drain(..) {
+                    state.observe(time, worker, event);
+                }
+            });
+    }
+
+    /// Records an event from `worker`, which occurred at `time` since the start of the computation.
+    pub fn observe(&self, time: Duration, worker: usize, event: DifferentialEvent) {
+        self.state.lock().expect("metrics lock poisoned").observe(time, worker, event);
+    }
+
+    /// Renders the current values of the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        self.state.lock().expect("metrics lock poisoned").render()
+    }
+
+    /// Serves the metrics over HTTP from `address`, returning the bound address.
+    ///
+    /// Connections are accepted by a background thread, which lives for the remainder of the
+    /// process, and each is answered from its own thread with the rendered metrics. Connections
+    /// that do not send a request, or do not accept the response, are closed after a timeout.
+    /// Binding to port zero selects an available port, which is reported in the returned address.
+    pub fn serve<A: ToSocketAddrs>(&self, address: A) -> ::std::io::Result<SocketAddr> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let metrics = self.clone();
+        ::std::thread::Builder::new()
+            .name("differential-metrics".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if let Ok(stream) = stream {
+                        let metrics = metrics.clone();
+                        // Failures affect only the one request, and are left to the client to observe.
+                        let _ = ::std::thread::Builder::new()
+                            .name("differential-metrics-request".to_string())
+                            .spawn(move || metrics.respond(stream));
+                    }
+                }
+            })?;
+        Ok(address)
+    }
+
+    /// Reads the request head from `stream` and writes the rendered metrics in response.
+    fn respond(&self, stream: ::std::net::TcpStream) -> ::std::io::Result<()> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            if line == "\r\n" || line == "\n" { break; }
+            line.clear();
+        }
+        let body = self.render();
+        let mut stream = reader.into_inner();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len(),
+        )?;
+        stream.write_all(body.as_bytes())?;
+        stream.flush()
+    }
+}
@@ -4,7 +4,7 @@ use std::rc::{Rc, Weak};
 use std::cell::RefCell;
 use std::collections::VecDeque;
 
-use timely::dataflow::Scope;
+use timely::dataflow::{Scope, Stream};
 use timely::dataflow::operators::generic::source;
 use timely::progress::Timestamp;
 use timely::progress::{Antichain, frontier::AntichainRef};
@@ -13,14 +13,16 @@ use timely::dataflow::operators::CapabilitySet;
 use lattice::Lattice;
 use trace::{Trace, TraceReader, Batch, BatchReader, Cursor};
 
-use trace::wrappers::rc::TraceBox;
+use trace::wrappers::rc::{TraceBox, LagMonitor, LagReport};
 
 use timely::scheduling::Activator;
 
-use super::{TraceWriter, TraceAgentQueueWriter, TraceAgentQueueReader, Arranged};
+use super::{TraceWriter, TraceAgentQueueWriter, TraceAgentQueueReader, Arranged, MemoryBudget};
 use super::TraceReplayInstruction;
 
 use crate::trace::wrappers::frontier::{TraceFrontier, BatchFrontier};
+use crate::trace::wrappers::map_time::{TraceMapTime, BatchMapTime};
+use crate::trace::introspection::{LayerIntrospection, TraceIntrospection};
 
 
 /// A `TraceReader` wrapper which can be imported into other dataflows.
@@ -40,6 +42,9 @@ where
 
     operator: ::timely::dataflow::operators::generic::OperatorInfo,
     logging: Option<::logging::Logger>,
+
+    /// Identifies this handle among the holders of the trace.
+    holder: usize,
 }
 
 impl<Tr> TraceReader for TraceAgent<Tr>
@@ -60,8 +65,10 @@ where
         // Instead, it determines the joint consequences of both guarantees and moves forward with that.
         crate::lattice::antichain_join_into(&self.logical_compaction.borrow()[..], &frontier[..], &mut self.temp_antichain);
         self.trace.borrow_mut().adjust_logical_compaction(self.logical_compaction.borrow(), self.temp_antichain.borrow());
+        let changed = self.logical_compaction != self.temp_antichain;
         ::std::mem::swap(&mut self.logical_compaction, &mut self.temp_antichain);
         self.temp_antichain.clear();
+        if changed { self.update_holder(); }
     }
     fn get_logical_compaction(&mut self) -> AntichainRef<Tr::Time> {
         self.logical_compaction.borrow()
@@ -71,8 +78,10 @@ where
         // Instead, it determines the joint consequences of both guarantees and moves forward with that.
         crate::lattice::antichain_join_into(&self.physical_compaction.borrow()[..], &frontier[..], &mut self.temp_antichain);
         self.trace.borrow_mut().adjust_physical_compaction(self.physical_compaction.borrow(), self.temp_antichain.borrow());
+        let changed = self.physical_compaction != self.temp_antichain;
         ::std::mem::swap(&mut self.physical_compaction, &mut self.temp_antichain);
         self.temp_antichain.clear();
+        if changed { self.update_holder(); }
     }
     fn get_physical_compaction(&mut self) -> AntichainRef<Tr::Time> {
         self.physical_compaction.borrow()
@@ -81,6 +90,117 @@ where
         self.trace.borrow_mut().trace.cursor_through(frontier)
     }
     fn map_batches<F: FnMut(&Self::Batch)>(&self, f: F) { self.trace.borrow().trace.map_batches(f) }
+    fn layers(&self) -> Vec<LayerIntrospection<Tr::Time>> { self.trace.borrow().trace.layers() }
+}
+
+impl<Tr> TraceAgent<Tr>
+where
+    Tr: TraceReader,
+    Tr::Time: Lattice+Ord+Clone+'static,
+{
+    /// Sets the label identifying this handle among the holders of the trace.
+    ///
+    /// By default, a handle is labeled by the operator that created the trace. Distinct labels make
+    /// it easier to determine which handle is holding back the compaction of a shared trace.
+    pub fn set_label(&mut self, label: &str) {
+        let previous = self.trace.borrow_mut().holders.get_mut(&self.holder).map(|holder| ::std::mem::replace(&mut holder.label, label.to_owned()));
+        if let Some(previous) = previous {
+            self.log_holder(previous, -1);
+            self.log_holder(label.to_owned(), 1);
+        }
+    }
+
+    /// Accounts the memory of the shared trace against `budget`.
+    ///
+    /// The trace is measured by its `arrange` operator as it inserts batches and performs merge work,
+    /// and whenever the compaction frontiers of its handles change. The account is shared by all
+    /// handles to the trace, replaces any previously installed, and is released with the trace.
+    pub fn set_budget(&mut self, budget: &MemoryBudget) {
+        self.trace.borrow_mut().set_budget(budget.register());
+    }
+
+    /// Describes the holders, compaction frontiers, and layers of the shared trace.
+    pub fn introspect(&self) -> TraceIntrospection<Tr::Time> {
+        self.trace.borrow().introspect()
+    }
+
+    /// Records the frontiers of this handle with the shared trace.
+    fn update_holder(&mut self) {
+        self.trace.borrow_mut().update_holder(self.holder, self.logical_compaction.borrow(), self.physical_compaction.borrow());
+    }
+
+    /// Logs the registration (`diff` of 1) or removal (`diff` of -1) of this handle as a holder.
+    fn log_holder(&self, label: String, diff: isize) {
+        if let Some(logging) = &self.logging {
+            logging.log(::logging::HolderEvent {
+                operator: self.operator.global_id,
+                holder: self.holder,
+                label,
+                diff,
+            });
+        }
+    }
+}
+
+impl<Tr> TraceAgent<Tr>
+where
+    Tr: TraceReader,
+    Tr::Time: Lattice+Ord+Clone+::std::fmt::Debug+'static,
+{
+    /// Reports logical compaction that does not keep pace with the trace for `threshold`.
+    ///
+    /// When the joint logical compaction frontier of all holders lags the upper frontier of the trace
+    /// without changing for at least `threshold`, a `CompactionLag` event is logged identifying the
+    /// holders that pin the frontier. This is commonly the sign of a forgotten trace handle. Nothing
+    /// is reported if logging is not enabled for the trace.
+    ///
+    /// The monitor is shared by all handles to the trace, and replaces any previously installed.
+    /// The arrangement operators install a monitor if the `differential/compaction_lag_ms` key is
+    /// set in the timely configuration.
+    pub fn monitor_compaction_lag(&mut self, threshold: ::std::time::Duration) {
+        if let Some(logging) = self.logging.clone() {
+            let operator = self.operator.global_id;
+            let frontier = |antichain: AntichainRef<Tr::Time>| {
+                let times = antichain.iter().map(|t| format!("{:?}", t)).collect::<Vec<_>>();
+                format!("[{}]", times.join(", "))
+            };
+            let report: Box<dyn FnMut(LagReport<Tr::Time>)> = Box::new(move |report| {
+                logging.log(::logging::CompactionLag {
+                    operator,
+                    duration_ms: report.duration.as_millis() as u64,
+                    frontier: frontier(report.frontier),
+                    upper: frontier(report.upper),
+                    holders: report.holders.iter().map(|h| (h.id, h.label.clone())).collect(),
+                });
+            });
+            self.trace.borrow_mut().lag_monitor = Some(LagMonitor::new(threshold, report));
+        }
+    }
+
+    /// Logs a description of the shared trace, if logging is enabled.
+    ///
+    /// The description includes the frontiers of each holder and the batches of each layer, and
+    /// is relatively expensive to produce; it is logged only when this method is called, whereas
+    /// changes to the holders of the trace are logged as they happen.
+    pub fn log_introspection(&self) {
+        if let Some(logging) = &self.logging {
+            let frontier = |antichain: &Antichain<Tr::Time>| {
+                let times = antichain.elements().iter().map(|t| format!("{:?}", t)).collect::<Vec<_>>();
+                format!("[{}]", times.join(", "))
+            };
+            let introspection = self.introspect();
+            logging.log(::logging::IntrospectionEvent {
+                operator: self.operator.global_id,
+                holders: introspection.holders.iter().map(|h| (h.id, h.label.clone(), frontier(&h.logical_compaction), frontier(&h.physical_compaction))).collect(),
+                logical_compaction: frontier(&introspection.logical_compaction),
+                physical_compaction: frontier(&introspection.physical_compaction),
+                layers: introspection.layers.iter().map(|layer| {
+                    let batches = layer.batches.iter().map(|b| (frontier(b.description.lower()), frontier(b.description.upper()), frontier(b.description.since()), b.len)).collect();
+                    (layer.level, layer.merging, batches)
+                }).collect(),
+            });
+        }
+    }
 }
 
 impl<Tr> TraceAgent<Tr>
@@ -103,15 +223,22 @@ where
             );
         }
 
+        let logical_compaction = trace.borrow().logical_compaction.frontier().to_owned();
+        let physical_compaction = trace.borrow().physical_compaction.frontier().to_owned();
+        let label = format!("operator {}", operator.global_id);
+        let holder = trace.borrow_mut().register_holder(label.clone(), logical_compaction.borrow(), physical_compaction.borrow());
+
         let reader = TraceAgent {
             trace: trace.clone(),
             queues: Rc::downgrade(&queues),
-            logical_compaction: trace.borrow().logical_compaction.frontier().to_owned(),
-            physical_compaction: trace.borrow().physical_compaction.frontier().to_owned(),
+            logical_compaction,
+            physical_compaction,
             temp_antichain: Antichain::new(),
             operator,
             logging,
+            holder,
         };
+        reader.log_holder(label, 1);
 
         let writer = TraceWriter::new(
             vec![<Tr::Time as Timestamp>::minimum()],
@@ -298,51 +425,8 @@ where
         Tr::Time: Timestamp,
     {
         let trace = self.clone();
-
-        let mut shutdown_button = None;
-
-        let stream = {
-
-            let shutdown_button_ref = &mut shutdown_button;
-            source(scope, name, move |capability, info| {
-
-                let capabilities = Rc::new(RefCell::new(Some(CapabilitySet::new())));
-
-                let activator = scope.activator_for(&info.address[..]);
-                let queue = self.new_listener(activator);
-
-                let activator = scope.activator_for(&info.address[..]);
-                *shutdown_button_ref = Some(ShutdownButton::new(capabilities.clone(), activator));
-
-                capabilities.borrow_mut().as_mut().unwrap().insert(capability);
-
-                move |output| {
-
-                    let mut capabilities = capabilities.borrow_mut();
-                    if let Some(ref mut capabilities) = *capabilities {
-
-                        let mut borrow = queue.1.borrow_mut();
-                        for instruction in borrow.drain(..) {
-                            match instruction {
-                                TraceReplayInstruction::Frontier(frontier) => {
-                                    capabilities.downgrade(&frontier.borrow()[..]);
-                                },
-                                TraceReplayInstruction::Batch(batch, hint) => {
-                                    if let Some(time) = hint {
-                                        if !batch.is_empty() {
-                                            let delayed = capabilities.delayed(&time);
-                                            output.session(&delayed).give(batch);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            })
-        };
-
-        (Arranged { stream, trace }, shutdown_button.unwrap())
+        let (stream, shutdown_button) = self.replay_into(scope, name, |time| time.clone(), |batch| batch);
+        (Arranged { stream, trace }, shutdown_button)
     }
 
     /// Imports an arrangement into the supplied scope.
@@ -436,7 +520,99 @@ where
     {
         let trace = self.clone();
         let trace = TraceFrontier::make_from(trace, frontier.borrow());
+        let (stream, shutdown_button) = self.replay_into(scope, name, |time| time.clone(), move |batch| BatchFrontier::make_from(batch, frontier.borrow()));
+        (Arranged { stream, trace }, shutdown_button)
+    }
+
+    /// Imports an arrangement into a scope with a different timestamp type.
+    ///
+    /// The times of updates and batch bounds are mapped through `logic`, which should be monotone.
+    /// Frontiers expressed in the new timestamp type are mapped back to the trace through `prior`,
+    /// which for each time `t` must produce the least time whose image under `logic` is greater or
+    /// equal to `t`. See the `map_time` module for further detail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use std::time::Duration;
+    ///
+    /// use timely::Config;
+    /// use timely::dataflow::operators::Probe;
+    /// use differential_dataflow::input::InputSession;
+    /// use differential_dataflow::operators::arrange::ArrangeBySelf;
+    ///
+    /// fn main() {
+    ///     ::timely::execute(Config::thread(), |worker| {
+    ///
+    ///         let mut input = InputSession::<u64,u32,isize>::new();
+    ///
+    ///         // an arrangement with times in seconds.
+    ///         let mut trace = worker.dataflow(|scope| {
+    ///             input.to_collection(scope).arrange_by_self().trace
+    ///         });
+    ///
+    ///         input.insert(0); input.advance_to(1); input.flush();
+    ///         input.insert(1); input.advance_to(2); input.flush();
+    ///
+    ///         // a dataflow with times as durations.
+    ///         let probe = worker.dataflow::<Duration,_,_>(|scope| {
+    ///             trace.import_map_time(
+    ///                     scope,
+    ///                     |secs: &u64| Duration::from_secs(*secs),
+    ///                     |duration: &Duration| duration.as_secs() + if duration.subsec_nanos() > 0 { 1 } else { 0 },
+    ///                  )
+    ///                  .as_collection(|k,_| *k)
+    ///                  .inspect(|x| println!("{:?}", x))
+    ///                  .probe()
+    ///         });
+    ///
+    ///         worker.step_while(|| probe.less_than(&Duration::from_secs(2)));
+    ///
+    ///     }).unwrap();
+    /// }
+    /// ```
+    pub fn import_map_time<G, F, P>(&mut self, scope: &G, logic: F, prior: P) -> Arranged<G, TraceMapTime<TraceAgent<Tr>, G::Timestamp, F, P>>
+    where
+        G: Scope,
+        G::Timestamp: Lattice+Ord,
+        Tr::Time: Timestamp,
+        F: Fn(&Tr::Time)->G::Timestamp+Clone+'static,
+        P: Fn(&G::Timestamp)->Tr::Time+Clone+'static,
+    {
+        self.import_map_time_core(scope, "ArrangedSource", logic, prior).0
+    }
+
+    /// Imports an arrangement into a scope with a different timestamp type, with a name and a shutdown button.
+    pub fn import_map_time_core<G, F, P>(&mut self, scope: &G, name: &str, logic: F, prior: P) -> (Arranged<G, TraceMapTime<TraceAgent<Tr>, G::Timestamp, F, P>>, ShutdownButton<CapabilitySet<G::Timestamp>>)
+    where
+        G: Scope,
+        G::Timestamp: Lattice+Ord,
+        Tr::Time: Timestamp,
+        F: Fn(&Tr::Time)->G::Timestamp+Clone+'static,
+        P: Fn(&G::Timestamp)->Tr::Time+Clone+'static,
+    {
+        let trace = TraceMapTime::make_from(self.clone(), logic.clone(), prior);
+        let batch_logic = logic.clone();
+        let (stream, shutdown_button) = self.replay_into(scope, name, logic, move |batch| BatchMapTime::make_from(batch, batch_logic.clone()));
+        (Arranged { stream, trace }, shutdown_button)
+    }
 
+    /// Replays the batches of the trace, and those it will receive, as a stream in `scope`.
+    ///
+    /// The times of capabilities are mapped through `time`, which should be monotone, and batches are
+    /// mapped through `batch`. Empty batches are not sent, and the stream's frontier tracks the upper
+    /// frontier of the trace until the returned button is pressed.
+    fn replay_into<G, B, FT, FB>(&mut self, scope: &G, name: &str, time: FT, batch: FB) -> (Stream<G, B>, ShutdownButton<CapabilitySet<G::Timestamp>>)
+    where
+        G: Scope,
+        B: ::timely::Data,
+        Tr::Time: Timestamp,
+        FT: Fn(&Tr::Time)->G::Timestamp+'static,
+        FB: Fn(Tr::Batch)->B+'static,
+    {
         let mut shutdown_button = None;
 
         let stream = {
@@ -463,13 +639,14 @@ where
                         for instruction in borrow.drain(..) {
                             match instruction {
                                 TraceReplayInstruction::Frontier(frontier) => {
-                                    capabilities.downgrade(&frontier.borrow()[..]);
+                                    let frontier = frontier.elements().iter().map(|t| time(t)).collect::<Vec<_>>();
+                                    capabilities.downgrade(&frontier[..]);
                                 },
-                                TraceReplayInstruction::Batch(batch, hint) => {
-                                    if let Some(time) = hint {
-                                        if !batch.is_empty() {
-                                            let delayed = capabilities.delayed(&time);
-                                            output.session(&delayed).give(BatchFrontier::make_from(batch, frontier.borrow()));
+                                TraceReplayInstruction::Batch(contents, hint) => {
+                                    if let Some(hint) = hint {
+                                        if !contents.is_empty() {
+                                            let delayed = capabilities.delayed(&time(&hint));
+                                            output.session(&delayed).give(batch(contents));
                                         }
                                     }
                                 }
@@ -480,7 +657,7 @@ where
             })
         };
 
-        (Arranged { stream, trace }, shutdown_button.unwrap())
+        (stream, shutdown_button.unwrap())
     }
 }
 
@@ -541,7 +718,10 @@ where
         self.trace.borrow_mut().adjust_logical_compaction(empty_frontier.borrow(), self.logical_compaction.borrow());
         self.trace.borrow_mut().adjust_physical_compaction(empty_frontier.borrow(), self.physical_compaction.borrow());
 
-        TraceAgent {
+        let label = self.trace.borrow().holders.get(&self.holder).map(|h| h.label.clone()).unwrap_or_default();
+        let holder = self.trace.borrow_mut().register_holder(label.clone(), self.logical_compaction.borrow(), self.physical_compaction.borrow());
+
+        let clone = TraceAgent {
             trace: self.trace.clone(),
             queues: self.queues.clone(),
             logical_compaction: self.logical_compaction.clone(),
@@ -549,7 +729,10 @@ where
             operator: self.operator.clone(),
             logging: self.logging.clone(),
             temp_antichain: Antichain::new(),
-        }
+            holder,
+        };
+        clone.log_holder(label, 1);
+        clone
     }
 }
 
@@ -570,5 +753,8 @@ where
         let empty_frontier = Antichain::new();
         self.trace.borrow_mut().adjust_logical_compaction(self.logical_compaction.borrow(), empty_frontier.borrow());
         self.trace.borrow_mut().adjust_physical_compaction(self.physical_compaction.borrow(), empty_frontier.borrow());
+        let label = self.trace.borrow().holders.get(&self.holder).map(|h| h.label.clone()).unwrap_or_default();
+        self.trace.borrow_mut().remove_holder(self.holder);
+        self.log_holder(label, -1);
     }
 }
@@ -38,7 +38,7 @@ use trace::wrappers::enter_at::TraceEnter as TraceEnterAt;
 use trace::wrappers::enter_at::BatchEnter as BatchEnterAt;
 use trace::wrappers::filter::{TraceFilter, BatchFilter};
 
-use super::TraceAgent;
+use super::{TraceAgent, MemoryBudget};
 
 /// An arranged collection of `(K,V)` values.
 ///
@@ -486,6 +486,37 @@ where
         self.arrange_core(exchange, name)
     }
 
+    /// Arranges a stream of `(Key, Val)` updates by `Key`, accounting the trace against `budget`.
+    ///
+    /// The `arrange` operator reports the memory held by the trace to `budget` as it maintains the trace,
+    /// allowing inputs that observe the budget to hold back while it is exceeded.
+    fn arrange_with_budget<Tr>(&self, budget: &MemoryBudget) -> Arranged<G, TraceAgent<Tr>>
+    where
+        K: ExchangeData+Hashable,
+        V: ExchangeData,
+        R: ExchangeData,
+        Tr: Trace+TraceReader<Key=K,Val=V,Time=G::Timestamp,R=R>+'static,
+        Tr::Batch: Batch<K, V, G::Timestamp, R>,
+        Tr::Cursor: Cursor<K, V, G::Timestamp, R>,
+    {
+        self.arrange_named_with_budget("Arrange", budget)
+    }
+
+    /// As `arrange_with_budget`, but with a name for the `arrange` operator.
+    fn arrange_named_with_budget<Tr>(&self, name: &str, budget: &MemoryBudget) -> Arranged<G, TraceAgent<Tr>>
+    where
+        K: ExchangeData+Hashable,
+        V: ExchangeData,
+        R: ExchangeData,
+        Tr: Trace+TraceReader<Key=K,Val=V,Time=G::Timestamp,R=R>+'static,
+        Tr::Batch: Batch<K, V, G::Timestamp, R>,
+        Tr::Cursor: Cursor<K, V, G::Timestamp, R>,
+    {
+        let mut arranged = self.arrange_named(name);
+        arranged.trace.set_budget(budget);
+        arranged
+    }
+
     /// Arranges a stream of `(Key, Val)` updates by `Key`. Accepts an empty instance of the trace type.
     ///
     /// This operator arranges a stream of values into a shared trace, whose contents it maintains.
@@ -563,8 +594,14 @@ where
                     (None, None)
                 };
 
+                let mut activations = ::logging::ActivationLogger::new(&self.scope(), info.global_id, "Arrange");
+
                 let empty_trace = Tr::new(info.clone(), logger.clone(), activator);
-                let (reader_local, mut writer) = TraceAgent::new(empty_trace, info, logger);
+                let (mut reader_local, mut writer) = TraceAgent::new(empty_trace, info, logger);
+
+                if let Some(lag_ms) = self.inner.scope().config().get::<u64>("differential/compaction_lag_ms").cloned() {
+                    reader_local.monitor_compaction_lag(::std::time::Duration::from_millis(lag_ms));
+                }
 
                 *reader = Some(reader_local);
 
@@ -573,6 +610,8 @@ where
 
                 move |input, output| {
 
+                    activations.start();
+
                     // As we receive data, we need to (i) stash the data and (ii) keep *enough* capabilities.
                     // We don't have to keep all capabilities, but we need to be able to form output messages
                     // when we realize that time intervals are complete.
@@ -580,6 +619,7 @@ where
                     input.for_each(|cap, data| {
                         capabilities.insert(cap.retain());
                         data.swap(&mut buffer);
+                        activations.input(buffer.len());
                         batcher.push_batch(&mut buffer);
                     });
 
@@ -631,6 +671,7 @@ where
                                     // Extract updates not in advance of `upper`.
                                     let batch = batcher.seal(upper.clone());
 
+                                    activations.output(batch.len());
                                     writer.insert(batch.clone(), Some(capability.time().clone()));
 
                                     // send the batch to downstream consumers, empty or not.
@@ -668,6 +709,8 @@ where
                     if let Some(mut fuel) = effort.clone() {
                         writer.exert(&mut fuel);
                     }
+
+                    activations.finish();
                 }
             })
         };
@@ -0,0 +1,127 @@
+//! Soft memory budgets for arrangements.
+//!
+//! A `MemoryBudget` is a shared handle that arrangements report their memory use to, and that
+//! producers of input can consult to decide whether to slow down. The budget is "soft": nothing
+//! prevents an arrangement from exceeding it, but inputs that observe the budget will decline to
+//! advance their times while it is exceeded, giving the dataflow an opportunity to catch up and
+//! compact its arrangements rather than accept ever more updates.
+//!
+//! Memory is measured with the `heap_size` method of the arranged traces, and so reflects the
+//! containers backing their batches but not any allocations owned by the updates themselves. Each
+//! arrangement is measured by its `arrange` operator whenever it inserts a batch or performs merge
+//! work, and whenever the compaction frontiers of the trace change, so that pressure is re-evaluated
+//! even while the inputs are held back. An arrangement stops counting against the budget once its
+//! trace is dropped.
+//!
+//! # Examples
+//!
+//! ```
+//! extern crate timely;
+//! extern crate differential_dataflow;
+//!
+//! use timely::dataflow::operators::Probe;
+//! use differential_dataflow::input::InputSession;
+//! use differential_dataflow::operators::arrange::{ArrangeBySelf, MemoryBudget};
+//!
+//! fn main() {
+//!     timely::execute_directly(move |worker| {
+//!
+//!         // A budget of one megabyte.
+//!         let budget = MemoryBudget::new(1 << 20);
+//!
+//!         let mut input = InputSession::new();
+//!         input.set_budget(budget.clone());
+//!
+//!         let probe = worker.dataflow(|scope| {
+//!             let mut arranged = input.to_collection(scope).arrange_by_self();
+//!             arranged.trace.set_budget(&budget);
+//!             arranged.stream.probe()
+//!         });
+//!
+//!         for round in 1 .. 10u64 {
+//!             input.insert(round);
+//!             // Step the worker until the budget permits the input to advance.
+//!             while !input.try_advance_to(round) {
+//!                 worker.step();
+//!             }
+//!             input.flush();
+//!             while probe.less_than(input.time()) { worker.step(); }
+//!         }
+//!     });
+//! }
+//! ```
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// A shared, soft limit on the memory held by a set of arrangements.
+///
+/// Cloning the budget produces another handle to the same budget.
+#[derive(Clone)]
+pub struct MemoryBudget {
+    state: Rc<RefCell<BudgetState>>,
+}
+
+struct BudgetState {
+    /// The number of bytes the arrangements may hold before pressure is signaled.
+    limit: usize,
+    /// The most recently reported bytes held by each monitored arrangement.
+    usage: HashMap<usize, usize>,
+    /// Identifier for the next monitored arrangement.
+    next_id: usize,
+}
+
+impl MemoryBudget {
+    /// Creates a budget permitting `limit` bytes.
+    pub fn new(limit: usize) -> Self {
+        MemoryBudget {
+            state: Rc::new(RefCell::new(BudgetState {
+                limit,
+                usage: HashMap::new(),
+                next_id: 0,
+            })),
+        }
+    }
+
+    /// The number of bytes permitted by the budget.
+    pub fn limit(&self) -> usize { self.state.borrow().limit }
+
+    /// Changes the number of bytes permitted by the budget.
+    pub fn set_limit(&self, limit: usize) { self.state.borrow_mut().limit = limit; }
+
+    /// The number of bytes most recently reported by monitored arrangements.
+    pub fn used(&self) -> usize { self.state.borrow().usage.values().sum() }
+
+    /// True when monitored arrangements hold more than the budget permits.
+    pub fn under_pressure(&self) -> bool { self.used() > self.limit() }
+
+    /// Registers a new arrangement, whose reported memory counts against the budget.
+    pub(crate) fn register(&self) -> BudgetAccount {
+        let mut state = self.state.borrow_mut();
+        state.next_id += 1;
+        state.usage.insert(state.next_id, 0);
+        BudgetAccount { budget: self.clone(), id: state.next_id }
+    }
+}
+
+/// The share of a budget held by one arrangement.
+///
+/// Dropping the account removes the arrangement's memory from the budget.
+pub(crate) struct BudgetAccount {
+    budget: MemoryBudget,
+    id: usize,
+}
+
+impl BudgetAccount {
+    /// Records that the arrangement currently holds `bytes` bytes.
+    pub fn report(&self, bytes: usize) {
+        self.budget.state.borrow_mut().usage.insert(self.id, bytes);
+    }
+}
+
+impl Drop for BudgetAccount {
+    fn drop(&mut self) {
+        self.budget.state.borrow_mut().usage.remove(&self.id);
+    }
+}
@@ -68,8 +68,12 @@ pub mod agent;
 pub mod arrangement;
 
 pub mod upsert;
+pub mod budget;
+pub mod shared;
 
 pub use self::writer::TraceWriter;
 pub use self::agent::{TraceAgent, ShutdownButton};
 
-pub use self::arrangement::{Arranged, Arrange, ArrangeByKey, ArrangeBySelf};
\ No newline at end of file
+pub use self::arrangement::{Arranged, Arrange, ArrangeByKey, ArrangeBySelf};
+pub use self::budget::MemoryBudget;
+pub use self::shared::{SharedTrace, SharedTraceWriter};
\ No newline at end of file
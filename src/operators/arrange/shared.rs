@@ -0,0 +1,549 @@
+//! Traces shared among the workers of a process.
+//!
+//! A `TraceAgent` is bound to the worker that created it, and each worker that requires a copy
+//! of a collection must maintain its own arrangement. For small collections replicated to every
+//! worker, such as reference tables, this holds one copy of the collection for each worker.
+//!
+//! A `SharedTrace` is instead maintained behind an `Arc`, with its batches behind `Arc`s as well,
+//! so that handles to it may be sent to each of the workers of a process. The trace is populated
+//! by a single `SharedTraceWriter`, commonly by publishing the batches of an arrangement built at
+//! one worker, and each worker may import the trace into its own dataflows.
+//!
+//! Batches are merged as they are introduced, by the thread that introduces them or that advances
+//! the frontiers of a handle. Merges happen outside the lock on the trace, so that other threads
+//! may read from and append to the trace meanwhile. As with other traces, the handles to the trace
+//! constrain its compaction: a handle that does not advance its compaction frontiers prevents the
+//! trace from merging, which is why the example below releases the handle it starts with.
+//!
+//! # Examples
+//!
+//! ```
+//! extern crate timely;
+//! extern crate differential_dataflow;
+//!
+//! use std::sync::Mutex;
+//!
+//! use timely::dataflow::channels::pact::Exchange;
+//! use timely::dataflow::operators::Probe;
+//!
+//! use differential_dataflow::input::Input;
+//! use differential_dataflow::operators::JoinCore;
+//! use differential_dataflow::operators::arrange::{Arrange, ArrangeByKey};
+//! use differential_dataflow::operators::arrange::shared::{self, SharedTrace};
+//! use differential_dataflow::trace::implementations::ord::{OrdValBatch, OrdValSpineArc};
+//!
+//! fn main() {
+//!
+//!     // One trace for the process, to be populated by the first worker.
+//!     let (writer, names) = SharedTrace::<u32, String, usize, isize, OrdValBatch<u32, String, usize, isize>>::new();
+//!     let writer = Mutex::new(Some(writer));
+//!
+//!     // One handle for each worker; the first handle would otherwise pin the trace's frontiers.
+//!     let handles = Mutex::new(vec![names.clone(), names]);
+//!
+//!     timely::execute(timely::Config::process(2), move |worker| {
+//!
+//!         let index = worker.index();
+//!         let writer = if index == 0 { writer.lock().unwrap().take() } else { None };
+//!
+//!         // Every worker constructs the dataflow, but the arrangement and its batches are at worker zero.
+//!         worker.dataflow::<usize,_,_>(|scope| {
+//!             let data = if index == 0 { vec![(0, "zero".to_string()), (1, "one".to_string())] } else { Vec::new() };
+//!             let arranged = scope.new_collection_from(data).1
+//!                                 .arrange_core::<_,OrdValSpineArc<_,_,_,_>>(Exchange::new(|_| 0), "Replicate");
+//!             shared::publish(&arranged, writer);
+//!         });
+//!
+//!         // Every worker reads the same trace, without exchanging data.
+//!         let probe = worker.dataflow::<usize,_,_>(|scope| {
+//!             let names = handles.lock().unwrap().pop().unwrap().import(scope);
+//!             scope.new_collection_from(vec![(index as u32, ())]).1
+//!                  .arrange_by_key()
+//!                  .join_core(&names, |key, &(), name| Some((*key, name.clone())))
+//!                  .inspect(move |x| println!("worker {}: {:?}", index, x))
+//!                  .probe()
+//!         });
+//!
+//!         while !probe.done() { worker.step(); }
+//!
+//!     }).unwrap();
+//! }
+//! ```
+
+use std::sync::{Arc, Mutex, Weak};
+use std::collections::{BTreeMap, VecDeque};
+use std::marker::PhantomData;
+
+use timely::dataflow::Scope;
+use timely::dataflow::channels::pact::Pipeline;
+use timely::dataflow::operators::CapabilitySet;
+use timely::dataflow::operators::generic::{source, Operator};
+use timely::order::PartialOrder;
+use timely::progress::{Antichain, Timestamp, frontier::AntichainRef};
+use timely::scheduling::SyncActivator;
+
+use ::difference::Semigroup;
+use lattice::Lattice;
+use trace::{TraceReader, Batch, BatchReader, Builder, Merger, Cursor};
+use trace::cursor::CursorList;
+
+use super::Arranged;
+
+/// Instructions from the trace to an importing operator.
+enum SharedInstruction<T, B> {
+    /// Describes a frontier advance.
+    Frontier(Antichain<T>),
+    /// Describes a batch of data and a capability hint.
+    Batch(Arc<B>, Option<T>),
+}
+
+/// A queue of instructions, and the means to activate the operator that consumes them.
+type SharedQueue<T, B> = Mutex<(SyncActivator, VecDeque<SharedInstruction<T, B>>)>;
+
+/// The state of a shared trace, common to the writer and all handles.
+struct SharedState<K, V, T, R, B> {
+    phantom: PhantomData<(K, V, R)>,
+    /// Contiguous batches, oldest first.
+    batches: Vec<Arc<B>>,
+    /// The upper frontier of the most recent batch.
+    upper: Antichain<T>,
+    /// The logical and physical compaction frontiers of each handle.
+    holders: BTreeMap<usize, (Antichain<T>, Antichain<T>)>,
+    /// Identifier for the next handle.
+    next_holder: usize,
+    /// Queues of importing operators.
+    listeners: Vec<Weak<SharedQueue<T, B>>>,
+    /// Indicates that some thread is merging batches, outside the lock.
+    merging: bool,
+}
+
+impl<K, V, T, R, B> SharedState<K, V, T, R, B>
+where
+    T: Lattice+Ord+Clone,
+    B: Batch<K, V, T, R>,
+{
+    /// Registers a handle with the supplied frontiers, returning its identifier.
+    fn register(&mut self, logical: Antichain<T>, physical: Antichain<T>) -> usize {
+        self.next_holder += 1;
+        let id = self.next_holder;
+        self.holders.insert(id, (logical, physical));
+        id
+    }
+
+    /// The lower envelope of the selected frontiers of all handles, or `upper` if there are none.
+    fn joint<F: Fn(&(Antichain<T>, Antichain<T>))->&Antichain<T>>(&self, select: F) -> Antichain<T> {
+        if self.holders.is_empty() {
+            self.upper.clone()
+        }
+        else {
+            let mut joint = Antichain::new();
+            for frontiers in self.holders.values() {
+                for time in select(frontiers).elements() {
+                    joint.insert(time.clone());
+                }
+            }
+            joint
+        }
+    }
+
+    /// Appends `batch` to the trace and informs the importing operators.
+    fn insert(&mut self, batch: Arc<B>, hint: Option<T>) {
+
+        assert!(&self.upper == batch.lower());
+        assert!(batch.lower() != batch.upper());
+
+        self.upper.clone_from(batch.upper());
+
+        for listener in self.listeners.iter() {
+            if let Some(listener) = listener.upgrade() {
+                let mut borrow = listener.lock().expect("shared trace queue poisoned");
+                borrow.1.push_back(SharedInstruction::Batch(batch.clone(), hint.clone()));
+                borrow.1.push_back(SharedInstruction::Frontier(batch.upper().clone()));
+                // The operator may have completed, in which case there is nothing to activate.
+                let _ = borrow.0.activate();
+            }
+        }
+        self.listeners.retain(|w| w.upgrade().is_some());
+
+        self.batches.push(batch);
+    }
+
+    /// Selects the two most recent batches for merging, if they are of comparable size and
+    /// permitted by the physical compaction of the handles, and no other merge is underway.
+    ///
+    /// Returns the batches and the logical compaction frontier to merge them with. The caller
+    /// must merge them and then call `complete_merge`.
+    fn next_merge(&mut self) -> Option<(Arc<B>, Arc<B>, Antichain<T>)> {
+        let len = self.batches.len();
+        if self.merging || len < 2 { return None; }
+        let physical = self.joint(|f| &f.1);
+        let older = &self.batches[len - 2];
+        let newer = &self.batches[len - 1];
+        if older.len() <= 2 * newer.len() && PartialOrder::less_equal(&newer.upper().borrow(), &physical.borrow()) {
+            self.merging = true;
+            Some((older.clone(), newer.clone(), self.joint(|f| &f.0)))
+        }
+        else {
+            None
+        }
+    }
+
+    /// Replaces the adjacent batches `older` and `newer` by `merged`.
+    ///
+    /// Batches are only appended while a merge is underway, so the two batches are still present.
+    fn complete_merge(&mut self, older: &Arc<B>, newer: &Arc<B>, merged: B) {
+        let position =
+        self.batches
+            .windows(2)
+            .position(|pair| Arc::ptr_eq(&pair[0], older) && Arc::ptr_eq(&pair[1], newer))
+            .expect("merged batches missing from shared trace");
+        self.batches.remove(position + 1);
+        self.batches[position] = Arc::new(merged);
+        self.merging = false;
+    }
+}
+
+/// Merges recent batches of comparable size, without holding the lock while merging.
+///
+/// Readers continue to use the batches being merged until the merge completes, and the logical
+/// compaction frontier may advance meanwhile, which at worst leaves the merged batch less compact.
+fn consider_merges<K, V, T, R, B>(state: &Mutex<SharedState<K, V, T, R, B>>)
+where
+    T: Lattice+Ord+Clone,
+    B: Batch<K, V, T, R>,
+{
+    loop {
+        let next = state.lock().expect("shared trace poisoned").next_merge();
+        match next {
+            Some((older, newer, logical)) => {
+                let mut merger = <B as Batch<K,V,T,R>>::begin_merge(&older, &newer, Some(logical.borrow()));
+                let mut fuel = isize::max_value();
+                merger.work(&older, &newer, &mut fuel);
+                let merged = merger.done();
+                state.lock().expect("shared trace poisoned").complete_merge(&older, &newer, merged);
+            },
+            None => { break; },
+        }
+    }
+}
+
+/// A trace shared among the workers of a process.
+///
+/// Each handle is a `TraceReader` with its own compaction frontiers, and may be sent to another
+/// thread. Cloning a handle produces a handle with the same frontiers.
+pub struct SharedTrace<K, V, T, R, B> {
+    state: Arc<Mutex<SharedState<K, V, T, R, B>>>,
+    /// Identifies this handle among the holders of the trace.
+    holder: usize,
+    logical_compaction: Antichain<T>,
+    physical_compaction: Antichain<T>,
+}
+
+/// Write endpoint for a shared trace.
+///
+/// Dropping the writer indicates that the trace is complete.
+pub struct SharedTraceWriter<K, V, T, R, B>
+where
+    T: Lattice+Timestamp+Ord+Clone,
+    B: Batch<K, V, T, R>,
+{
+    state: Arc<Mutex<SharedState<K, V, T, R, B>>>,
+}
+
+impl<K, V, T, R, B> SharedTrace<K, V, T, R, B>
+where
+    T: Lattice+Timestamp+Ord+Clone,
+    B: Batch<K, V, T, R>,
+{
+    /// Creates an empty shared trace, returning a writer and a first handle.
+    pub fn new() -> (SharedTraceWriter<K, V, T, R, B>, Self) {
+        let minimum = Antichain::from_elem(T::minimum());
+        let mut state = SharedState {
+            phantom: PhantomData,
+            batches: Vec::new(),
+            upper: minimum.clone(),
+            holders: BTreeMap::new(),
+            next_holder: 0,
+            listeners: Vec::new(),
+            merging: false,
+        };
+        let holder = state.register(minimum.clone(), minimum.clone());
+        let state = Arc::new(Mutex::new(state));
+        let writer = SharedTraceWriter { state: state.clone() };
+        let trace = SharedTrace {
+            state,
+            holder,
+            logical_compaction: minimum.clone(),
+            physical_compaction: minimum,
+        };
+        (writer, trace)
+    }
+
+    /// Updates the frontiers this handle reports to the trace, and considers merging.
+    fn update_holder(&mut self) {
+        self.state
+            .lock()
+            .expect("shared trace poisoned")
+            .holders
+            .insert(self.holder, (self.logical_compaction.clone(), self.physical_compaction.clone()));
+        consider_merges(&self.state);
+    }
+
+    /// Imports the trace into the supplied scope.
+    ///
+    /// The resulting arrangement presents the batches of the trace at the time of the import,
+    /// followed by those introduced subsequently. Its trace is a new handle to the shared trace.
+    pub fn import<G>(&self, scope: &G) -> Arranged<G, Self>
+    where
+        G: Scope<Timestamp=T>,
+        K: Ord+Clone+'static,
+        V: Ord+Clone+'static,
+        R: Semigroup+'static,
+        B: 'static,
+    {
+        self.import_named(scope, "SharedSource")
+    }
+
+    /// Same as `import`, but allows to name the source.
+    pub fn import_named<G>(&self, scope: &G, name: &str) -> Arranged<G, Self>
+    where
+        G: Scope<Timestamp=T>,
+        K: Ord+Clone+'static,
+        V: Ord+Clone+'static,
+        R: Semigroup+'static,
+        B: 'static,
+    {
+        let trace = self.clone();
+
+        let stream = source(scope, name, move |capability, info| {
+
+            let mut capabilities = CapabilitySet::new();
+            capabilities.insert(capability);
+
+            // Prime the queue with the existing batches of the trace.
+            let activator = scope.sync_activator_for(&info.address[..]);
+            let queue = {
+                let mut state = self.state.lock().expect("shared trace poisoned");
+                let mut instructions = VecDeque::new();
+                for batch in state.batches.iter() {
+                    instructions.push_back(SharedInstruction::Batch(batch.clone(), Some(T::minimum())));
+                }
+                instructions.push_back(SharedInstruction::Frontier(state.upper.clone()));
+                let queue = Arc::new(Mutex::new((activator, instructions)));
+                state.listeners.push(Arc::downgrade(&queue));
+                queue
+            };
+
+            move |output| {
+                let mut borrow = queue.lock().expect("shared trace queue poisoned");
+                for instruction in borrow.1.drain(..) {
+                    match instruction {
+                        SharedInstruction::Frontier(frontier) => {
+                            capabilities.downgrade(&frontier.borrow()[..]);
+                        },
+                        SharedInstruction::Batch(batch, hint) => {
+                            if let Some(time) = hint {
+                                if !batch.is_empty() {
+                                    let delayed = capabilities.delayed(&time);
+                                    output.session(&delayed).give(batch);
+                                }
+                            }
+                        },
+                    }
+                }
+            }
+        });
+
+        Arranged { stream, trace }
+    }
+}
+
+impl<K, V, T, R, B> TraceReader for SharedTrace<K, V, T, R, B>
+where
+    K: Ord+Clone,
+    V: Ord+Clone,
+    T: Lattice+Timestamp+Ord+Clone,
+    R: Semigroup,
+    B: Batch<K, V, T, R>+'static,
+{
+    type Key = K;
+    type Val = V;
+    type Time = T;
+    type R = R;
+
+    type Batch = Arc<B>;
+    type Cursor = CursorList<K, V, T, R, <Arc<B> as BatchReader<K, V, T, R>>::Cursor>;
+
+    fn cursor_through(&mut self, upper: AntichainRef<T>) -> Option<(Self::Cursor, <Self::Cursor as Cursor<K, V, T, R>>::Storage)> {
+
+        // Merged batches may not be separated before the physical compaction frontier.
+        assert!(PartialOrder::less_equal(&self.physical_compaction.borrow(), &upper));
+
+        let mut cursors = Vec::new();
+        let mut storage = Vec::new();
+
+        let state = self.state.lock().expect("shared trace poisoned");
+        for batch in state.batches.iter() {
+            if !batch.is_empty() {
+                let include_lower = PartialOrder::less_equal(&batch.lower().borrow(), &upper);
+                let include_upper = PartialOrder::less_equal(&batch.upper().borrow(), &upper);
+                // A batch straddling `upper` cannot be subset.
+                if include_lower != include_upper && upper != batch.lower().borrow() {
+                    return None;
+                }
+                if include_upper {
+                    cursors.push(batch.cursor());
+                    storage.push(batch.clone());
+                }
+            }
+        }
+
+        Some((CursorList::new(cursors, &storage), storage))
+    }
+    fn set_logical_compaction(&mut self, frontier: AntichainRef<T>) {
+        let mut joined = Antichain::new();
+        crate::lattice::antichain_join_into(&self.logical_compaction.borrow()[..], &frontier[..], &mut joined);
+        if joined != self.logical_compaction {
+            self.logical_compaction = joined;
+            self.update_holder();
+        }
+    }
+    fn get_logical_compaction(&mut self) -> AntichainRef<T> { self.logical_compaction.borrow() }
+    fn set_physical_compaction(&mut self, frontier: AntichainRef<T>) {
+        let mut joined = Antichain::new();
+        crate::lattice::antichain_join_into(&self.physical_compaction.borrow()[..], &frontier[..], &mut joined);
+        if joined != self.physical_compaction {
+            self.physical_compaction = joined;
+            self.update_holder();
+        }
+    }
+    fn get_physical_compaction(&mut self) -> AntichainRef<T> { self.physical_compaction.borrow() }
+
+    fn map_batches<F: FnMut(&Self::Batch)>(&self, mut f: F) {
+        // Release the lock before calling `f`, which may itself use the trace.
+        let batches = self.state.lock().expect("shared trace poisoned").batches.clone();
+        for batch in batches.iter() {
+            f(batch);
+        }
+    }
+}
+
+impl<K, V, T, R, B> Clone for SharedTrace<K, V, T, R, B>
+where
+    T: Lattice+Ord+Clone,
+    B: Batch<K, V, T, R>,
+{
+    fn clone(&self) -> Self {
+        let holder = self.state
+            .lock()
+            .expect("shared trace poisoned")
+            .register(self.logical_compaction.clone(), self.physical_compaction.clone());
+        SharedTrace {
+            state: self.state.clone(),
+            holder,
+            logical_compaction: self.logical_compaction.clone(),
+            physical_compaction: self.physical_compaction.clone(),
+        }
+    }
+}
+
+impl<K, V, T, R, B> Drop for SharedTrace<K, V, T, R, B> {
+    fn drop(&mut self) {
+        // A poisoned lock means some other handle panicked, and there is nothing to release.
+        if let Ok(mut state) = self.state.lock() {
+            state.holders.remove(&self.holder);
+        }
+    }
+}
+
+impl<K, V, T, R, B> SharedTraceWriter<K, V, T, R, B>
+where
+    T: Lattice+Timestamp+Ord+Clone,
+    B: Batch<K, V, T, R>,
+{
+    /// Advances the trace by `batch`.
+    ///
+    /// The `hint` argument is either `None` in the case of an empty batch,
+    /// or is `Some(time)` for a time less or equal to all updates in the
+    /// batch and which is suitable for use as a capability.
+    pub fn insert(&mut self, batch: Arc<B>, hint: Option<T>) {
+        self.state.lock().expect("shared trace poisoned").insert(batch, hint);
+        consider_merges(&self.state);
+    }
+
+    /// Inserts an empty batch up to `upper`.
+    pub fn seal(&mut self, upper: Antichain<T>) {
+        {
+            let mut state = self.state.lock().expect("shared trace poisoned");
+            if state.upper != upper {
+                let builder = <B as Batch<K,V,T,R>>::Builder::new();
+                let batch = builder.done(state.upper.clone(), upper, Antichain::from_elem(T::minimum()));
+                state.insert(Arc::new(batch), None);
+            }
+        }
+        consider_merges(&self.state);
+    }
+}
+
+impl<K, V, T, R, B> Drop for SharedTraceWriter<K, V, T, R, B>
+where
+    T: Lattice+Timestamp+Ord+Clone,
+    B: Batch<K, V, T, R>,
+{
+    fn drop(&mut self) {
+        self.seal(Antichain::new())
+    }
+}
+
+/// Publishes the batches of `arranged` to a shared trace.
+///
+/// Every worker must call this method when constructing the dataflow, but only the worker that
+/// holds the arrangement's data should supply `writer`; the batches of other workers are ignored.
+/// Commonly, the arrangement is formed with a parallelization contract that directs all updates
+/// to that worker. The trace is sealed once the arrangement's stream is complete.
+pub fn publish<G, Tr, K, V, R, B>(arranged: &Arranged<G, Tr>, writer: Option<SharedTraceWriter<K, V, G::Timestamp, R, B>>)
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    Tr: TraceReader<Key=K, Val=V, Time=G::Timestamp, R=R, Batch=Arc<B>>+Clone+'static,
+    B: Batch<K, V, G::Timestamp, R>+'static,
+    K: 'static,
+    V: 'static,
+    R: 'static,
+{
+    let mut writer = writer;
+    let mut trace = Some(arranged.trace.clone());
+    let mut upper = Antichain::from_elem(<G::Timestamp as Timestamp>::minimum());
+    let mut buffer = Vec::new();
+
+    arranged.stream.sink(Pipeline, "SharedPublish", move |input| {
+
+        input.for_each(|capability, batches| {
+            batches.swap(&mut buffer);
+            for batch in buffer.drain(..) {
+                upper.clone_from(batch.upper());
+                if let Some(writer) = writer.as_mut() {
+                    // The arranged stream omits frontier advances made while no updates were held,
+                    // so the gap before `batch` holds no updates and is published as an empty batch.
+                    writer.seal(batch.lower().clone());
+                    writer.insert(batch, Some(capability.time().clone()));
+                }
+            }
+        });
+
+        if input.frontier().frontier().is_empty() {
+            // Release the trace and the writer, which seals the shared trace.
+            trace = None;
+            writer = None;
+        }
+        else if let Some(trace) = trace.as_mut() {
+            // Advance through empty batches, which are not sent, and release their history.
+            trace.advance_upper(&mut upper);
+            trace.set_logical_compaction(upper.borrow());
+            trace.set_physical_compaction(upper.borrow());
+            if let Some(writer) = writer.as_mut() {
+                writer.seal(upper.clone());
+            }
+        }
+    });
+}
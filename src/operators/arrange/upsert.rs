@@ -13,11 +13,21 @@
 //!
 //! # Notes
 //!
-//! Upserts currently only work with totally ordered timestamps.
+//! The `arrange_from_upsert` operator only works with totally ordered timestamps, for which
+//! the most recent value of each key can be read back from the arrangement itself. The
+//! `arrange_from_upsert_partial` operator supports partially ordered timestamps, at the cost
+//! of retaining the upserts themselves in addition to the arrangement. For partially ordered
+//! times the value of a key at a time is determined by those upserts at times less or equal
+//! to it that are not themselves less than another such upsert.
 //!
 //! In the case of ties in timestamps (concurrent updates to the same key) they choose
 //! the *greatest* value according to `Option<Val>` ordering, which will prefer a value
 //! to `None` and choose the greatest value (informally, as if applied in order of value).
+//! For partially ordered times, incomparable upserts are treated as ties.
+//!
+//! The `arrange_from_upsert_with_ttl` operator additionally removes keys that have not been
+//! written for a configurable duration, expressed as a timestamp summary. The removals are
+//! scheduled alongside pending upserts, and are cancelled by any subsequent write to the key.
 //!
 //! If the same value is repeated, no change will occur in the output. That may make this
 //! operator effective at determining the difference between collections of keyed values,
@@ -111,11 +121,12 @@ use timely::order::{PartialOrder, TotalOrder};
 use timely::dataflow::{Scope, Stream};
 use timely::dataflow::operators::generic::Operator;
 use timely::dataflow::channels::pact::Exchange;
-use timely::progress::Timestamp;
-use timely::progress::Antichain;
+use timely::progress::{PathSummary, Timestamp};
+use timely::progress::{Antichain, frontier::AntichainRef};
 use timely::dataflow::operators::Capability;
 
 use ::{ExchangeData, Hashable};
+use consolidation::consolidate;
 use lattice::Lattice;
 use trace::{Trace, TraceReader, Batch, Cursor};
 
@@ -132,13 +143,85 @@ use super::TraceAgent;
 /// value in sequence either replaces or removes the existing value, should it
 /// exist.
 ///
-/// This method is only implemented for totally ordered times, as we do not yet
-/// understand what a "sequence" of upserts would mean for partially ordered
-/// timestamps.
+/// This method is only implemented for totally ordered times, for which the prior
+/// value of each key can be recovered from the arrangement. For partially ordered
+/// timestamps, use `arrange_from_upsert_partial`.
 pub fn arrange_from_upsert<G, Tr>(
     stream: &Stream<G, (Tr::Key, Option<Tr::Val>, G::Timestamp)>,
     name: &str,
 ) -> Arranged<G, TraceAgent<Tr>>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord+TotalOrder+ExchangeData,
+    Tr::Key: ExchangeData+Hashable+std::hash::Hash,
+    Tr::Val: ExchangeData,
+    Tr: Trace+TraceReader<Time=G::Timestamp,R=isize>+'static,
+    Tr::Batch: Batch<Tr::Key, Tr::Val, G::Timestamp, isize>,
+    Tr::Cursor: Cursor<Tr::Key, Tr::Val, G::Timestamp, isize>,
+{
+    arrange_from_upsert_core(stream, name, None)
+}
+
+/// Arrange data from a stream of keyed upserts, expiring keys that are not written for `ttl`.
+///
+/// This method behaves as `arrange_from_upsert`, except that a key whose most recent upsert
+/// installed a value at time `t` is removed at time `t` advanced by `ttl` (as determined by
+/// `ttl.results_in(&t)`), unless it is written again before then. The removal is processed
+/// like any other upsert, once the input frontier passes the expiry time, and an upsert at
+/// exactly the expiry time takes precedence over the expiry.
+///
+/// # Examples
+///
+/// ```
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use timely::dataflow::operators::Input;
+/// use differential_dataflow::trace::implementations::ord::OrdValSpine;
+/// use differential_dataflow::operators::arrange::upsert;
+///
+/// fn main() {
+///     timely::execute_directly(move |worker| {
+///
+///         let mut input = timely::dataflow::InputHandle::new();
+///         let probe = worker.dataflow(|scope| {
+///             let stream = scope.input_from(&mut input);
+///             // Keys expire ten time units after their last write.
+///             upsert::arrange_from_upsert_with_ttl::<_, OrdValSpine<String, String, u64, isize>>(&stream, "Sessions", 10)
+///                 .as_collection(|k,v| (k.clone(), v.clone()))
+///                 .inspect(|x| println!("Observed: {:?}", x))
+///                 .probe()
+///         });
+///
+///         input.send(("session".to_string(), Some("data".to_string()), 0));
+///         input.advance_to(20);
+///         while probe.less_than(input.time()) { worker.step(); }
+///     });
+/// }
+/// ```
+pub fn arrange_from_upsert_with_ttl<G, Tr>(
+    stream: &Stream<G, (Tr::Key, Option<Tr::Val>, G::Timestamp)>,
+    name: &str,
+    ttl: <G::Timestamp as Timestamp>::Summary,
+) -> Arranged<G, TraceAgent<Tr>>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord+TotalOrder+ExchangeData,
+    Tr::Key: ExchangeData+Hashable+std::hash::Hash,
+    Tr::Val: ExchangeData,
+    Tr: Trace+TraceReader<Time=G::Timestamp,R=isize>+'static,
+    Tr::Batch: Batch<Tr::Key, Tr::Val, G::Timestamp, isize>,
+    Tr::Cursor: Cursor<Tr::Key, Tr::Val, G::Timestamp, isize>,
+{
+    arrange_from_upsert_core(stream, name, Some(ttl))
+}
+
+/// Arrange data from a stream of keyed upserts, with optional expiry of unwritten keys.
+fn arrange_from_upsert_core<G, Tr>(
+    stream: &Stream<G, (Tr::Key, Option<Tr::Val>, G::Timestamp)>,
+    name: &str,
+    ttl: Option<<G::Timestamp as Timestamp>::Summary>,
+) -> Arranged<G, TraceAgent<Tr>>
 where
     G: Scope,
     G::Timestamp: Lattice+Ord+TotalOrder+ExchangeData,
@@ -188,7 +271,10 @@ where
             let mut prev_frontier = Antichain::from_elem(<G::Timestamp as Timestamp>::minimum());
 
             // For stashing input upserts, ordered increasing by time (`BinaryHeap` is a max-heap).
-            let mut priority_queue = BinaryHeap::<std::cmp::Reverse<(G::Timestamp, Tr::Key, Option<Tr::Val>)>>::new();
+            // The final flag indicates a scheduled expiry rather than an upsert from the input.
+            let mut priority_queue = BinaryHeap::<std::cmp::Reverse<(G::Timestamp, Tr::Key, Option<Tr::Val>, bool)>>::new();
+            // For each key with a value, the time at which it expires (only if `ttl` is set).
+            let mut expirations = HashMap::<Tr::Key, G::Timestamp>::new();
             let mut updates = Vec::new();
 
             move |input, output| {
@@ -198,7 +284,7 @@ where
                     capabilities.insert(cap.retain());
                     data.swap(&mut buffer);
                     for (key, val, time) in buffer.drain(..) {
-                        priority_queue.push(std::cmp::Reverse((time, key, val)))
+                        priority_queue.push(std::cmp::Reverse((time, key, val, false)))
                     }
                 });
 
@@ -233,9 +319,9 @@ where
 
                                 // Extract upserts available to process as of this `upper`.
                                 let mut to_process = HashMap::new();
-                                while priority_queue.peek().map(|std::cmp::Reverse((t,_k,_v))| !upper.less_equal(t)).unwrap_or(false) {
-                                    let std::cmp::Reverse((time, key, val)) = priority_queue.pop().expect("Priority queue just ensured non-empty");
-                                    to_process.entry(key).or_insert(Vec::new()).push((time, std::cmp::Reverse(val)));
+                                while priority_queue.peek().map(|std::cmp::Reverse((t,_k,_v,_e))| !upper.less_equal(t)).unwrap_or(false) {
+                                    let std::cmp::Reverse((time, key, val, expiry)) = priority_queue.pop().expect("Priority queue just ensured non-empty");
+                                    to_process.entry(key).or_insert(Vec::new()).push((time, std::cmp::Reverse(val), expiry));
                                 }
                                 // Reduce the allocation behind the priority queue if it is presently excessive.
                                 // A factor of four is used to avoid repeated doubling and shrinking.
@@ -253,7 +339,7 @@ where
                                 // new stuff that we add.
                                 let (mut trace_cursor, trace_storage) = reader_local.cursor();
                                 let mut builder = <Tr::Batch as Batch<Tr::Key,Tr::Val,G::Timestamp,Tr::R>>::Builder::new();
-                                for (key, mut list) in to_process.drain(..) {
+                                for (key, list) in to_process.drain(..) {
 
                                     // The prior value associated with the key.
                                     let mut prev_value: Option<Tr::Val> = None;
@@ -275,10 +361,38 @@ where
                                         trace_cursor.step_key(&trace_storage);
                                     }
 
-                                    // Sort the list of upserts to `key` by their time, suppress multiple updates.
-                                    list.sort();
-                                    list.dedup_by(|(t1,_), (t2,_)| t1 == t2);
-                                    for (time, std::cmp::Reverse(next)) in list {
+                                    // Order the upserts to `key` by their time, suppressing multiple updates.
+                                    // Expiries scheduled within this batch are introduced as we go, so we
+                                    // draw from a heap rather than a sorted list.
+                                    let mut list = list.into_iter().map(std::cmp::Reverse).collect::<BinaryHeap<_>>();
+                                    let mut prev_time: Option<G::Timestamp> = None;
+                                    while let Some(std::cmp::Reverse((time, std::cmp::Reverse(next), expiry))) = list.pop() {
+                                        if prev_time.as_ref() == Some(&time) { continue; }
+                                        prev_time = Some(time.clone());
+                                        if let Some(ttl) = ttl.as_ref() {
+                                            if expiry {
+                                                // Only the expiry scheduled by the most recent write has an effect.
+                                                if expirations.get(&key) != Some(&time) { continue; }
+                                                expirations.remove(&key);
+                                            }
+                                            else if next.is_some() {
+                                                match ttl.results_in(&time) {
+                                                    Some(expire) => {
+                                                        expirations.insert(key.clone(), expire.clone());
+                                                        if upper.less_equal(&expire) {
+                                                            priority_queue.push(std::cmp::Reverse((expire, key.clone(), None, true)));
+                                                        }
+                                                        else {
+                                                            list.push(std::cmp::Reverse((expire, std::cmp::Reverse(None), true)));
+                                                        }
+                                                    },
+                                                    None => { expirations.remove(&key); },
+                                                }
+                                            }
+                                            else {
+                                                expirations.remove(&key);
+                                            }
+                                        }
                                         if prev_value != next {
                                             if let Some(prev) = prev_value {
                                                 updates.push((key.clone(), prev, time.clone(), -1));
@@ -309,8 +423,14 @@ where
                         // This may involve discarding capabilities, which is fine as any new updates arrive
                         // in messages with new capabilities.
 
+                        // Expiries superseded by a later write to their key would have no effect, and
+                        // must not hold back the output frontier until their time.
+                        while priority_queue.peek().map(|std::cmp::Reverse((t,k,_v,e))| *e && expirations.get(k) != Some(t)).unwrap_or(false) {
+                            priority_queue.pop();
+                        }
+
                         let mut new_capabilities = Antichain::new();
-                        if let Some(std::cmp::Reverse((time, _, _))) = priority_queue.peek() {
+                        if let Some(std::cmp::Reverse((time, _, _, _))) = priority_queue.peek() {
                             if let Some(capability) = capabilities.elements().iter().find(|c| c.time().less_equal(time)) {
                                 new_capabilities.insert(capability.delayed(time));
                             }
@@ -345,3 +465,290 @@ where
     Arranged { stream: stream, trace: reader.unwrap() }
 
 }
+
+/// Arrange data from a stream of keyed upserts, with partially ordered timestamps.
+///
+/// The input should be a stream of timestamped pairs of Key and Option<Val>. The value of
+/// a key at a time is determined by the upserts at times less or equal to that time which
+/// are not less than any other such upsert; should there be several, the greatest value is
+/// chosen, with `None` less than any value. For totally ordered times this coincides with
+/// `arrange_from_upsert`, which should be preferred as it does not retain upsert history.
+///
+/// The operator retains, for each key, those upserts that may still determine the value of
+/// the key at times beyond the input frontier. Upserts that are less than another upsert and
+/// indistinguishable from it at all future times are discarded as the input frontier advances.
+pub fn arrange_from_upsert_partial<G, Tr>(
+    stream: &Stream<G, (Tr::Key, Option<Tr::Val>, G::Timestamp)>,
+    name: &str,
+) -> Arranged<G, TraceAgent<Tr>>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord+ExchangeData,
+    Tr::Key: ExchangeData+Hashable+std::hash::Hash,
+    Tr::Val: ExchangeData,
+    Tr: Trace+TraceReader<Time=G::Timestamp,R=isize>+'static,
+    Tr::Batch: Batch<Tr::Key, Tr::Val, G::Timestamp, isize>,
+    Tr::Cursor: Cursor<Tr::Key, Tr::Val, G::Timestamp, isize>,
+{
+    let mut reader: Option<TraceAgent<Tr>> = None;
+
+    // fabricate a data-parallel operator using the `unary_notify` pattern.
+    let stream = {
+
+        let reader = &mut reader;
+
+        let exchange = Exchange::new(move |update: &(Tr::Key,Option<Tr::Val>,G::Timestamp)| (update.0).hashed().into());
+
+        stream.unary_frontier(exchange, name, move |_capability, info| {
+
+            // Acquire a logger for arrange events.
+            let logger = {
+                let scope = stream.scope();
+                let register = scope.log_register();
+                register.get::<::logging::DifferentialEvent>("differential/arrange")
+            };
+
+            // Establish compaction effort to apply even without updates.
+            let (activator, effort) =
+            if let Some(effort) = stream.scope().config().get::<isize>("differential/idle_merge_effort").cloned() {
+                (Some(stream.scope().activator_for(&info.address[..])), Some(effort))
+            }
+            else {
+                (None, None)
+            };
+
+            // Tracks the lower envelope of times in `pending` and `deferred`.
+            let mut capabilities = Antichain::<Capability<G::Timestamp>>::new();
+            let mut buffer = Vec::new();
+            // Form the trace we will both use internally and publish.
+            let empty_trace = Tr::new(info.clone(), logger.clone(), activator);
+            let (mut reader_local, mut writer) = TraceAgent::new(empty_trace, info, logger);
+            // Capture the reader outside the builder scope.
+            *reader = Some(reader_local.clone());
+
+            // Tracks the input frontier, used to populate the lower bound of new batches.
+            let mut prev_frontier = Antichain::from_elem(<G::Timestamp as Timestamp>::minimum());
+
+            // Upserts not yet applied; with partially ordered times there is no order in which to extract them.
+            let mut pending = Vec::<(G::Timestamp, Tr::Key, Option<Tr::Val>)>::new();
+            // Applied upserts that may yet influence the value of their key, by key.
+            let mut history = HashMap::<Tr::Key, Vec<(G::Timestamp, Option<Tr::Val>)>>::new();
+            // Times at which the value of a key must be re-evaluated, but which were not complete.
+            let mut deferred = HashMap::<Tr::Key, Vec<G::Timestamp>>::new();
+            let mut updates = Vec::new();
+
+            move |input, output| {
+
+                // Stash capabilities and associated data.
+                input.for_each(|cap, data| {
+                    capabilities.insert(cap.retain());
+                    data.swap(&mut buffer);
+                    for (key, val, time) in buffer.drain(..) {
+                        pending.push((time, key, val));
+                    }
+                });
+
+                // Assert that the frontier never regresses.
+                assert!(PartialOrder::less_equal(&prev_frontier.borrow(), &input.frontier().frontier()));
+
+                // Test to see if strict progress has occurred, which happens whenever the new
+                // frontier isn't equal to the previous. It is only in this case that we have any
+                // data processing to do.
+                if prev_frontier.borrow() != input.frontier().frontier() {
+
+                    // If there is at least one capability not in advance of the input frontier ...
+                    if capabilities.elements().iter().any(|c| !input.frontier().less_equal(c.time())) {
+
+                        let mut upper = Antichain::new();   // re-used allocation for sealing batches.
+
+                        // For each capability not in advance of the input frontier ...
+                        for (index, capability) in capabilities.elements().iter().enumerate() {
+
+                            if !input.frontier().less_equal(capability.time()) {
+
+                                // Assemble the upper bound on times we can commit with this capabilities.
+                                // We must respect the input frontier, and *subsequent* capabilities, as
+                                // we are pretending to retire the capability changes one by one.
+                                upper.clear();
+                                for time in input.frontier().frontier().iter() {
+                                    upper.insert(time.clone());
+                                }
+                                for other_capability in &capabilities.elements()[(index + 1) .. ] {
+                                    upper.insert(other_capability.time().clone());
+                                }
+
+                                // Extract upserts and deferred times available to process as of this `upper`.
+                                let mut to_process = HashMap::new();
+                                let mut cursor = 0;
+                                while cursor < pending.len() {
+                                    if !upper.less_equal(&pending[cursor].0) {
+                                        let (time, key, val) = pending.swap_remove(cursor);
+                                        to_process.entry(key).or_insert_with(|| (Vec::new(), Vec::new())).0.push((time, val));
+                                    }
+                                    else {
+                                        cursor += 1;
+                                    }
+                                }
+                                for (key, times) in deferred.iter_mut() {
+                                    if times.iter().any(|t| !upper.less_equal(t)) {
+                                        let entry = to_process.entry(key.clone()).or_insert_with(|| (Vec::new(), Vec::new()));
+                                        times.retain(|t| if !upper.less_equal(t) { entry.1.push(t.clone()); false } else { true });
+                                    }
+                                }
+                                deferred.retain(|_key, times| !times.is_empty());
+
+                                // Put (key, list) into key order, to match cursor enumeration.
+                                let mut to_process = to_process.into_iter().collect::<Vec<_>>();
+                                to_process.sort_by(|x, y| x.0.cmp(&y.0));
+
+                                // Prepare a cursor to the existing arrangement, and a batch builder for
+                                // new stuff that we add.
+                                let (mut trace_cursor, trace_storage) = reader_local.cursor();
+                                let mut builder = <Tr::Batch as Batch<Tr::Key,Tr::Val,G::Timestamp,Tr::R>>::Builder::new();
+                                for (key, (upserts, mut times)) in to_process.drain(..) {
+
+                                    // Install new upserts in the history of the key.
+                                    let list = history.entry(key.clone()).or_insert_with(Vec::new);
+                                    times.extend(upserts.iter().map(|(time, _)| time.clone()));
+                                    list.extend(upserts);
+
+                                    // The value may change at joins of new times with any upsert times.
+                                    let mut cursor = 0;
+                                    while cursor < times.len() {
+                                        for (time, _) in list.iter() {
+                                            let join = times[cursor].join(time);
+                                            if !times.contains(&join) {
+                                                times.push(join);
+                                            }
+                                        }
+                                        cursor += 1;
+                                    }
+                                    // Process times in an order consistent with the partial order.
+                                    times.sort();
+                                    times.dedup();
+
+                                    // Collect the existing updates for the key.
+                                    let mut existing = Vec::new();
+                                    trace_cursor.seek_key(&trace_storage, &key);
+                                    if trace_cursor.get_key(&trace_storage) == Some(&key) {
+                                        while let Some(val) = trace_cursor.get_val(&trace_storage) {
+                                            trace_cursor.map_times(&trace_storage, |time, diff| existing.push((val.clone(), time.clone(), *diff)));
+                                            trace_cursor.step_val(&trace_storage);
+                                        }
+                                    }
+
+                                    let mut accumulation = Vec::new();
+                                    for time in times {
+                                        if upper.less_equal(&time) {
+                                            deferred.entry(key.clone()).or_insert_with(Vec::new).push(time);
+                                        }
+                                        else {
+                                            // Correct the accumulation at `time` to be the upserted value.
+                                            accumulation.extend(existing.iter().filter(|(_,t,_)| t.less_equal(&time)).map(|(v,_,d)| (v.clone(), *d)));
+                                            if let Some(value) = upsert_value(&list[..], &time) {
+                                                accumulation.push((value, -1));
+                                            }
+                                            consolidate(&mut accumulation);
+                                            for (val, diff) in accumulation.drain(..) {
+                                                existing.push((val.clone(), time.clone(), -diff));
+                                                updates.push((key.clone(), val, time.clone(), -diff));
+                                            }
+                                        }
+                                    }
+
+                                    // Must insert updates in (key, val, time) order.
+                                    updates.sort();
+                                    for update in updates.drain(..) {
+                                        builder.push(update);
+                                    }
+
+                                    compact_history(list, input.frontier().frontier());
+                                }
+                                let batch = builder.done(prev_frontier.clone(), upper.clone(), Antichain::from_elem(G::Timestamp::minimum()));
+                                prev_frontier.clone_from(&upper);
+
+                                // Communicate `batch` to the arrangement and the stream.
+                                writer.insert(batch.clone(), Some(capability.time().clone()));
+                                output.session(&capabilities.elements()[index]).give(batch);
+                            }
+                        }
+
+                        // Having extracted and sent batches between each capability and the input frontier,
+                        // we should downgrade all capabilities to match the lower envelope of times we may
+                        // yet need to produce, from both pending upserts and deferred re-evaluations.
+
+                        let mut new_capabilities = Antichain::new();
+                        let retained = pending.iter().map(|(time, _, _)| time).chain(deferred.values().flat_map(|times| times.iter()));
+                        for time in retained {
+                            if !new_capabilities.elements().iter().any(|c: &Capability<G::Timestamp>| c.time().less_equal(time)) {
+                                if let Some(capability) = capabilities.elements().iter().find(|c| c.time().less_equal(time)) {
+                                    new_capabilities.insert(capability.delayed(time));
+                                }
+                                else {
+                                    panic!("failed to find capability");
+                                }
+                            }
+                        }
+
+                        capabilities = new_capabilities;
+                    }
+                    else {
+                        // Announce progress updates, even without data.
+                        writer.seal(input.frontier().frontier().to_owned());
+                    }
+
+                    // Update our view of the input frontier.
+                    prev_frontier.clear();
+                    prev_frontier.extend(input.frontier().frontier().iter().cloned());
+
+                    // Downgrade capabilities for `reader_local`.
+                    reader_local.set_logical_compaction(prev_frontier.borrow());
+                    reader_local.set_physical_compaction(prev_frontier.borrow());
+                }
+
+                if let Some(mut fuel) = effort.clone() {
+                    writer.exert(&mut fuel);
+                }
+            }
+        })
+    };
+
+    Arranged { stream: stream, trace: reader.unwrap() }
+}
+
+/// The value determined by upserts `list` at `time`, if any.
+///
+/// Of the upserts at times less or equal to `time`, those not less than another are considered,
+/// and the greatest of their values is returned.
+fn upsert_value<T: PartialOrder, V: Ord+Clone>(list: &[(T, Option<V>)], time: &T) -> Option<V> {
+    let prior = list.iter().filter(|(t,_)| t.less_equal(time)).collect::<Vec<_>>();
+    prior
+        .iter()
+        .filter(|(t1,_)| !prior.iter().any(|(t2,_)| t1.less_than(t2)))
+        .map(|(_,v)| v)
+        .max()
+        .and_then(|v| v.clone())
+}
+
+/// Discards upserts that can no longer determine the value of their key beyond `frontier`.
+///
+/// An upsert less than another upsert, or at the same time with a lesser value, is redundant once
+/// both times advance to the same time by `frontier`, as the two will then be present for exactly
+/// the same future times.
+fn compact_history<T: Lattice+Clone, V: Ord>(list: &mut Vec<(T, Option<V>)>, frontier: AntichainRef<T>) {
+    let advanced = list.iter().map(|(time,_)| { let mut time = time.clone(); time.advance_by(frontier); time }).collect::<Vec<_>>();
+    let mut keep = Vec::with_capacity(list.len());
+    for index1 in 0 .. list.len() {
+        let (ref time1, ref val1) = list[index1];
+        let redundant = (0 .. list.len()).any(|index2| {
+            let (ref time2, ref val2) = list[index2];
+            index1 != index2 && advanced[index1] == advanced[index2] && (
+                time1.less_than(time2) ||
+                (time1 == time2 && (val1 < val2 || (val1 == val2 && index1 < index2)))
+            )
+        });
+        keep.push(!redundant);
+    }
+    let mut index = 0;
+    list.retain(|_| { index += 1; keep[index - 1] });
+}
@@ -54,7 +54,9 @@ where
     /// Exerts merge effort, even without additional updates.
     pub fn exert(&mut self, fuel: &mut isize) {
         if let Some(trace) = self.trace.upgrade() {
-            trace.borrow_mut().trace.exert(fuel);
+            let mut borrow = trace.borrow_mut();
+            borrow.trace.exert(fuel);
+            borrow.report_memory();
         }
     }
 
@@ -87,7 +89,10 @@ where
 
         // push data to the trace, if it still exists.
         if let Some(trace) = self.trace.upgrade() {
-            trace.borrow_mut().trace.insert(batch);
+            let mut borrow = trace.borrow_mut();
+            borrow.trace.insert(batch);
+            borrow.check_compaction_lag(self.upper.borrow());
+            borrow.report_memory();
         }
 
     }
@@ -17,7 +17,8 @@ pub trait Consolidate<D: ExchangeData+Hashable> : Sized {
     /// Aggregates the weights of equal records into at most one record.
     ///
     /// This method uses the type `D`'s `hashed()` method to partition the data. The data are
-    /// accumulated in place, each held back until their timestamp has completed.
+    /// accumulated in place, each held back until their timestamp has completed. The work is
+    /// performed by an arrangement, whose activations are instrumented as those of `arrange`.
     ///
     /// # Examples
     ///
@@ -109,15 +110,21 @@ where
         use collection::AsCollection;
 
         self.inner
-            .unary(Pipeline, "ConsolidateStream", |_cap, _info| {
+            .unary(Pipeline, "ConsolidateStream", |_cap, info| {
+
+                let mut activations = ::logging::ActivationLogger::new(&self.scope(), info.global_id, "ConsolidateStream");
 
                 let mut vector = Vec::new();
                 move |input, output| {
+                    activations.start();
                     input.for_each(|time, data| {
                         data.swap(&mut vector);
+                        activations.input(vector.len());
                         crate::consolidation::consolidate_updates(&mut vector);
+                        activations.output(vector.len());
                         output.session(&time).give_vec(&mut vector);
-                    })
+                    });
+                    activations.finish();
                 }
             })
             .as_collection()
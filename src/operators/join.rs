@@ -421,6 +421,9 @@ impl<G, T1> JoinCore<G, T1::Key, T1::Val, T1::R> for Arranged<G,T1>
             let activations = self.stream.scope().activations().clone();
             let activator = Activator::new(&info.address[..], activations);
 
+            // Records the work of each activation, if instrumentation is enabled.
+            let mut instrument = ::logging::ActivationLogger::new(&self.stream.scope(), info.global_id, "Join");
+
             // Our initial invariants are that for each trace, physical compaction is less or equal the trace's upper bound.
             // These invariants ensure that we can reference observed batch frontiers from `_start_upper` onward, as long as
             // we maintain our physical compaction capabilities appropriately. These assertions are tested as we load up the
@@ -488,6 +491,8 @@ impl<G, T1> JoinCore<G, T1::Key, T1::Val, T1::R> for Arranged<G,T1>
 
             move |input1, input2, output| {
 
+                instrument.start();
+
                 // 1. Consuming input.
                 //
                 // The join computation repeatedly accepts batches of updates from each of its inputs.
@@ -508,6 +513,7 @@ impl<G, T1> JoinCore<G, T1::Key, T1::Val, T1::R> for Arranged<G,T1>
                         let capability = capability.retain();
                         data.swap(&mut input1_buffer);
                         for batch1 in input1_buffer.drain(..) {
+                            instrument.input(batch1.len());
                             // Ignore any pre-loaded data.
                             if PartialOrder::less_equal(&acknowledged1, &batch1.lower()) {
                                 if !batch1.is_empty() {
@@ -536,6 +542,7 @@ impl<G, T1> JoinCore<G, T1::Key, T1::Val, T1::R> for Arranged<G,T1>
                         let capability = capability.retain();
                         data.swap(&mut input2_buffer);
                         for batch2 in input2_buffer.drain(..) {
+                            instrument.input(batch2.len());
                             // Ignore any pre-loaded data.
                             if PartialOrder::less_equal(&acknowledged2, &batch2.lower()) {
                                 if !batch2.is_empty() {
@@ -578,22 +585,24 @@ impl<G, T1> JoinCore<G, T1::Key, T1::Val, T1::R> for Arranged<G,T1>
                 // Perform some amount of outstanding work.
                 let mut fuel = 1_000_000;
                 while !todo1.is_empty() && fuel > 0 {
-                    todo1.front_mut().unwrap().work(
+                    let produced = todo1.front_mut().unwrap().work(
                         output,
                         |k,v2,v1,t,r2,r1| result(k,v1,v2,t,r1,r2),
                         &mut fuel
                     );
+                    instrument.output(produced);
                     if !todo1.front().unwrap().work_remains() { todo1.pop_front(); }
                 }
 
                 // Perform some amount of outstanding work.
                 let mut fuel = 1_000_000;
                 while !todo2.is_empty() && fuel > 0 {
-                    todo2.front_mut().unwrap().work(
+                    let produced = todo2.front_mut().unwrap().work(
                         output,
                         |k,v1,v2,t,r1,r2| result(k,v1,v2,t,r1,r2),
                         &mut fuel
                     );
+                    instrument.output(produced);
                     if !todo2.front().unwrap().work_remains() { todo2.pop_front(); }
                 }
 
@@ -640,6 +649,8 @@ impl<G, T1> JoinCore<G, T1::Key, T1::Val, T1::R> for Arranged<G,T1>
                         trace2.set_physical_compaction(acknowledged2.borrow());
                     }
                 }
+
+                instrument.finish();
             }
         })
         .as_collection()
@@ -704,8 +715,10 @@ where
     }
 
     /// Process keys until at least `fuel` output tuples produced, or the work is exhausted.
+    ///
+    /// Returns the number of output tuples produced.
     #[inline(never)]
-    fn work<L, I>(&mut self, output: &mut OutputHandle<T, (D, T, R3), Tee<T, (D, T, R3)>>, mut logic: L, fuel: &mut usize)
+    fn work<L, I>(&mut self, output: &mut OutputHandle<T, (D, T, R3), Tee<T, (D, T, R3)>>, mut logic: L, fuel: &mut usize) -> usize
     where I: IntoIterator<Item=(D, T, R3)>, L: FnMut(&K, &V1, &V2, &T, &R1, &R2)->I {
 
         let meet = self.capability.time();
@@ -767,6 +780,8 @@ where
 
         if effort > *fuel { *fuel = 0; }
         else              { *fuel -= effort; }
+
+        effort
     }
 }
 
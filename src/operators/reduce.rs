@@ -364,6 +364,8 @@ where
                     (None, None)
                 };
 
+                let mut activations = ::logging::ActivationLogger::new(&self.stream.scope(), operator_info.global_id, "Reduce");
+
                 let empty = T2::new(operator_info.clone(), logger.clone(), activator);
                 let mut source_trace = self.trace.clone();
 
@@ -398,6 +400,8 @@ where
 
                 move |input, output| {
 
+                    activations.start();
+
                     // The `reduce` operator receives fully formed batches, which each serve as an indication
                     // that the frontier has advanced to the upper bound of their description.
                     //
@@ -430,6 +434,7 @@ where
 
                         batches.swap(&mut input_buffer);
                         for batch in input_buffer.drain(..) {
+                            activations.input(batch.len());
                             upper_limit.clone_from(batch.upper());
                             batch_cursors.push(batch.cursor());
                             batch_storage.push(batch);
@@ -582,6 +587,7 @@ where
                                     let batch = builder.done(output_lower.clone(), output_upper.clone(), Antichain::from_elem(G::Timestamp::minimum()));
 
                                     // ship batch to the output, and commit to the output trace.
+                                    activations.output(batch.len());
                                     output.session(&capabilities[index]).give(batch.clone());
                                     output_writer.insert(batch, Some(capabilities[index].time().clone()));
 
@@ -635,6 +641,8 @@ where
                     if let Some(mut fuel) = effort.clone() {
                         output_writer.exert(&mut fuel);
                     }
+
+                    activations.finish();
                 }
             }
         )
@@ -120,18 +120,24 @@ where
         let mut trace = self.trace.clone();
         let mut buffer = Vec::new();
 
-        self.stream.unary_frontier(Pipeline, "ThresholdTotal", move |_,_| {
+        self.stream.unary_frontier(Pipeline, "ThresholdTotal", move |_, info| {
 
             // tracks the upper limit of known-complete timestamps.
             let mut upper_limit = timely::progress::frontier::Antichain::from_elem(<G::Timestamp as timely::progress::Timestamp>::minimum());
 
+            let mut activations = ::logging::ActivationLogger::new(&self.stream.scope(), info.global_id, "ThresholdTotal");
+
             move |input, output| {
 
+                activations.start();
+
                 input.for_each(|capability, batches| {
                     batches.swap(&mut buffer);
                     let mut session = output.session(&capability);
                     for batch in buffer.drain(..) {
 
+                        activations.input(batch.len());
+
                         let mut batch_cursor = batch.cursor();
                         let (mut trace_cursor, trace_storage) = trace.cursor_through(batch.lower().borrow()).unwrap();
 
@@ -174,6 +180,7 @@ where
 
                                 if let Some(difference) = difference {
                                     if !difference.is_zero() {
+                                        activations.output(1);
                                         session.give((key.clone(), time.clone(), difference));
                                     }
                                 }
@@ -188,6 +195,8 @@ where
                 trace.advance_upper(&mut upper_limit);
                 trace.set_logical_compaction(upper_limit.borrow());
                 trace.set_physical_compaction(upper_limit.borrow());
+
+                activations.finish();
             }
         })
         .as_collection()
@@ -9,6 +9,7 @@
 //! and should consume fewer resources (computation and memory) when it applies.
 
 use std::rc::Rc;
+use std::sync::Arc;
 use std::convert::{TryFrom, TryInto};
 use std::marker::PhantomData;
 use std::fmt::Debug;
@@ -23,7 +24,7 @@ use trace::layers::Builder as TrieBuilder;
 use trace::layers::Cursor as TrieCursor;
 use trace::layers::ordered::{OrdOffset, OrderedLayer, OrderedBuilder, OrderedCursor};
 use trace::layers::ordered_leaf::{OrderedLeaf, OrderedLeafBuilder};
-use trace::{Batch, BatchReader, Builder, Merger, Cursor};
+use trace::{Batch, BatchReader, Builder, Merger, Cursor, HeapSize};
 use trace::description::Description;
 
 use trace::layers::MergeBuilder;
@@ -40,12 +41,18 @@ pub type OrdValSpine<K, V, T, R, O=usize> = Spine<K, V, T, R, Rc<OrdValBatch<K,
 /// A trace implementation using a spine of abomonated ordered lists.
 pub type OrdValSpineAbom<K, V, T, R, O=usize> = Spine<K, V, T, R, Rc<Abomonated<OrdValBatch<K, V, T, R, O>, Vec<u8>>>>;
 
+/// A trace implementation using a spine of ordered lists, whose batches may be shared between threads.
+pub type OrdValSpineArc<K, V, T, R, O=usize> = Spine<K, V, T, R, Arc<OrdValBatch<K, V, T, R, O>>>;
+
 /// A trace implementation for empty values using a spine of ordered lists.
 pub type OrdKeySpine<K, T, R, O=usize> = Spine<K, (), T, R, Rc<OrdKeyBatch<K, T, R, O>>>;
 
 /// A trace implementation for empty values using a spine of abomonated ordered lists.
 pub type OrdKeySpineAbom<K, T, R, O=usize> = Spine<K, (), T, R, Rc<Abomonated<OrdKeyBatch<K, T, R, O>, Vec<u8>>>>;
 
+/// A trace implementation for empty values using a spine of ordered lists, whose batches may be shared between threads.
+pub type OrdKeySpineArc<K, T, R, O=usize> = Spine<K, (), T, R, Arc<OrdKeyBatch<K, T, R, O>>>;
+
 
 /// An immutable collection of update tuples, from a contiguous interval of logical times.
 #[derive(Debug, Abomonation)]
@@ -74,6 +81,7 @@ where
     fn cursor(&self) -> Self::Cursor { OrdValCursor { cursor: self.layer.cursor() } }
     fn len(&self) -> usize { <OrderedLayer<K, OrderedLayer<V, OrderedLeaf<T, R>, O>, O> as Trie>::tuples(&self.layer) }
     fn description(&self) -> &Description<T> { &self.desc }
+    fn heap_size(&self) -> HeapSize { self.layer.heap_size() }
 }
 
 impl<K, V, T, R, O> Batch<K, V, T, R> for OrdValBatch<K, V, T, R, O>
@@ -427,6 +435,7 @@ where
     }
     fn len(&self) -> usize { <OrderedLayer<K, OrderedLeaf<T, R>, O> as Trie>::tuples(&self.layer) }
     fn description(&self) -> &Description<T> { &self.desc }
+    fn heap_size(&self) -> HeapSize { self.layer.heap_size() }
 }
 
 impl<K, T, R, O> Batch<K, (), T, R> for OrdKeyBatch<K, T, R, O>
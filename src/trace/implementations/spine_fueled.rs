@@ -67,6 +67,15 @@
 //! do this, we should make sure that we correctly account for completed merges at low layers: they
 //! should still extract fuel from new updates even though they have completed, at least until they
 //! have paid back any "debt" to higher layers by continuing to provide fuel as updates arrive.
+//!
+//! ## Merge policies
+//!
+//! The amount of fuel applied to merges, and whether to eagerly fold all batches together, are
+//! determined by a `MergePolicy`. The default `TieredMergePolicy` is the behavior described above,
+//! in which fuel is proportional to the introduced records scaled by an effort multiplier. The
+//! `EagerMergePolicy` completes each merge as soon as it is initiated, and `BoundedMergePolicy`
+//! merges the smallest batches whenever there would otherwise be more than some number of them,
+//! which trades additional work on insertion for fewer batches to consult on each read.
 
 
 use std::fmt::Debug;
@@ -74,7 +83,8 @@ use std::fmt::Debug;
 use ::logging::Logger;
 use ::difference::Semigroup;
 use lattice::Lattice;
-use trace::{Batch, BatchReader, Trace, TraceReader};
+use trace::{Batch, BatchReader, Trace, TraceReader, HeapSize};
+use trace::{BatchIntrospection, LayerIntrospection};
 use trace::cursor::{Cursor, CursorList};
 use trace::Merger;
 
@@ -96,7 +106,7 @@ pub struct Spine<K, V, T: Lattice+Ord, R: Semigroup, B: Batch<K, V, T, R>> {
     merging: Vec<MergeState<K,V,T,R,B>>,// Several possibly shared collections of updates.
     pending: Vec<B>,                       // Batches at times in advance of `frontier`.
     upper: Antichain<T>,
-    effort: usize,
+    policy: Box<dyn MergePolicy>,
     activator: Option<timely::scheduling::activate::Activator>,
 }
 
@@ -225,18 +235,33 @@ where
     }
     fn get_physical_compaction(&mut self) -> AntichainRef<T> { self.physical_frontier.borrow() }
 
-    fn map_batches<F: FnMut(&Self::Batch)>(&self, mut f: F) {
-        for batch in self.merging.iter().rev() {
-            match batch {
-                MergeState::Double(MergeVariant::InProgress(batch1, batch2, _)) => { f(batch1); f(batch2); },
-                MergeState::Double(MergeVariant::Complete(Some((batch, _)))) => { f(batch) },
-                MergeState::Single(Some(batch)) => { f(batch) },
-                _ => { },
-            }
+    fn map_batches<F: FnMut(&Self::Batch)>(&self, f: F) {
+        self.map_batches_internal(f)
+    }
+
+    fn layers(&self) -> Vec<LayerIntrospection<T>> {
+        let mut layers = Vec::new();
+        for (level, merge_state) in self.merging.iter().enumerate().rev() {
+            let (merging, batches) = match merge_state {
+                MergeState::Double(MergeVariant::InProgress(batch1, batch2, _)) => (true, vec![batch1, batch2]),
+                MergeState::Double(MergeVariant::Complete(Some((batch, _)))) => (false, vec![batch]),
+                MergeState::Single(Some(batch)) => (false, vec![batch]),
+                _ => continue,
+            };
+            layers.push(LayerIntrospection {
+                level: Some(level),
+                merging,
+                batches: batches.into_iter().map(|b| BatchIntrospection::from_batch::<K, V, R, B>(b)).collect(),
+            });
         }
         for batch in self.pending.iter() {
-            f(batch);
+            layers.push(LayerIntrospection {
+                level: None,
+                merging: false,
+                batches: vec![BatchIntrospection::from_batch::<K, V, R, B>(batch)],
+            });
         }
+        layers
     }
 }
 
@@ -295,6 +320,7 @@ where
             operator: self.operator.global_id,
             length: batch.len()
         }));
+        self.log_size(batch.heap_size(), HeapSize::default());
 
         assert!(batch.lower() != batch.upper());
         assert_eq!(batch.lower(), &self.upper);
@@ -339,6 +365,7 @@ where
     /// Drops and logs batches. Used in `set_logical_compaction` and drop.
     fn drop_batches(&mut self) {
         if let Some(logger) = &self.logger {
+            let mut size = HeapSize::default();
             for batch in self.merging.drain(..) {
                 match batch {
                     MergeState::Single(Some(batch)) => {
@@ -346,6 +373,7 @@ where
                             operator: self.operator.global_id,
                             length: batch.len(),
                         });
+                        size += batch.heap_size();
                     },
                     MergeState::Double(MergeVariant::InProgress(batch1, batch2, _)) => {
                         logger.log(::logging::DropEvent {
@@ -356,12 +384,15 @@ where
                             operator: self.operator.global_id,
                             length: batch2.len(),
                         });
+                        size += batch1.heap_size();
+                        size += batch2.heap_size();
                     },
                     MergeState::Double(MergeVariant::Complete(Some((batch, _)))) => {
                         logger.log(::logging::DropEvent {
                             operator: self.operator.global_id,
                             length: batch.len(),
                         });
+                        size += batch.heap_size();
                     }
                     _ => { },
                 }
@@ -371,7 +402,20 @@ where
                     operator: self.operator.global_id,
                     length: batch.len(),
                 });
+                size += batch.heap_size();
             }
+            self.log_size(HeapSize::default(), size);
+        }
+    }
+
+    /// Logs a change in the memory held by the trace, from `removed` to `added`.
+    fn log_size(&self, added: HeapSize, removed: HeapSize) {
+        if added != removed {
+            self.logger.as_ref().map(|l| l.log(::logging::SizeEvent {
+                operator: self.operator.global_id,
+                allocated: added.allocated as isize - removed.allocated as isize,
+                used: added.used as isize - removed.used as isize,
+            }));
         }
     }
 }
@@ -420,15 +464,21 @@ where
     /// of the batch's length in effort to each merge. The `effort` parameter is that multiplier.
     /// This value should be at least one for the merging to happen; a value of zero is not helpful.
     pub fn with_effort(
-        mut effort: usize,
+        effort: usize,
         operator: OperatorInfo,
         logger: Option<::logging::Logger>,
         activator: Option<timely::scheduling::activate::Activator>,
     ) -> Self {
+        Self::with_policy(Box::new(TieredMergePolicy::with_effort(effort)), operator, logger, activator)
+    }
 
-        // Zero effort is .. not smart.
-        if effort == 0 { effort = 1; }
-
+    /// Allocates a fueled `Spine` whose maintenance is directed by `policy`.
+    pub fn with_policy(
+        policy: Box<dyn MergePolicy>,
+        operator: OperatorInfo,
+        logger: Option<::logging::Logger>,
+        activator: Option<timely::scheduling::activate::Activator>,
+    ) -> Self {
         Spine {
             operator,
             logger,
@@ -438,11 +488,41 @@ where
             merging: Vec::new(),
             pending: Vec::new(),
             upper: Antichain::from_elem(<T as timely::progress::Timestamp>::minimum()),
-            effort,
+            policy,
             activator,
         }
     }
 
+    /// Replaces the policy directing the maintenance of the spine.
+    ///
+    /// The new policy applies to subsequently introduced batches; merges already in progress
+    /// are not interrupted.
+    pub fn set_merge_policy(&mut self, policy: Box<dyn MergePolicy>) {
+        self.policy = policy;
+    }
+
+    /// The number of non-empty batches held by the spine, including pending batches.
+    pub fn batches(&self) -> usize {
+        let mut count = 0;
+        self.map_batches_internal(|batch| if !batch.is_empty() { count += 1; });
+        count
+    }
+
+    /// Applies `logic` to each batch in the spine, merging or otherwise.
+    fn map_batches_internal<F: FnMut(&B)>(&self, mut logic: F) {
+        for batch in self.merging.iter().rev() {
+            match batch {
+                MergeState::Double(MergeVariant::InProgress(batch1, batch2, _)) => { logic(batch1); logic(batch2); },
+                MergeState::Double(MergeVariant::Complete(Some((batch, _)))) => { logic(batch) },
+                MergeState::Single(Some(batch)) => { logic(batch) },
+                _ => { },
+            }
+        }
+        for batch in self.pending.iter() {
+            logic(batch);
+        }
+    }
+
     /// Migrate data from `self.pending` into `self.merging`.
     ///
     /// This method reflects on the bookmarks held by others that may prevent merging, and in the
@@ -506,28 +586,15 @@ where
         //          The fuel use policy is negotiable, in that we might aim
         //          to use relatively less when we can, so that we return
         //          control promptly, or we might account more work to larger
-        //          batches. Not clear to me which are best, of if there
-        //          should be a configuration knob controlling this.
-
-        // The amount of fuel to use is proportional to 2^batch_index, scaled
-        // by a factor of self.effort which determines how eager we are in
-        // performing maintenance work. We need to ensure that each merge in
-        // progress receives fuel for each introduced batch, and so multiply
-        // by that as well.
+        //          batches. This choice is made by `self.policy`.
+
+        // By default, the amount of fuel to use is proportional to 2^batch_index,
+        // scaled by an effort multiplier which determines how eager we are in
+        // performing maintenance work.
         if batch_index > 32 { println!("Large batch index: {}", batch_index); }
 
-        // We believe that eight units of fuel is sufficient for each introduced
-        // record, accounted as four for each record, and a potential four more
-        // for each virtual record associated with promoting existing smaller
-        // batches. We could try and make this be less, or be scaled to merges
-        // based on their deficit at time of instantiation. For now, we remain
-        // conservative.
-        let mut fuel = 8 << batch_index;
-        // Scale up by the effort parameter, which is calibrated to one as the
-        // minimum amount of effort.
-        fuel *= self.effort;
-        // Convert to an `isize` so we can observe any fuel shortfall.
-        let mut fuel = fuel as isize;
+        // See `TieredMergePolicy` for the reasoning behind the default amount.
+        let mut fuel = self.policy.fuel(batch_index);
 
         // Step 1.  Apply fuel to each in-progress merge.
         //
@@ -566,6 +633,19 @@ where
         //         as their ascension is what ensures the merging and
         //         eventual compaction of the largest layers.
         self.tidy_layers();
+
+        // Step 5. Consult the policy about whether there are too many batches, and if so
+        //         fold the smallest layers into the next larger layer, one layer at a time,
+        //         until there are few enough. Larger layers are only rewritten when the
+        //         smaller layers alone do not bring the number of batches under the bound.
+        let mut level = 1;
+        while level <= self.merging.len() && self.policy.too_many_batches(self.merging.iter().map(|m| m.batches()).sum()) {
+            self.roll_up(level);
+            level += 1;
+        }
+        if level > 1 {
+            self.tidy_layers();
+        }
     }
 
     /// Ensures that an insertion at layer `index` will succeed.
@@ -588,23 +668,35 @@ where
             // Collect and merge all batches at layers up to but not including `index`.
             let mut merged = None;
             for i in 0 .. index {
-                self.insert_at(merged, i);
+                self.insert_completing(merged, i);
                 merged = self.complete_at(i);
             }
 
             // The merged results should be introduced at level `index`, which should
             // be ready to absorb them (possibly creating a new merge at the time).
-            self.insert_at(merged, index);
+            self.insert_completing(merged, index);
 
             // If the insertion results in a merge, we should complete it to ensure
             // the upcoming insertion at `index` does not panic.
             if self.merging[index].is_double() {
                 let merged = self.complete_at(index);
-                self.insert_at(merged, index + 1);
+                self.insert_completing(merged, index + 1);
             }
         }
     }
 
+    /// Inserts a batch at `index`, first completing any merge in progress at that layer.
+    ///
+    /// Our fueling discipline should ensure that merges below the layer a batch is introduced
+    /// at have completed, but merges at other layers may be in progress when they are rolled up.
+    fn insert_completing(&mut self, batch: Option<B>, index: usize) {
+        if index < self.merging.len() && self.merging[index].is_double() {
+            let complete = self.complete_at(index);
+            self.merging[index] = MergeState::Single(complete);
+        }
+        self.insert_at(batch, index);
+    }
+
     /// Applies an amount of fuel to merges in progress.
     ///
     /// The supplied `fuel` is for each in progress merge, and if we want to spend
@@ -688,6 +780,7 @@ where
                         complete: Some(merged.len()),
                     }
                 ));
+                self.log_size(merged.heap_size(), input1.heap_size() + input2.heap_size());
             }
             Some(merged)
         }
@@ -761,6 +854,92 @@ where
     }
 }
 
+/// Directs the maintenance work performed by a `Spine`.
+///
+/// A spine consults its policy each time a batch is introduced into its layers, both to determine
+/// how much fuel to apply to merges in progress and whether to immediately merge small batches.
+/// Policies only influence the physical layout of the spine, and never the updates it presents.
+pub trait MergePolicy {
+    /// The fuel to apply to each merge in progress when a batch is introduced at `level`.
+    ///
+    /// A batch at `level` is accounted as `2^level` updates. Policies that provide less than
+    /// eight units of fuel for each update risk merges failing to complete before they are
+    /// needed, at which point they are completed forcibly.
+    fn fuel(&self, level: usize) -> isize;
+    /// Indicates that there are too many batches, given the number of non-empty batches.
+    ///
+    /// While this holds the spine merges its smallest layers into larger layers, and so
+    /// policies should accept some number of batches no less than one.
+    fn too_many_batches(&self, _batches: usize) -> bool { false }
+}
+
+/// The default merge policy, tiering batches by size and fueling merges in proportion to updates.
+#[derive(Clone, Debug)]
+pub struct TieredMergePolicy {
+    effort: usize,
+}
+
+impl TieredMergePolicy {
+    /// Creates a tiered policy with a specified effort multiplier.
+    ///
+    /// The multiplier should be at least one; a value of zero is treated as one.
+    pub fn with_effort(effort: usize) -> Self {
+        TieredMergePolicy { effort: if effort == 0 { 1 } else { effort } }
+    }
+}
+
+impl Default for TieredMergePolicy {
+    fn default() -> Self { Self::with_effort(1) }
+}
+
+impl MergePolicy for TieredMergePolicy {
+    fn fuel(&self, level: usize) -> isize {
+        // We believe that eight units of fuel is sufficient for each introduced
+        // record, accounted as four for each record, and a potential four more
+        // for each virtual record associated with promoting existing smaller
+        // batches. We could try and make this be less, or be scaled to merges
+        // based on their deficit at time of instantiation. For now, we remain
+        // conservative. We scale up by the effort parameter, which is calibrated
+        // to one as the minimum amount of effort.
+        ((8 << level) * self.effort) as isize
+    }
+}
+
+/// A merge policy that completes all merges in progress whenever a batch is introduced.
+///
+/// This keeps the fewest batches the tiered layout permits, at the cost of
+/// unpredictable latency when large merges are performed.
+#[derive(Clone, Debug, Default)]
+pub struct EagerMergePolicy;
+
+impl MergePolicy for EagerMergePolicy {
+    fn fuel(&self, _level: usize) -> isize { isize::max_value() }
+}
+
+/// A merge policy that merges the smallest batches whenever there are more than a bound.
+///
+/// Fuel is applied as in `TieredMergePolicy`, and layers are merged from the smallest upward
+/// only until the bound is met, so that large batches are rarely rewritten.
+#[derive(Clone, Debug)]
+pub struct BoundedMergePolicy {
+    tiered: TieredMergePolicy,
+    max_batches: usize,
+}
+
+impl BoundedMergePolicy {
+    /// Creates a policy retaining at most `max_batches` merged batches.
+    ///
+    /// Batches that are not yet eligible for merging, as they are beyond the physical
+    /// compaction frontier, are not counted against the bound.
+    pub fn new(max_batches: usize) -> Self {
+        BoundedMergePolicy { tiered: TieredMergePolicy::default(), max_batches }
+    }
+}
+
+impl MergePolicy for BoundedMergePolicy {
+    fn fuel(&self, level: usize) -> isize { self.tiered.fuel(level) }
+    fn too_many_batches(&self, batches: usize) -> bool { batches > self.max_batches }
+}
 
 /// Describes the state of a layer.
 ///
@@ -790,6 +969,16 @@ impl<K, V, T: Eq, R, B: Batch<K, V, T, R>> MergeState<K, V, T, R, B> {
         }
     }
 
+    /// The number of non-empty batches contained in the level.
+    fn batches(&self) -> usize {
+        match self {
+            MergeState::Single(Some(b)) => if b.is_empty() { 0 } else { 1 },
+            MergeState::Double(MergeVariant::InProgress(b1,b2,_)) => (if b1.is_empty() { 0 } else { 1 }) + (if b2.is_empty() { 0 } else { 1 }),
+            MergeState::Double(MergeVariant::Complete(Some((b, _)))) => if b.is_empty() { 0 } else { 1 },
+            _ => 0,
+        }
+    }
+
     /// True only for the MergeState::Vacant variant.
     fn is_vacant(&self) -> bool {
         if let MergeState::Vacant = self { true } else { false }
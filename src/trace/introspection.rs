@@ -0,0 +1,68 @@
+//! Descriptions of the internal state of traces, for diagnosing their behavior.
+//!
+//! Traces shared through a `TraceAgent` can only compact their representations as the holders of
+//! the trace permit, and a single holder that fails to advance its compaction frontiers prevents
+//! all compaction. The types in this module report the holders of a trace with their frontiers,
+//! as well as the batches of the trace and the layers in which they are maintained.
+
+use timely::progress::Antichain;
+
+use trace::{BatchReader, Description};
+
+/// A description of a batch in a trace.
+#[derive(Clone, Debug)]
+pub struct BatchIntrospection<T> {
+    /// The lower, upper, and since frontiers of the batch.
+    pub description: Description<T>,
+    /// The number of updates in the batch.
+    pub len: usize,
+}
+
+impl<T: Clone> BatchIntrospection<T> {
+    /// Describes `batch`.
+    pub fn from_batch<K, V, R, B: BatchReader<K, V, T, R>>(batch: &B) -> Self {
+        BatchIntrospection {
+            description: batch.description().clone(),
+            len: batch.len(),
+        }
+    }
+}
+
+/// A description of a layer of batches in a trace.
+#[derive(Clone, Debug)]
+pub struct LayerIntrospection<T> {
+    /// The level of the layer, if the trace organizes batches by level.
+    ///
+    /// Batches that are not yet eligible for merging are reported with no level.
+    pub level: Option<usize>,
+    /// True if the batches of the layer are in the process of merging.
+    pub merging: bool,
+    /// The batches of the layer.
+    pub batches: Vec<BatchIntrospection<T>>,
+}
+
+/// A description of a handle to a shared trace.
+#[derive(Clone, Debug)]
+pub struct HolderIntrospection<T> {
+    /// An identifier for the handle, unique among handles to the trace.
+    pub id: usize,
+    /// A label describing the handle.
+    pub label: String,
+    /// The logical compaction frontier the handle requires.
+    pub logical_compaction: Antichain<T>,
+    /// The physical compaction frontier the handle requires.
+    pub physical_compaction: Antichain<T>,
+}
+
+/// A description of a shared trace.
+#[derive(Clone, Debug)]
+pub struct TraceIntrospection<T> {
+    /// The live handles to the trace.
+    pub holders: Vec<HolderIntrospection<T>>,
+    /// The joint logical compaction frontier of all holders.
+    pub logical_compaction: Antichain<T>,
+    /// The joint physical compaction frontier of all holders.
+    pub physical_compaction: Antichain<T>,
+    /// The layers of batches in the trace.
+    pub layers: Vec<LayerIntrospection<T>>,
+}
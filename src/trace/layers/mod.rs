@@ -4,6 +4,8 @@
 //! in the next layer. Similarly, ranges of elements in the layer itself may correspond
 //! to single elements in the layer above.
 
+use trace::HeapSize;
+
 pub mod ordered;
 pub mod ordered_leaf;
 // pub mod hashed;
@@ -29,6 +31,10 @@ pub trait Trie  : ::std::marker::Sized {
     fn keys(&self) -> usize;
     /// The total number of tuples in the collection.
     fn tuples(&self) -> usize;
+    /// The heap memory backing the collection.
+    ///
+    /// Implementations that cannot determine their size may report nothing.
+    fn heap_size(&self) -> HeapSize { HeapSize::default() }
     /// Returns a cursor capable of navigating the collection.
     fn cursor(&self) -> Self::Cursor { self.cursor_from(0, self.keys()) }
     /// Returns a cursor over a range of data, commonly used by others to restrict navigation to
@@ -1,6 +1,7 @@
 //! Implementation using ordered keys and exponential search.
 
 use super::{Trie, Cursor, Builder, MergeBuilder, TupleBuilder, advance};
+use trace::HeapSize;
 use std::convert::{TryFrom, TryInto};
 use std::fmt::Debug;
 use std::ops::{Sub,Add};
@@ -49,6 +50,7 @@ where
 
     fn keys(&self) -> usize { self.keys.len() }
     fn tuples(&self) -> usize { self.vals.tuples() }
+    fn heap_size(&self) -> HeapSize { HeapSize::of_vec(&self.keys) + HeapSize::of_vec(&self.offs) + self.vals.heap_size() }
     fn cursor_from(&self, lower: usize, upper: usize) -> Self::Cursor {
 
         if lower < upper {
@@ -3,6 +3,7 @@
 use ::difference::Semigroup;
 
 use super::{Trie, Cursor, Builder, MergeBuilder, TupleBuilder, advance};
+use trace::HeapSize;
 
 /// A layer of unordered values.
 #[derive(Debug, Eq, PartialEq, Clone, Abomonation)]
@@ -18,6 +19,7 @@ impl<K: Ord+Clone, R: Semigroup+Clone> Trie for OrderedLeaf<K, R> {
     type TupleBuilder = OrderedLeafBuilder<K, R>;
     fn keys(&self) -> usize { self.vals.len() }
     fn tuples(&self) -> usize { <OrderedLeaf<K, R> as Trie>::keys(&self) }
+    fn heap_size(&self) -> HeapSize { HeapSize::of_vec(&self.vals) }
     fn cursor_from(&self, lower: usize, upper: usize) -> Self::Cursor {
         OrderedLeafCursor {
             bounds: (lower, upper),
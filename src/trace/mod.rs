@@ -9,8 +9,10 @@
 
 pub mod cursor;
 pub mod description;
+pub mod introspection;
 pub mod implementations;
 pub mod layers;
+pub mod size;
 pub mod wrappers;
 
 use timely::progress::{Antichain, frontier::AntichainRef};
@@ -19,6 +21,8 @@ use timely::progress::Timestamp;
 // use ::difference::Semigroup;
 pub use self::cursor::Cursor;
 pub use self::description::Description;
+pub use self::size::HeapSize;
+pub use self::introspection::{BatchIntrospection, LayerIntrospection};
 
 //     The traces and batch and cursors want the flexibility to appear as if they manage certain types of keys and
 //     values and such, while perhaps using other representations, I'm thinking mostly of wrappers around the keys
@@ -187,6 +191,31 @@ pub trait TraceReader {
         });
     }
 
+    /// Describes the batches of the trace, grouped into the layers in which the trace maintains them.
+    ///
+    /// Traces without a layered structure report each batch as a layer without a level.
+    fn layers(&self) -> Vec<LayerIntrospection<Self::Time>>
+    where
+        Self::Time: Clone,
+    {
+        let mut layers = Vec::new();
+        self.map_batches(|batch| layers.push(LayerIntrospection {
+            level: None,
+            merging: false,
+            batches: vec![BatchIntrospection::from_batch::<Self::Key, Self::Val, Self::R, _>(batch)],
+        }));
+        layers
+    }
+
+    /// Reports the heap memory held by the batches of the trace.
+    ///
+    /// Batches shared with other traces are counted in full by each of them.
+    fn heap_size(&self) -> HeapSize {
+        let mut size = HeapSize::default();
+        self.map_batches(|batch| size += batch.heap_size());
+        size
+    }
+
 }
 
 /// An append-only collection of `(key, val, time, diff)` tuples.
@@ -246,6 +275,10 @@ where
     fn is_empty(&self) -> bool { self.len() == 0 }
     /// Describes the times of the updates in the batch.
     fn description(&self) -> &Description<T>;
+    /// Reports the heap memory held by the batch.
+    ///
+    /// Implementations that cannot determine their size may report nothing.
+    fn heap_size(&self) -> HeapSize { HeapSize::default() }
 
     /// All times in the batch are greater or equal to an element of `lower`.
     fn lower(&self) -> &Antichain<T> { self.description().lower() }
@@ -329,7 +362,7 @@ pub mod rc_blanket_impls {
     use std::rc::Rc;
 
     use timely::progress::{Antichain, frontier::AntichainRef};
-    use super::{Batch, BatchReader, Batcher, Builder, Merger, Cursor, Description};
+    use super::{Batch, BatchReader, Batcher, Builder, Merger, Cursor, Description, HeapSize};
 
     impl<K, V, T, R, B: BatchReader<K,V,T,R>> BatchReader<K,V,T,R> for Rc<B> {
 
@@ -344,6 +377,8 @@ pub mod rc_blanket_impls {
         fn len(&self) -> usize { (&**self).len() }
         /// Describes the times of the updates in the batch.
         fn description(&self) -> &Description<T> { (&**self).description() }
+        /// Reports the heap memory held by the batch.
+        fn heap_size(&self) -> HeapSize { (&**self).heap_size() }
     }
 
     /// Wrapper to provide cursor to nested scope.
@@ -427,6 +462,115 @@ pub mod rc_blanket_impls {
 }
 
 
+/// Blanket implementations for atomically reference counted batches.
+///
+/// Batches behind an `Arc` may be shared between threads, for example by traces shared among the
+/// workers of a process.
+pub mod arc_blanket_impls {
+
+    use std::sync::Arc;
+
+    use timely::progress::{Antichain, frontier::AntichainRef};
+    use super::{Batch, BatchReader, Batcher, Builder, Merger, Cursor, Description, HeapSize};
+
+    impl<K, V, T, R, B: BatchReader<K,V,T,R>> BatchReader<K,V,T,R> for Arc<B> {
+
+        /// The type used to enumerate the batch's contents.
+        type Cursor = ArcBatchCursor<K, V, T, R, B>;
+        /// Acquires a cursor to the batch's contents.
+        fn cursor(&self) -> Self::Cursor {
+            ArcBatchCursor::new((&**self).cursor())
+        }
+
+        /// The number of updates in the batch.
+        fn len(&self) -> usize { (&**self).len() }
+        /// Describes the times of the updates in the batch.
+        fn description(&self) -> &Description<T> { (&**self).description() }
+        /// Reports the heap memory held by the batch.
+        fn heap_size(&self) -> HeapSize { (&**self).heap_size() }
+    }
+
+    /// Wrapper to provide cursor to nested scope.
+    pub struct ArcBatchCursor<K, V, T, R, B: BatchReader<K, V, T, R>> {
+        phantom: ::std::marker::PhantomData<(K, V, T, R)>,
+        cursor: B::Cursor,
+    }
+
+    impl<K, V, T, R, B: BatchReader<K, V, T, R>> ArcBatchCursor<K, V, T, R, B> {
+        fn new(cursor: B::Cursor) -> Self {
+            ArcBatchCursor {
+                cursor,
+                phantom: ::std::marker::PhantomData,
+            }
+        }
+    }
+
+    impl<K, V, T, R, B: BatchReader<K, V, T, R>> Cursor<K, V, T, R> for ArcBatchCursor<K, V, T, R, B> {
+
+        type Storage = Arc<B>;
+
+        #[inline] fn key_valid(&self, storage: &Self::Storage) -> bool { self.cursor.key_valid(storage) }
+        #[inline] fn val_valid(&self, storage: &Self::Storage) -> bool { self.cursor.val_valid(storage) }
+
+        #[inline] fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { self.cursor.key(storage) }
+        #[inline] fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V { self.cursor.val(storage) }
+
+        #[inline]
+        fn map_times<L: FnMut(&T, &R)>(&mut self, storage: &Self::Storage, logic: L) {
+            self.cursor.map_times(storage, logic)
+        }
+
+        #[inline] fn step_key(&mut self, storage: &Self::Storage) { self.cursor.step_key(storage) }
+        #[inline] fn seek_key(&mut self, storage: &Self::Storage, key: &K) { self.cursor.seek_key(storage, key) }
+
+        #[inline] fn step_val(&mut self, storage: &Self::Storage) { self.cursor.step_val(storage) }
+        #[inline] fn seek_val(&mut self, storage: &Self::Storage, val: &V) { self.cursor.seek_val(storage, val) }
+
+        #[inline] fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind_keys(storage) }
+        #[inline] fn rewind_vals(&mut self, storage: &Self::Storage) { self.cursor.rewind_vals(storage) }
+    }
+
+    /// An immutable collection of updates.
+    impl<K,V,T,R,B: Batch<K,V,T,R>> Batch<K, V, T, R> for Arc<B> {
+        type Batcher = ArcBatcher<K, V, T, R, B>;
+        type Builder = ArcBuilder<K, V, T, R, B>;
+        type Merger = ArcMerger<K, V, T, R, B>;
+    }
+
+    /// Wrapper type for batching atomically reference counted batches.
+    pub struct ArcBatcher<K,V,T,R,B:Batch<K,V,T,R>> { batcher: B::Batcher }
+
+    /// Functionality for collecting and batching updates.
+    impl<K,V,T,R,B:Batch<K,V,T,R>> Batcher<K, V, T, R, Arc<B>> for ArcBatcher<K,V,T,R,B> {
+        fn new() -> Self { ArcBatcher { batcher: <B::Batcher as Batcher<K,V,T,R,B>>::new() } }
+        fn push_batch(&mut self, batch: &mut Vec<((K, V), T, R)>) { self.batcher.push_batch(batch) }
+        fn seal(&mut self, upper: Antichain<T>) -> Arc<B> { Arc::new(self.batcher.seal(upper)) }
+        fn frontier(&mut self) -> timely::progress::frontier::AntichainRef<T> { self.batcher.frontier() }
+    }
+
+    /// Wrapper type for building atomically reference counted batches.
+    pub struct ArcBuilder<K,V,T,R,B:Batch<K,V,T,R>> { builder: B::Builder }
+
+    /// Functionality for building batches from ordered update sequences.
+    impl<K,V,T,R,B:Batch<K,V,T,R>> Builder<K, V, T, R, Arc<B>> for ArcBuilder<K,V,T,R,B> {
+        fn new() -> Self { ArcBuilder { builder: <B::Builder as Builder<K,V,T,R,B>>::new() } }
+        fn with_capacity(cap: usize) -> Self { ArcBuilder { builder: <B::Builder as Builder<K,V,T,R,B>>::with_capacity(cap) } }
+        fn push(&mut self, element: (K, V, T, R)) { self.builder.push(element) }
+        fn done(self, lower: Antichain<T>, upper: Antichain<T>, since: Antichain<T>) -> Arc<B> { Arc::new(self.builder.done(lower, upper, since)) }
+    }
+
+    /// Wrapper type for merging atomically reference counted batches.
+    pub struct ArcMerger<K,V,T,R,B:Batch<K,V,T,R>> { merger: B::Merger }
+
+    /// Represents a merge in progress.
+    impl<K,V,T,R,B:Batch<K,V,T,R>> Merger<K, V, T, R, Arc<B>> for ArcMerger<K,V,T,R,B> {
+        fn new(source1: &Arc<B>, source2: &Arc<B>, compaction_frontier: Option<AntichainRef<T>>) -> Self { ArcMerger { merger: B::begin_merge(source1, source2, compaction_frontier) } }
+        fn work(&mut self, source1: &Arc<B>, source2: &Arc<B>, fuel: &mut isize) { self.merger.work(source1, source2, fuel) }
+        fn done(self) -> Arc<B> { Arc::new(self.merger.done()) }
+    }
+}
+
+
 /// Blanket implementations for reference counted batches.
 pub mod abomonated_blanket_impls {
 
@@ -436,7 +580,7 @@ pub mod abomonated_blanket_impls {
     use abomonation::abomonated::Abomonated;
     use timely::progress::{Antichain, frontier::AntichainRef};
 
-    use super::{Batch, BatchReader, Batcher, Builder, Merger, Cursor, Description};
+    use super::{Batch, BatchReader, Batcher, Builder, Merger, Cursor, Description, HeapSize};
 
     impl<K, V, T, R, B: BatchReader<K,V,T,R>+Abomonation> BatchReader<K,V,T,R> for Abomonated<B, Vec<u8>> {
 
@@ -451,6 +595,11 @@ pub mod abomonated_blanket_impls {
         fn len(&self) -> usize { (&**self).len() }
         /// Describes the times of the updates in the batch.
         fn description(&self) -> &Description<T> { (&**self).description() }
+        /// Reports the heap memory held by the batch, which is its serialized length.
+        fn heap_size(&self) -> HeapSize {
+            let bytes = ::abomonation::measure(&**self);
+            HeapSize::new(bytes, bytes)
+        }
     }
 
     /// Wrapper to provide cursor to nested scope.
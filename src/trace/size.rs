@@ -0,0 +1,49 @@
+//! Accounting for the memory held by batches and traces.
+//!
+//! Sizes are measured in bytes of heap memory backing the containers of a batch: its keys, values,
+//! times and differences, and the offsets relating them. The heap allocations owned by individual
+//! keys, values, times, or differences (for example, the contents of a `String`) are not counted,
+//! as there is no general way to determine them.
+
+use std::ops::{Add, AddAssign};
+
+/// Heap memory attributed to a batch or trace, in bytes.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct HeapSize {
+    /// Bytes allocated, including any unused capacity.
+    pub allocated: usize,
+    /// Bytes occupied by present data.
+    pub used: usize,
+}
+
+impl HeapSize {
+    /// Creates a size from allocated and used byte counts.
+    pub fn new(allocated: usize, used: usize) -> Self {
+        HeapSize { allocated, used }
+    }
+    /// The memory backing the elements of `vec`.
+    pub fn of_vec<T>(vec: &Vec<T>) -> Self {
+        let size = ::std::mem::size_of::<T>();
+        HeapSize {
+            allocated: vec.capacity() * size,
+            used: vec.len() * size,
+        }
+    }
+}
+
+impl Add for HeapSize {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        HeapSize {
+            allocated: self.allocated + other.allocated,
+            used: self.used + other.used,
+        }
+    }
+}
+
+impl AddAssign for HeapSize {
+    fn add_assign(&mut self, other: Self) {
+        self.allocated += other.allocated;
+        self.used += other.used;
+    }
+}
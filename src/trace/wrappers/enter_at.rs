@@ -5,7 +5,7 @@ use timely::progress::Timestamp;
 use timely::progress::{Antichain, frontier::AntichainRef};
 
 use lattice::Lattice;
-use trace::{TraceReader, BatchReader, Description};
+use trace::{TraceReader, BatchReader, Description, HeapSize};
 use trace::cursor::Cursor;
 
 /// Wrapper to provide trace to nested scope.
@@ -163,6 +163,7 @@ where
     }
     fn len(&self) -> usize { self.batch.len() }
     fn description(&self) -> &Description<TInner> { &self.description }
+    fn heap_size(&self) -> HeapSize { self.batch.heap_size() }
 }
 
 impl<K, V, T, R, B, TInner, F> BatchEnter<K, V, T, R, B, TInner, F>
@@ -3,7 +3,7 @@
 use timely::progress::Timestamp;
 use timely::progress::frontier::AntichainRef;
 
-use trace::{TraceReader, BatchReader, Description};
+use trace::{TraceReader, BatchReader, Description, HeapSize};
 use trace::cursor::Cursor;
 
 /// Wrapper to provide trace to nested scope.
@@ -105,6 +105,7 @@ where
     }
     fn len(&self) -> usize { self.batch.len() }
     fn description(&self) -> &Description<T> { &self.batch.description() }
+    fn heap_size(&self) -> HeapSize { self.batch.heap_size() }
 }
 
 impl<K, V, T, R, B, F> BatchFilter<K, V, T, R, B, F>
@@ -25,7 +25,7 @@ use timely::progress::frontier::AntichainRef;
 
 use operators::arrange::Arranged;
 use lattice::Lattice;
-use trace::{TraceReader, BatchReader, Description};
+use trace::{TraceReader, BatchReader, Description, HeapSize};
 use trace::cursor::Cursor;
 
 /// Freezes updates to an arrangement using a supplied function.
@@ -166,6 +166,7 @@ where
     }
     fn len(&self) -> usize { self.batch.len() }
     fn description(&self) -> &Description<T> { self.batch.description() }
+    fn heap_size(&self) -> HeapSize { self.batch.heap_size() }
 }
 
 impl<K, V, T, R, B, F> BatchFreeze<K, V, T, R, B, F>
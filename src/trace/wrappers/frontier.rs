@@ -7,7 +7,7 @@
 use timely::progress::Timestamp;
 use timely::progress::{Antichain, frontier::AntichainRef};
 
-use trace::{TraceReader, BatchReader, Description};
+use trace::{TraceReader, BatchReader, Description, HeapSize};
 use trace::cursor::Cursor;
 use crate::lattice::Lattice;
 
@@ -111,6 +111,7 @@ where
     }
     fn len(&self) -> usize { self.batch.len() }
     fn description(&self) -> &Description<T> { &self.batch.description() }
+    fn heap_size(&self) -> HeapSize { self.batch.heap_size() }
 }
 
 impl<K, V, T, R, B> BatchFrontier<K, V, T, R, B>
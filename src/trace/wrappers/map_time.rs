@@ -0,0 +1,320 @@
+//! Wrappers to present a trace with a different timestamp type.
+//!
+//! Each update is presented with its time mapped through a user-supplied function, which should
+//! be monotone: if `t1` is less or equal to `t2`, then `logic(t1)` should be less or equal to
+//! `logic(t2)`. The bounds of batches are mapped by the same function.
+//!
+//! As frontiers are expressed in the new timestamp type, they must be translated back to the wrapped
+//! trace by a second function, `prior`. For each time `t`, `prior(t)` must be the least time whose
+//! image under `logic` is greater or equal to `t`. For totally ordered times, an update at `s` then
+//! precedes `prior(t)` exactly when `logic(s)` precedes `t`, which is what `cursor_through` needs to
+//! select the updates its mapped `upper` frontier describes. When `logic` is not injective, as when
+//! it rounds durations down to whole seconds, `prior(t)` is the least of the times mapped to `t`.
+//!
+//! Compaction frontiers are translated by `prior` as well, which is only correct for times `t` that
+//! are themselves images under `logic`: compacting the wrapped trace to `prior(t)` must not merge
+//! times whose images are distinguished at `t`. Frontiers that contain other times, which arise only
+//! when `logic` is not surjective, leave the wrapped trace's compaction frontiers where they were.
+//!
+//! Because `prior` need not invert `logic`, the wrapper reports the compaction frontiers it was most
+//! recently asked for, rather than the images of the wrapped trace's frontiers under `logic`.
+
+use timely::order::PartialOrder;
+use timely::progress::Timestamp;
+use timely::progress::{Antichain, frontier::AntichainRef};
+
+use lattice::Lattice;
+use trace::{TraceReader, BatchReader, Description, HeapSize};
+use trace::cursor::Cursor;
+
+/// Wrapper to present a trace with mapped timestamps.
+pub struct TraceMapTime<Tr, T2, F, P>
+where
+    Tr: TraceReader,
+{
+    trace: Tr,
+    stash1: Antichain<Tr::Time>,
+    logical_compaction: Antichain<T2>,
+    physical_compaction: Antichain<T2>,
+    logic: F,
+    prior: P,
+}
+
+impl<Tr, T2, F, P> Clone for TraceMapTime<Tr, T2, F, P>
+where
+    Tr: TraceReader+Clone,
+    T2: Clone,
+    F: Clone,
+    P: Clone,
+{
+    fn clone(&self) -> Self {
+        TraceMapTime {
+            trace: self.trace.clone(),
+            stash1: Antichain::new(),
+            logical_compaction: self.logical_compaction.clone(),
+            physical_compaction: self.physical_compaction.clone(),
+            logic: self.logic.clone(),
+            prior: self.prior.clone(),
+        }
+    }
+}
+
+impl<Tr, T2, F, P> TraceReader for TraceMapTime<Tr, T2, F, P>
+where
+    Tr: TraceReader,
+    Tr::Batch: Clone,
+    Tr::Key: 'static,
+    Tr::Val: 'static,
+    Tr::Time: Timestamp,
+    Tr::R: 'static,
+    T2: Timestamp+Lattice,
+    F: Fn(&Tr::Time)->T2+Clone+'static,
+    P: Fn(&T2)->Tr::Time+Clone+'static,
+{
+    type Key = Tr::Key;
+    type Val = Tr::Val;
+    type Time = T2;
+    type R = Tr::R;
+
+    type Batch = BatchMapTime<Tr::Key, Tr::Val, Tr::Time, Tr::R, Tr::Batch, T2, F>;
+    type Cursor = CursorMapTime<Tr::Key, Tr::Val, Tr::Time, Tr::R, Tr::Cursor, T2, F>;
+
+    fn map_batches<F2: FnMut(&Self::Batch)>(&self, mut f: F2) {
+        let logic = self.logic.clone();
+        self.trace.map_batches(|batch| {
+            f(&Self::Batch::make_from(batch.clone(), logic.clone()));
+        })
+    }
+
+    fn set_logical_compaction(&mut self, frontier: AntichainRef<T2>) {
+        if self.stash_prior_images(frontier) {
+            self.trace.set_logical_compaction(self.stash1.borrow());
+        }
+        self.logical_compaction = frontier.to_owned();
+    }
+    fn get_logical_compaction(&mut self) -> AntichainRef<T2> { self.logical_compaction.borrow() }
+
+    fn set_physical_compaction(&mut self, frontier: AntichainRef<T2>) {
+        if self.stash_prior_images(frontier) {
+            self.trace.set_physical_compaction(self.stash1.borrow());
+        }
+        self.physical_compaction = frontier.to_owned();
+    }
+    fn get_physical_compaction(&mut self) -> AntichainRef<T2> { self.physical_compaction.borrow() }
+
+    fn cursor_through(&mut self, upper: AntichainRef<T2>) -> Option<(Self::Cursor, <Self::Cursor as Cursor<Tr::Key, Tr::Val, T2, Tr::R>>::Storage)> {
+        // Updates at times before `prior(t)` are exactly those whose images are before `t`.
+        self.stash1.clear();
+        for time in upper.iter() {
+            self.stash1.insert((self.prior)(time));
+        }
+        self.trace.cursor_through(self.stash1.borrow()).map(|(x,y)| (CursorMapTime::new(x, self.logic.clone()), y))
+    }
+}
+
+impl<Tr, T2, F, P> TraceMapTime<Tr, T2, F, P>
+where
+    Tr: TraceReader,
+    Tr::Time: Timestamp,
+    T2: Timestamp,
+    F: Fn(&Tr::Time)->T2,
+{
+    /// Stashes the times `prior` maps `frontier` to, and indicates whether `logic` maps them back.
+    ///
+    /// Only in that case may the wrapped trace compact to the stashed frontier.
+    fn stash_prior_images(&mut self, frontier: AntichainRef<T2>) -> bool
+    where
+        P: Fn(&T2)->Tr::Time,
+    {
+        self.stash1.clear();
+        let mut exact = true;
+        for time in frontier.iter() {
+            let prior = (self.prior)(time);
+            exact &= (self.logic)(&prior).less_equal(time);
+            self.stash1.insert(prior);
+        }
+        exact
+    }
+
+    /// Makes a new trace wrapper
+    ///
+    /// The initial compaction frontiers are the images of those of `trace` under `logic`.
+    pub fn make_from(mut trace: Tr, logic: F, prior: P) -> Self {
+        let mut logical_compaction = Antichain::new();
+        for time in trace.get_logical_compaction().iter() {
+            logical_compaction.insert(logic(time));
+        }
+        let mut physical_compaction = Antichain::new();
+        for time in trace.get_physical_compaction().iter() {
+            physical_compaction.insert(logic(time));
+        }
+        TraceMapTime {
+            trace,
+            stash1: Antichain::new(),
+            logical_compaction,
+            physical_compaction,
+            logic,
+            prior,
+        }
+    }
+}
+
+
+/// Wrapper to present a batch with mapped timestamps.
+pub struct BatchMapTime<K, V, T, R, B, T2, F> {
+    phantom: ::std::marker::PhantomData<(K, V, T, R)>,
+    batch: B,
+    description: Description<T2>,
+    logic: F,
+}
+
+impl<K, V, T, R, B: Clone, T2: Clone, F: Clone> Clone for BatchMapTime<K, V, T, R, B, T2, F> {
+    fn clone(&self) -> Self {
+        BatchMapTime {
+            phantom: ::std::marker::PhantomData,
+            batch: self.batch.clone(),
+            description: self.description.clone(),
+            logic: self.logic.clone(),
+        }
+    }
+}
+
+impl<K, V, T, R, B, T2, F> BatchReader<K, V, T2, R> for BatchMapTime<K, V, T, R, B, T2, F>
+where
+    B: BatchReader<K, V, T, R>,
+    T: Timestamp,
+    T2: Timestamp+Lattice,
+    F: Fn(&T)->T2+Clone,
+{
+    type Cursor = BatchCursorMapTime<K, V, T, R, B, T2, F>;
+
+    fn cursor(&self) -> Self::Cursor {
+        BatchCursorMapTime::new(self.batch.cursor(), self.logic.clone())
+    }
+    fn len(&self) -> usize { self.batch.len() }
+    fn description(&self) -> &Description<T2> { &self.description }
+    fn heap_size(&self) -> HeapSize { self.batch.heap_size() }
+}
+
+impl<K, V, T, R, B, T2, F> BatchMapTime<K, V, T, R, B, T2, F>
+where
+    B: BatchReader<K, V, T, R>,
+    T: Timestamp,
+    T2: Timestamp+Lattice,
+    F: Fn(&T)->T2,
+{
+    /// Makes a new batch wrapper
+    pub fn make_from(batch: B, logic: F) -> Self {
+        let lower: Vec<_> = batch.description().lower().elements().iter().map(|x| logic(x)).collect();
+        let upper: Vec<_> = batch.description().upper().elements().iter().map(|x| logic(x)).collect();
+        let since: Vec<_> = batch.description().since().elements().iter().map(|x| logic(x)).collect();
+
+        BatchMapTime {
+            phantom: ::std::marker::PhantomData,
+            batch,
+            description: Description::new(Antichain::from(lower), Antichain::from(upper), Antichain::from(since)),
+            logic,
+        }
+    }
+}
+
+/// Wrapper to present a cursor with mapped timestamps.
+pub struct CursorMapTime<K, V, T, R, C: Cursor<K, V, T, R>, T2, F> {
+    phantom: ::std::marker::PhantomData<(K, V, T, R, T2)>,
+    cursor: C,
+    logic: F,
+}
+
+impl<K, V, T, R, C: Cursor<K, V, T, R>, T2, F> CursorMapTime<K, V, T, R, C, T2, F> {
+    fn new(cursor: C, logic: F) -> Self {
+        CursorMapTime {
+            phantom: ::std::marker::PhantomData,
+            cursor,
+            logic,
+        }
+    }
+}
+
+impl<K, V, T, R, C, T2, F> Cursor<K, V, T2, R> for CursorMapTime<K, V, T, R, C, T2, F>
+where
+    C: Cursor<K, V, T, R>,
+    T: Timestamp,
+    T2: Timestamp+Lattice,
+    F: Fn(&T)->T2,
+{
+    type Storage = C::Storage;
+
+    #[inline] fn key_valid(&self, storage: &Self::Storage) -> bool { self.cursor.key_valid(storage) }
+    #[inline] fn val_valid(&self, storage: &Self::Storage) -> bool { self.cursor.val_valid(storage) }
+
+    #[inline] fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { self.cursor.key(storage) }
+    #[inline] fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V { self.cursor.val(storage) }
+
+    #[inline]
+    fn map_times<L: FnMut(&T2, &R)>(&mut self, storage: &Self::Storage, mut logic: L) {
+        let logic2 = &self.logic;
+        self.cursor.map_times(storage, |time, diff| {
+            logic(&logic2(time), diff)
+        })
+    }
+
+    #[inline] fn step_key(&mut self, storage: &Self::Storage) { self.cursor.step_key(storage) }
+    #[inline] fn seek_key(&mut self, storage: &Self::Storage, key: &K) { self.cursor.seek_key(storage, key) }
+
+    #[inline] fn step_val(&mut self, storage: &Self::Storage) { self.cursor.step_val(storage) }
+    #[inline] fn seek_val(&mut self, storage: &Self::Storage, val: &V) { self.cursor.seek_val(storage, val) }
+
+    #[inline] fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind_keys(storage) }
+    #[inline] fn rewind_vals(&mut self, storage: &Self::Storage) { self.cursor.rewind_vals(storage) }
+}
+
+
+
+/// Wrapper to present a batch cursor with mapped timestamps.
+pub struct BatchCursorMapTime<K, V, T, R, B: BatchReader<K, V, T, R>, T2, F> {
+    phantom: ::std::marker::PhantomData<(K, V, R, T2)>,
+    cursor: B::Cursor,
+    logic: F,
+}
+
+impl<K, V, T, R, B: BatchReader<K, V, T, R>, T2, F> BatchCursorMapTime<K, V, T, R, B, T2, F> {
+    fn new(cursor: B::Cursor, logic: F) -> Self {
+        BatchCursorMapTime {
+            phantom: ::std::marker::PhantomData,
+            cursor,
+            logic,
+        }
+    }
+}
+
+impl<K, V, T, R, T2, B: BatchReader<K, V, T, R>, F> Cursor<K, V, T2, R> for BatchCursorMapTime<K, V, T, R, B, T2, F>
+where
+    T: Timestamp,
+    T2: Timestamp+Lattice,
+    F: Fn(&T)->T2,
+{
+    type Storage = BatchMapTime<K, V, T, R, B, T2, F>;
+
+    #[inline] fn key_valid(&self, storage: &Self::Storage) -> bool { self.cursor.key_valid(&storage.batch) }
+    #[inline] fn val_valid(&self, storage: &Self::Storage) -> bool { self.cursor.val_valid(&storage.batch) }
+
+    #[inline] fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { self.cursor.key(&storage.batch) }
+    #[inline] fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V { self.cursor.val(&storage.batch) }
+
+    #[inline]
+    fn map_times<L: FnMut(&T2, &R)>(&mut self, storage: &Self::Storage, mut logic: L) {
+        let logic2 = &self.logic;
+        self.cursor.map_times(&storage.batch, |time, diff| {
+            logic(&logic2(time), diff)
+        })
+    }
+
+    #[inline] fn step_key(&mut self, storage: &Self::Storage) { self.cursor.step_key(&storage.batch) }
+    #[inline] fn seek_key(&mut self, storage: &Self::Storage, key: &K) { self.cursor.seek_key(&storage.batch, key) }
+
+    #[inline] fn step_val(&mut self, storage: &Self::Storage) { self.cursor.step_val(&storage.batch) }
+    #[inline] fn seek_val(&mut self, storage: &Self::Storage, val: &V) { self.cursor.seek_val(&storage.batch, val) }
+
+    #[inline] fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind_keys(&storage.batch) }
+    #[inline] fn rewind_vals(&mut self, storage: &Self::Storage) { self.cursor.rewind_vals(&storage.batch) }
+}
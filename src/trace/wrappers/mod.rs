@@ -3,6 +3,7 @@
 pub mod enter;
 pub mod enter_at;
 pub mod frontier;
+pub mod map_time;
 pub mod rc;
 
 pub mod filter;
@@ -13,12 +13,18 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
 
 use timely::progress::{Antichain, frontier::{AntichainRef, MutableAntichain}};
 
+use timely::order::PartialOrder;
+
 use lattice::Lattice;
 use trace::TraceReader;
 use trace::cursor::Cursor;
+use trace::introspection::{HolderIntrospection, LayerIntrospection, TraceIntrospection};
+use operators::arrange::budget::BudgetAccount;
 
 /// A wrapper around a trace which tracks the frontiers of all referees.
 ///
@@ -36,6 +42,55 @@ where
     pub physical_compaction: MutableAntichain<Tr::Time>,
     /// The wrapped trace.
     pub trace: Tr,
+    /// Labels and frontiers of registered handles, by identifier.
+    pub holders: BTreeMap<usize, HolderIntrospection<Tr::Time>>,
+    /// Identifier for the next registered handle.
+    next_holder: usize,
+    /// Detects logical compaction that fails to keep pace with the trace, if enabled.
+    pub lag_monitor: Option<LagMonitor<Tr::Time>>,
+    /// The memory budget the trace reports its size to, if any.
+    budget: Option<BudgetAccount>,
+}
+
+/// A report of logical compaction that has not kept pace with a trace.
+pub struct LagReport<'a, T> {
+    /// How long the logical compaction frontier has remained unchanged.
+    pub duration: Duration,
+    /// The joint logical compaction frontier.
+    pub frontier: AntichainRef<'a, T>,
+    /// The upper frontier of the trace.
+    pub upper: AntichainRef<'a, T>,
+    /// The holders whose logical compaction frontiers pin the joint frontier.
+    pub holders: Vec<&'a HolderIntrospection<T>>,
+}
+
+/// Tracks how long the logical compaction frontier of a shared trace has lagged its upper frontier.
+///
+/// The frontier is considered to lag if it is not in advance of the upper frontier of the trace, and it
+/// is considered stuck if it has lagged without change for at least the threshold duration. Each stuck
+/// frontier is reported once, and reporting resumes when the frontier changes. The monitor is consulted
+/// both as batches are added to the trace and as the compaction frontiers of its handles change.
+pub struct LagMonitor<T> {
+    threshold: Duration,
+    frontier: Antichain<T>,
+    upper: Option<Antichain<T>>,
+    since: Instant,
+    reported: bool,
+    report: Box<dyn FnMut(LagReport<T>)>,
+}
+
+impl<T> LagMonitor<T> {
+    /// Creates a monitor that invokes `report` when the frontier is stuck for `threshold`.
+    pub fn new(threshold: Duration, report: Box<dyn FnMut(LagReport<T>)>) -> Self {
+        LagMonitor {
+            threshold,
+            frontier: Antichain::new(),
+            upper: None,
+            since: Instant::now(),
+            reported: false,
+            report,
+        }
+    }
 }
 
 impl<Tr> TraceBox<Tr>
@@ -58,6 +113,82 @@ where
             logical_compaction,
             physical_compaction,
             trace: trace,
+            holders: BTreeMap::new(),
+            next_holder: 0,
+            lag_monitor: None,
+            budget: None,
+        }
+    }
+    /// Accounts the memory of the trace against `account`, replacing any previous account.
+    pub(crate) fn set_budget(&mut self, account: BudgetAccount) {
+        self.budget = Some(account);
+        self.report_memory();
+    }
+    /// Reports the current memory of the trace to its budget, if any.
+    pub fn report_memory(&mut self) {
+        if let Some(account) = &self.budget {
+            account.report(self.trace.heap_size().used);
+        }
+    }
+    /// Determines whether logical compaction has lagged `upper` for too long, and reports if so.
+    pub fn check_compaction_lag(&mut self, upper: AntichainRef<Tr::Time>) {
+        if let Some(monitor) = &mut self.lag_monitor {
+            monitor.upper = Some(upper.to_owned());
+            let frontier = self.logical_compaction.frontier();
+            if upper.is_empty() || PartialOrder::less_equal(&upper, &frontier) || monitor.frontier.borrow() != frontier {
+                // The frontier is either not lagging, or has moved since we last looked.
+                monitor.frontier = frontier.to_owned();
+                monitor.since = Instant::now();
+                monitor.reported = false;
+            }
+            else if !monitor.reported && monitor.since.elapsed() >= monitor.threshold {
+                monitor.reported = true;
+                let holders =
+                self.holders
+                    .values()
+                    .filter(|h| PartialOrder::less_equal(&h.logical_compaction.borrow(), &frontier))
+                    .collect();
+                (monitor.report)(LagReport {
+                    duration: monitor.since.elapsed(),
+                    frontier,
+                    upper,
+                    holders,
+                });
+            }
+        }
+    }
+    /// Registers a handle with its label and frontiers, for introspection.
+    ///
+    /// Registration is independent of the frontiers maintained with `adjust_*_compaction`.
+    pub fn register_holder(&mut self, label: String, logical: AntichainRef<Tr::Time>, physical: AntichainRef<Tr::Time>) -> usize {
+        let id = self.next_holder;
+        self.next_holder += 1;
+        self.holders.insert(id, HolderIntrospection {
+            id,
+            label,
+            logical_compaction: logical.to_owned(),
+            physical_compaction: physical.to_owned(),
+        });
+        id
+    }
+    /// Updates the frontiers of a registered handle.
+    pub fn update_holder(&mut self, id: usize, logical: AntichainRef<Tr::Time>, physical: AntichainRef<Tr::Time>) {
+        if let Some(holder) = self.holders.get_mut(&id) {
+            holder.logical_compaction = logical.to_owned();
+            holder.physical_compaction = physical.to_owned();
+        }
+    }
+    /// Removes a registered handle.
+    pub fn remove_holder(&mut self, id: usize) {
+        self.holders.remove(&id);
+    }
+    /// Describes the holders, frontiers, and layers of the trace.
+    pub fn introspect(&self) -> TraceIntrospection<Tr::Time> {
+        TraceIntrospection {
+            holders: self.holders.values().cloned().collect(),
+            logical_compaction: self.logical_compaction.frontier().to_owned(),
+            physical_compaction: self.physical_compaction.frontier().to_owned(),
+            layers: self.trace.layers(),
         }
     }
     /// Replaces elements of `lower` with those of `upper`.
@@ -65,12 +196,18 @@ where
         self.logical_compaction.update_iter(upper.iter().cloned().map(|t| (t,1)));
         self.logical_compaction.update_iter(lower.iter().cloned().map(|t| (t,-1)));
         self.trace.set_logical_compaction(self.logical_compaction.frontier());
+        // Holders may advance while others pin the joint frontier, without any new batches arriving.
+        if let Some(upper) = self.lag_monitor.as_ref().and_then(|m| m.upper.clone()) {
+            self.check_compaction_lag(upper.borrow());
+        }
+        self.report_memory();
     }
     /// Replaces elements of `lower` with those of `upper`.
     pub fn adjust_physical_compaction(&mut self, lower: AntichainRef<Tr::Time>, upper: AntichainRef<Tr::Time>) {
         self.physical_compaction.update_iter(upper.iter().cloned().map(|t| (t,1)));
         self.physical_compaction.update_iter(lower.iter().cloned().map(|t| (t,-1)));
         self.trace.set_physical_compaction(self.physical_compaction.frontier());
+        self.report_memory();
     }
 }
 
@@ -127,6 +264,10 @@ where
     fn map_batches<F: FnMut(&Self::Batch)>(&self, f: F) {
         ::std::cell::RefCell::borrow(&self.wrapper).trace.map_batches(f)
     }
+
+    fn layers(&self) -> Vec<LayerIntrospection<Tr::Time>> {
+        ::std::cell::RefCell::borrow(&self.wrapper).trace.layers()
+    }
 }
 
 impl<Tr> TraceRc<Tr>
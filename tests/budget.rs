@@ -0,0 +1,49 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use timely::dataflow::operators::Probe;
+
+use differential_dataflow::input::InputSession;
+use differential_dataflow::operators::arrange::{Arrange, MemoryBudget};
+use differential_dataflow::trace::implementations::ord::OrdValSpine;
+
+#[test]
+fn budget_pressure_and_recovery() {
+    timely::execute_directly(move |worker| {
+
+        let budget = MemoryBudget::new(1 << 10);
+
+        let mut input = InputSession::new();
+        input.set_budget(budget.clone());
+
+        let index = worker.next_dataflow_index();
+        let (trace, probe) = worker.dataflow::<usize,_,_>(|scope| {
+            let arranged =
+            input.to_collection(scope)
+                 .map(|x: u64| (x, x))
+                 .arrange_named_with_budget::<OrdValSpine<u64, u64, usize, isize>>("Budgeted", &budget);
+            (arranged.trace, arranged.stream.probe())
+        });
+
+        // Nothing has been arranged, and so the input may advance.
+        assert_eq!(budget.used(), 0);
+        for x in 0 .. 1000 { input.insert(x); }
+        assert!(input.try_advance_to(1));
+        input.flush();
+        while probe.less_than(input.time()) { worker.step(); }
+
+        // The arrangement now exceeds the budget, and the input is held back.
+        assert!(budget.used() > budget.limit());
+        assert!(budget.under_pressure());
+        for _ in 0 .. 10 { worker.step(); }
+        assert!(!input.try_advance_to(2));
+        assert_eq!(input.time(), &1);
+
+        // Retiring the arrangement releases its share of the budget.
+        drop(trace);
+        worker.drop_dataflow(index);
+        assert_eq!(budget.used(), 0);
+        assert!(input.try_advance_to(2));
+        assert_eq!(input.time(), &2);
+    });
+}
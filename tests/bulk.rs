@@ -0,0 +1,72 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use timely::Config;
+use timely::dataflow::operators::Probe;
+use timely::progress::frontier::AntichainRef;
+
+use differential_dataflow::input::BulkSession;
+use differential_dataflow::trace::TraceReader;
+use differential_dataflow::trace::cursor::CursorDebug;
+use differential_dataflow::trace::implementations::ord::OrdValSpine;
+
+#[test]
+fn bulk_load_unsorted() {
+    timely::execute(Config::thread(), |worker| {
+
+        let mut session = BulkSession::<OrdValSpine<u64, u64, usize, i64>>::new();
+        let (probe, mut trace) = worker.dataflow(|scope| {
+            let arranged = session.to_arrangement(scope);
+            (arranged.stream.probe(), arranged.trace)
+        });
+
+        let mut data = vec![((2, 0), 0, 1), ((1, 1), 0, 1), ((2, 0), 0, 1), ((3, 3), 2, 1), ((1, 1), 1, -1)];
+        session.load(&mut data);
+        assert!(data.is_empty());
+        session.advance_to(2);
+        session.flush();
+        while probe.less_than(session.time()) { worker.step(); }
+
+        let (mut cursor, storage) = trace.cursor_through(AntichainRef::new(&[2])).unwrap();
+        assert_eq!(cursor.to_vec(&storage), vec![
+            ((1, 1), vec![(0, 1), (1, -1)]),
+            ((2, 0), vec![(0, 2)]),
+        ]);
+
+        session.update((4, 4), 1);
+        session.advance_to(3);
+        session.flush();
+        while probe.less_than(session.time()) { worker.step(); }
+
+        let (mut cursor, storage) = trace.cursor_through(AntichainRef::new(&[3])).unwrap();
+        assert_eq!(cursor.to_vec(&storage), vec![
+            ((1, 1), vec![(0, 1), (1, -1)]),
+            ((2, 0), vec![(0, 2)]),
+            ((3, 3), vec![(2, 1)]),
+            ((4, 4), vec![(2, 1)]),
+        ]);
+
+    }).unwrap();
+}
+
+#[test]
+fn bulk_load_sorted() {
+    timely::execute(Config::thread(), |worker| {
+
+        let mut session = BulkSession::<OrdValSpine<u64, u64, usize, i64>>::new();
+        let (probe, mut trace) = worker.dataflow(|scope| {
+            let arranged = session.to_arrangement(scope);
+            (arranged.stream.probe(), arranged.trace)
+        });
+
+        session.load_sorted((0 .. 100).map(|x| ((x, x), 0, 1)).collect());
+        session.advance_to(1);
+        session.flush();
+        while probe.less_than(session.time()) { worker.step(); }
+
+        let mut count = 0;
+        trace.map_batches(|batch| count += differential_dataflow::trace::BatchReader::len(batch));
+        assert_eq!(count, 100);
+
+    }).unwrap();
+}
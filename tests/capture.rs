@@ -0,0 +1,235 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use timely::dataflow::operators::Probe;
+use timely::progress::Antichain;
+use timely::scheduling::SyncActivator;
+
+use differential_dataflow::input::InputSession;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::capture::{Message, Writer, arrangement};
+use differential_dataflow::trace::{Cursor, TraceReader};
+use differential_dataflow::trace::implementations::ord::OrdValSpine;
+
+type Msg = Message<(u64, u64), usize, isize>;
+
+// An in-memory queue standing in for storage shared by two clusters.
+#[derive(Default)]
+struct Channel {
+    messages: VecDeque<Msg>,
+    activator: Option<SyncActivator>,
+}
+
+struct ChannelWriter(Rc<RefCell<Channel>>);
+
+impl Writer<Msg> for ChannelWriter {
+    fn poll(&mut self, item: &Msg) -> Option<Duration> {
+        let mut channel = self.0.borrow_mut();
+        channel.messages.push_back(item.clone());
+        if let Some(ref activator) = channel.activator {
+            activator.activate().unwrap();
+        }
+        None
+    }
+    fn done(&self) -> bool { true }
+}
+
+struct ChannelReader(Rc<RefCell<Channel>>);
+
+impl Iterator for ChannelReader {
+    type Item = Msg;
+    fn next(&mut self) -> Option<Msg> {
+        self.0.borrow_mut().messages.pop_front()
+    }
+}
+
+#[test]
+fn export_import() {
+
+    let guards = timely::execute(timely::Config::thread(), |worker| {
+
+        let channel = Rc::new(RefCell::new(Channel::default()));
+        let writer = Rc::new(RefCell::new(ChannelWriter(channel.clone())));
+
+        let mut input = InputSession::<usize, (u64, u64), isize>::new();
+        let mut trace = worker.dataflow(|scope| {
+            input.to_collection(scope).arrange_by_key().trace
+        });
+
+        // The export retains its own handle to the trace, and we can release ours.
+        let since = worker.dataflow(|scope| {
+            arrangement::export(scope, &mut trace, 0, Rc::downgrade(&writer), Rc::downgrade(&writer))
+        });
+        drop(trace);
+
+        // Import in a separate dataflow, as another cluster would.
+        let (_token, mut imported, probe) = worker.dataflow(|scope| {
+            let (token, arranged) = arrangement::import::<_,_,_,OrdValSpine<u64, u64, usize, isize>>(scope, "Imported", since.borrow(), |activator| {
+                channel.borrow_mut().activator = Some(activator);
+                ChannelReader(channel.clone())
+            });
+            let probe = arranged.stream.probe();
+            (token, arranged.trace, probe)
+        });
+
+        // Each key's value is replaced in each round.
+        for round in 0 .. 5usize {
+            for key in 0 .. 10u64 {
+                input.insert((key, round as u64));
+                if round > 0 {
+                    input.remove((key, round as u64 - 1));
+                }
+            }
+            input.advance_to(round + 1);
+            input.flush();
+            worker.step_while(|| probe.less_than(input.time()));
+        }
+        input.close();
+
+        while !probe.done() { worker.step(); }
+
+        let (mut cursor, storage) = imported.cursor();
+        let mut contents = Vec::new();
+        while let Some(key) = cursor.get_key(&storage) {
+            while let Some(val) = cursor.get_val(&storage) {
+                let mut sum = 0;
+                cursor.map_times(&storage, |_, diff| sum += diff);
+                if sum != 0 { contents.push((*key, *val, sum)); }
+                cursor.step_val(&storage);
+            }
+            cursor.step_key(&storage);
+        }
+        contents
+    }).unwrap();
+
+    for result in guards.join() {
+        assert_eq!(result.unwrap(), (0 .. 10).map(|key| (key, 4, 1)).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn export_import_empty_first_round() {
+
+    let guards = timely::execute(timely::Config::thread(), |worker| {
+
+        let channel = Rc::new(RefCell::new(Channel::default()));
+        let writer = Rc::new(RefCell::new(ChannelWriter(channel.clone())));
+
+        let mut input = InputSession::<usize, (u64, u64), isize>::new();
+        let mut trace = worker.dataflow(|scope| {
+            input.to_collection(scope).arrange_by_key().trace
+        });
+
+        let since = worker.dataflow(|scope| {
+            arrangement::export(scope, &mut trace, 0, Rc::downgrade(&writer), Rc::downgrade(&writer))
+        });
+        drop(trace);
+
+        let (_token, mut imported, probe) = worker.dataflow(|scope| {
+            let (token, arranged) = arrangement::import::<_,_,_,OrdValSpine<u64, u64, usize, isize>>(scope, "Imported", since.borrow(), |activator| {
+                channel.borrow_mut().activator = Some(activator);
+                ChannelReader(channel.clone())
+            });
+            let probe = arranged.stream.probe();
+            (token, arranged.trace, probe)
+        });
+
+        // The frontier advances before any updates arrive.
+        input.advance_to(1);
+        input.flush();
+        worker.step_while(|| probe.less_than(input.time()));
+
+        input.insert((0, 1));
+        input.advance_to(2);
+        input.flush();
+        worker.step_while(|| probe.less_than(input.time()));
+        input.close();
+
+        while !probe.done() { worker.step(); }
+
+        let (mut cursor, storage) = imported.cursor();
+        let mut contents = Vec::new();
+        while let Some(key) = cursor.get_key(&storage) {
+            while let Some(val) = cursor.get_val(&storage) {
+                let mut sum = 0;
+                cursor.map_times(&storage, |_, diff| sum += diff);
+                if sum != 0 { contents.push((*key, *val, sum)); }
+                cursor.step_val(&storage);
+            }
+            cursor.step_key(&storage);
+        }
+        contents
+    }).unwrap();
+
+    for result in guards.join() {
+        assert_eq!(result.unwrap(), vec![(0, 1, 1)]);
+    }
+}
+
+#[test]
+fn export_import_compacted() {
+
+    let guards = timely::execute(timely::Config::thread(), |worker| {
+
+        let channel = Rc::new(RefCell::new(Channel::default()));
+        let writer = Rc::new(RefCell::new(ChannelWriter(channel.clone())));
+
+        let mut input = InputSession::<usize, (u64, u64), isize>::new();
+        let (mut trace, probe) = worker.dataflow(|scope| {
+            let arranged = input.to_collection(scope).arrange_by_key();
+            (arranged.trace, arranged.stream.probe())
+        });
+
+        for round in 0 .. 3usize {
+            input.insert((round as u64, round as u64));
+            input.advance_to(round + 1);
+            input.flush();
+            worker.step_while(|| probe.less_than(input.time()));
+        }
+
+        // The exporting trace no longer distinguishes times before three.
+        trace.set_logical_compaction(Antichain::from_elem(3).borrow());
+        let since = worker.dataflow(|scope| {
+            arrangement::export(scope, &mut trace, 0, Rc::downgrade(&writer), Rc::downgrade(&writer))
+        });
+        drop(trace);
+        assert_eq!(since, Antichain::from_elem(3));
+
+        let (_token, mut imported, probe) = worker.dataflow(|scope| {
+            let (token, arranged) = arrangement::import::<_,_,_,OrdValSpine<u64, u64, usize, isize>>(scope, "Imported", since.borrow(), |activator| {
+                channel.borrow_mut().activator = Some(activator);
+                ChannelReader(channel.clone())
+            });
+            let probe = arranged.stream.probe();
+            (token, arranged.trace, probe)
+        });
+        input.close();
+
+        while !probe.done() { worker.step(); }
+
+        // The imported trace starts from the exporter's compaction.
+        assert_eq!(imported.get_logical_compaction().to_owned(), Antichain::from_elem(3));
+
+        let (mut cursor, storage) = imported.cursor();
+        let mut contents = Vec::new();
+        while let Some(key) = cursor.get_key(&storage) {
+            while let Some(val) = cursor.get_val(&storage) {
+                let mut sum = 0;
+                cursor.map_times(&storage, |_, diff| sum += diff);
+                if sum != 0 { contents.push((*key, *val, sum)); }
+                cursor.step_val(&storage);
+            }
+            cursor.step_key(&storage);
+        }
+        contents
+    }).unwrap();
+
+    for result in guards.join() {
+        assert_eq!(result.unwrap(), vec![(0, 0, 1), (1, 1, 1), (2, 2, 1)]);
+    }
+}
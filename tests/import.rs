@@ -247,3 +247,255 @@ fn import_skewed() {
         (4, vec![((0, 1), 1)]),
     ]);
 }
+
+#[test]
+fn introspect_holders() {
+
+    timely::execute(timely::Config::thread(), move |worker| {
+
+        let mut input = InputSession::<usize, u64, isize>::new();
+        let mut trace = worker.dataflow(|scope| {
+            input.to_collection(scope).arrange_by_self().trace
+        });
+
+        input.insert(0);
+        input.advance_to(1);
+        input.flush();
+        worker.step_while(|| trace.introspect().layers.is_empty());
+
+        // Holders are the returned handle and the arrangement's own handle.
+        let holders = trace.introspect().holders.len();
+
+        let mut clone = trace.clone();
+        clone.set_label("forgotten clone");
+        trace.set_logical_compaction(AntichainRef::new(&[1]));
+
+        let introspection = trace.introspect();
+        assert_eq!(introspection.holders.len(), holders + 1);
+        let forgotten = introspection.holders.iter().find(|h| h.label == "forgotten clone").unwrap();
+        assert_eq!(forgotten.logical_compaction.elements(), &[0]);
+        // The clone holds back the joint compaction frontier.
+        assert_eq!(introspection.logical_compaction.elements(), &[0]);
+
+        drop(clone);
+        assert_eq!(trace.introspect().holders.len(), holders);
+
+    }).unwrap();
+}
+
+#[test]
+fn log_holder_changes() {
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use differential_dataflow::logging::DifferentialEvent;
+
+    timely::execute(timely::Config::thread(), move |worker| {
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let events2 = events.clone();
+        worker.log_register().insert::<DifferentialEvent,_>("differential/arrange", move |_time, data| {
+            for (_, _, event) in data.drain(..) {
+                if let DifferentialEvent::Holder(holder) = event {
+                    events2.borrow_mut().push((holder.label, holder.diff));
+                }
+            }
+        });
+
+        let mut input = InputSession::<usize, u64, isize>::new();
+        let mut trace = worker.dataflow(|scope| {
+            input.to_collection(scope).arrange_by_self().trace
+        });
+
+        // Frontier changes are not holder changes.
+        input.advance_to(1);
+        input.flush();
+        trace.set_logical_compaction(AntichainRef::new(&[1]));
+        worker.step();
+        let registered = events.borrow().len();
+        assert!(events.borrow().iter().all(|(_, diff)| *diff == 1));
+
+        let mut clone = trace.clone();
+        clone.set_label("relabeled");
+        drop(clone);
+        worker.step();
+
+        let events = events.borrow();
+        let label = events[registered].0.clone();
+        assert_eq!(&events[registered ..], &[
+            (label.clone(), 1),
+            (label, -1),
+            ("relabeled".to_string(), 1),
+            ("relabeled".to_string(), -1),
+        ]);
+
+    }).unwrap();
+}
+
+#[test]
+fn report_compaction_lag() {
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use differential_dataflow::logging::DifferentialEvent;
+
+    timely::execute(timely::Config::thread(), move |worker| {
+
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let reports2 = reports.clone();
+        worker.log_register().insert::<DifferentialEvent,_>("differential/arrange", move |_time, data| {
+            for (_, _, event) in data.drain(..) {
+                if let DifferentialEvent::CompactionLag(lag) = event {
+                    reports2.borrow_mut().push(lag);
+                }
+            }
+        });
+
+        let mut input = InputSession::<usize, u64, isize>::new();
+        let (mut trace, probe) = worker.dataflow(|scope| {
+            let arranged = input.to_collection(scope).arrange_by_self();
+            (arranged.trace, arranged.stream.probe())
+        });
+        trace.monitor_compaction_lag(std::time::Duration::from_millis(0));
+
+        let mut forgotten = trace.clone();
+        forgotten.set_label("forgotten");
+
+        for round in 1 .. 5 {
+            input.insert(round as u64);
+            input.advance_to(round);
+            input.flush();
+            trace.set_logical_compaction(AntichainRef::new(&[round]));
+            worker.step_while(|| probe.less_than(input.time()));
+        }
+        worker.step();
+
+        let reports = reports.borrow();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].holders.iter().any(|(_, label)| label == "forgotten"));
+
+    }).unwrap();
+}
+
+#[test]
+fn import_map_time() {
+
+    let captured = timely::execute(timely::Config::thread(), move |worker| {
+
+        let mut input = InputSession::<u64, u64, isize>::new();
+        let mut trace = worker.dataflow(|scope| {
+            input.to_collection(scope).arrange_by_self().trace
+        });
+
+        // Times in the importing dataflow are ten times those of the trace.
+        let (probe, captured) = worker.dataflow::<u64,_,_>(|scope| {
+            let stream = trace.import_map_time(scope, |t: &u64| 10 * t, |t: &u64| (t + 9) / 10)
+                              .as_collection(|k, _| *k)
+                              .inner;
+            (stream.probe(), stream.capture())
+        });
+
+        for round in 1 .. 4u64 {
+            input.insert(round);
+            input.advance_to(round);
+            input.flush();
+            worker.step_while(|| probe.less_than(&(10 * round)));
+        }
+
+        captured
+    }).unwrap().join().into_iter().map(|x| x.unwrap()).next().unwrap();
+
+    let mut results = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    results.sort();
+    assert_eq!(results, vec![(1, 0, 1), (2, 10, 1), (3, 20, 1)]);
+}
+
+#[test]
+fn map_time_rounding_down() {
+
+    use std::time::Duration;
+    use timely::progress::Antichain;
+    use differential_dataflow::trace::Cursor;
+    use differential_dataflow::trace::wrappers::map_time::TraceMapTime;
+
+    timely::execute(timely::Config::thread(), move |worker| {
+
+        let mut input = InputSession::<Duration, u64, isize>::new();
+        let (trace, probe) = worker.dataflow(|scope| {
+            let arranged = input.to_collection(scope).arrange_by_self();
+            (arranged.trace, arranged.stream.probe())
+        });
+
+        // Batches end at whole seconds, so that whole seconds never straddle them.
+        let rounds = vec![vec![(500, 0)], vec![(1500, 1), (1700, 2)], vec![(2000, 3)]];
+        for (secs, updates) in rounds.into_iter().enumerate() {
+            for (millis, key) in updates {
+                input.update_at(key, Duration::from_millis(millis), 1);
+            }
+            input.advance_to(Duration::from_secs(secs as u64 + 1));
+            input.flush();
+            worker.step_while(|| probe.less_than(input.time()));
+        }
+
+        // Whole seconds, rounded down; the least duration of `secs` seconds is `secs` seconds.
+        let mut mapped = TraceMapTime::make_from(trace, |time: &Duration| time.as_secs(), |secs: &u64| Duration::from_secs(*secs));
+
+        for &(upper, ref expected) in &[(1, vec![(0, 0)]), (2, vec![(0, 0), (1, 1), (2, 1)])] {
+            let (mut cursor, storage) = mapped.cursor_through(Antichain::from_elem(upper).borrow()).expect("batches straddle upper");
+            let mut results = Vec::new();
+            while cursor.key_valid(&storage) {
+                let key = *cursor.key(&storage);
+                cursor.map_times(&storage, |time, diff| { assert_eq!(*diff, 1); results.push((key, *time)); });
+                cursor.step_key(&storage);
+            }
+            assert_eq!(&results, expected);
+        }
+
+    }).unwrap();
+}
+
+#[test]
+fn report_compaction_lag_without_updates() {
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use differential_dataflow::logging::DifferentialEvent;
+
+    timely::execute(timely::Config::thread(), move |worker| {
+
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let reports2 = reports.clone();
+        worker.log_register().insert::<DifferentialEvent,_>("differential/arrange", move |_time, data| {
+            for (_, _, event) in data.drain(..) {
+                if let DifferentialEvent::CompactionLag(lag) = event {
+                    reports2.borrow_mut().push(lag);
+                }
+            }
+        });
+
+        let mut input = InputSession::<usize, u64, isize>::new();
+        let (mut trace, probe) = worker.dataflow(|scope| {
+            let arranged = input.to_collection(scope).arrange_by_self();
+            (arranged.trace, arranged.stream.probe())
+        });
+        trace.monitor_compaction_lag(std::time::Duration::from_millis(50));
+
+        let mut forgotten = trace.clone();
+        forgotten.set_label("forgotten");
+
+        input.insert(0);
+        input.advance_to(1);
+        input.flush();
+        worker.step_while(|| probe.less_than(input.time()));
+
+        // No further batches arrive, but advancing one handle re-examines the pinned frontier.
+        std::thread::sleep(std::time::Duration::from_millis(60));
+        trace.set_logical_compaction(AntichainRef::new(&[1]));
+        worker.step();
+
+        let reports = reports.borrow();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].holders.iter().any(|(_, label)| label == "forgotten"));
+
+    }).unwrap();
+}
@@ -0,0 +1,85 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use timely::dataflow::operators::Probe;
+
+use differential_dataflow::input::InputSession;
+use differential_dataflow::operators::{Join, CountTotal, ThresholdTotal};
+use differential_dataflow::logging::DifferentialEvent;
+
+/// Runs a computation, and returns the name of the operator, the input, and the output of each activation.
+fn run(instrument: bool) -> Vec<(String, usize, usize)> {
+
+    let mut config = timely::Config::thread();
+    differential_dataflow::instrument_operators(&mut config.worker, instrument);
+
+    let guards = timely::execute(config, move |worker| {
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let events2 = events.clone();
+        let mut names = std::collections::HashMap::new();
+        worker.log_register().insert::<DifferentialEvent,_>("differential/arrange", move |_time, data| {
+            for (_, _, event) in data.drain(..) {
+                match event {
+                    DifferentialEvent::OperatorName(event) => { names.insert(event.operator, event.name); },
+                    DifferentialEvent::Activation(event) => {
+                        let name = names.get(&event.operator).cloned().expect("activation of an unnamed operator");
+                        events2.borrow_mut().push((name, event.input, event.output));
+                    },
+                    _ => { },
+                }
+            }
+        });
+
+        let mut input = InputSession::new();
+        let probe = worker.dataflow(|scope| {
+            let pairs = input.to_collection(scope).map(|x: u64| (x % 5, x));
+            pairs.join(&pairs)
+                 .map(|(key, _)| key)
+                 .count_total()
+                 .map(|(key, _count)| key)
+                 .distinct_total()
+                 .probe()
+        });
+
+        for round in 0 .. 10u64 {
+            input.insert(round);
+            input.advance_to(round + 1);
+            input.flush();
+            worker.step_while(|| probe.less_than(input.time()));
+        }
+        input.close();
+        while worker.step() { }
+
+        let events = events.borrow().clone();
+        events
+    }).unwrap();
+
+    guards.join().into_iter().flat_map(|result| result.unwrap()).collect()
+}
+
+#[test]
+fn activations_logged() {
+
+    let events = run(true);
+
+    for name in &["Arrange", "Join", "CountTotal", "ThresholdTotal"] {
+        assert!(events.iter().any(|event| event.0 == **name), "no activations of {}", name);
+    }
+
+    // Each of the ten inputs is received by the join's arrangement.
+    let arranged: usize = events.iter().filter(|e| e.0 == "Arrange").map(|e| e.1).sum();
+    assert!(arranged >= 10);
+
+    // Each of the five keys has two values, and so four joined pairs.
+    let joined: usize = events.iter().filter(|e| e.0 == "Join").map(|e| e.2).sum();
+    assert_eq!(joined, 20);
+}
+
+#[test]
+fn activations_not_logged_by_default() {
+    assert!(run(false).is_empty());
+}
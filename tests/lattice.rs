@@ -0,0 +1,90 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use timely::PartialOrder;
+use timely::progress::Antichain;
+
+use differential_dataflow::lattice::{Lattice, MultiProduct, antichain_join, antichain_meet};
+
+#[test]
+fn tuple_product_order() {
+    let time1 = MultiProduct::new((1, 2, 3));
+    let time2 = MultiProduct::new((1, 3, 3));
+    let time3 = MultiProduct::new((2, 1, 3));
+    assert!(time1.less_equal(&time2));
+    assert!(!time2.less_equal(&time1));
+    assert!(!time1.less_equal(&time3));
+    assert!(!time3.less_equal(&time1));
+    assert_eq!(time1.join(&time3), MultiProduct::new((2, 2, 3)));
+    assert_eq!(time1.meet(&time3), MultiProduct::new((1, 1, 3)));
+}
+
+#[test]
+fn tuple_six_coordinates() {
+    let time1 = MultiProduct::new((0u64, 5u32, 2u8, 9usize, 1i64, 4u16));
+    let time2 = MultiProduct::new((3u64, 1u32, 2u8, 7usize, 1i64, 8u16));
+    assert_eq!(time1.join(&time2), MultiProduct::new((3, 5, 2, 9, 1, 8)));
+    assert_eq!(time1.meet(&time2), MultiProduct::new((0, 1, 2, 7, 1, 4)));
+}
+
+#[test]
+fn array_product_order() {
+    let time1 = MultiProduct::new([1u64, 2, 3, 4]);
+    let time2 = MultiProduct::new([4u64, 3, 2, 1]);
+    assert!(!time1.less_equal(&time2));
+    assert!(time1.less_equal(&time1.join(&time2)));
+    assert!(time2.less_equal(&time1.join(&time2)));
+    assert_eq!(time1.join(&time2), MultiProduct::new([4, 3, 3, 4]));
+    assert_eq!(time1.meet(&time2), MultiProduct::new([1, 2, 2, 1]));
+}
+
+#[test]
+fn advance_by_tuple() {
+    let time = MultiProduct::new((3, 7, 0));
+    let mut advanced = time.clone();
+    let frontier = Antichain::from(vec![MultiProduct::new((4, 8, 1)), MultiProduct::new((5, 3, 1))]);
+    advanced.advance_by(frontier.borrow());
+    assert_eq!(advanced, MultiProduct::new((4, 7, 1)));
+    for i in 0 .. 8 {
+        for j in 0 .. 10 {
+            for k in 0 .. 3 {
+                let test = MultiProduct::new((i, j, k));
+                if frontier.less_equal(&test) {
+                    assert_eq!(time.less_equal(&test), advanced.less_equal(&test));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn antichain_join_tuple() {
+    let f1 = &[MultiProduct::new((3, 7, 0)), MultiProduct::new((5, 6, 0))];
+    let f2 = &[MultiProduct::new((4, 6, 1))];
+    let join = antichain_join(f1, f2);
+    assert_eq!(&*join.elements(), &[MultiProduct::new((4, 7, 1)), MultiProduct::new((5, 6, 1))]);
+}
+
+#[test]
+fn antichain_meet_tuple() {
+    let f1 = &[MultiProduct::new((3, 7, 0)), MultiProduct::new((5, 6, 0))];
+    let f2 = &[MultiProduct::new((4, 6, 0))];
+    let meet = antichain_meet(f1, f2);
+    assert_eq!(&*meet.elements(), &[MultiProduct::new((3, 7, 0)), MultiProduct::new((4, 6, 0))]);
+}
+
+#[test]
+fn antichain_join_array() {
+    let f1 = &[MultiProduct::new([3, 7, 0, 0]), MultiProduct::new([5, 6, 0, 0])];
+    let f2 = &[MultiProduct::new([4, 6, 0, 2])];
+    let join = antichain_join(f1, f2);
+    assert_eq!(&*join.elements(), &[MultiProduct::new([4, 7, 0, 2]), MultiProduct::new([5, 6, 0, 2])]);
+}
+
+#[test]
+fn antichain_meet_array() {
+    let f1 = &[MultiProduct::new([3, 7, 0, 0]), MultiProduct::new([5, 6, 0, 0])];
+    let f2 = &[MultiProduct::new([4, 6, 0, 0]), MultiProduct::new([3, 7, 0, 1])];
+    let meet = antichain_meet(f1, f2);
+    assert_eq!(&*meet.elements(), &[MultiProduct::new([3, 7, 0, 0]), MultiProduct::new([4, 6, 0, 0])]);
+}
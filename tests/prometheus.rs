@@ -0,0 +1,78 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use timely::dataflow::operators::Probe;
+
+use differential_dataflow::input::InputSession;
+use differential_dataflow::operators::arrange::ArrangeBySelf;
+use differential_dataflow::logging::prometheus::PrometheusMetrics;
+
+fn scrape(address: std::net::SocketAddr) -> String {
+    let mut stream = TcpStream::connect(address).expect("failed to connect");
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+/// Sums the values of a metric across all label sets.
+fn total(body: &str, name: &str) -> f64 {
+    body.lines()
+        .filter(|line| line.starts_with(&format!("{}{{", name)))
+        .map(|line| line.rsplit(' ').next().unwrap().parse::<f64>().unwrap())
+        .sum()
+}
+
+#[test]
+fn scrape_metrics() {
+
+    let metrics = PrometheusMetrics::new();
+    let address = metrics.serve("127.0.0.1:0").expect("failed to bind");
+
+    let worker_metrics = metrics.clone();
+    timely::execute(timely::Config::thread(), move |worker| {
+
+        worker_metrics.enable(worker);
+
+        let mut input = InputSession::new();
+        let (probe, trace) = worker.dataflow(|scope| {
+            let arranged = input.to_collection(scope).arrange_by_self();
+            (arranged.stream.probe(), arranged.trace)
+        });
+
+        for round in 1 .. 20u64 {
+            input.insert(round);
+            input.advance_to(round);
+            input.flush();
+            worker.step_while(|| probe.less_than(input.time()));
+        }
+
+        drop(trace);
+
+    }).unwrap();
+
+    let response = scrape(address);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    let body = &response[response.find("\r\n\r\n").unwrap() + 4 ..];
+
+    assert!(body.contains("# TYPE differential_batches_total counter"));
+    assert!(body.contains("# TYPE differential_records gauge"));
+    assert!(total(body, "differential_batches_total") >= 19.0);
+    assert_eq!(total(body, "differential_batch_records_total"), 19.0);
+    assert!(total(body, "differential_merges_total") > 0.0);
+}
+
+#[test]
+fn scrape_beside_idle_client() {
+
+    let metrics = PrometheusMetrics::new();
+    let address = metrics.serve("127.0.0.1:0").expect("failed to bind");
+
+    // A client that connects but never sends a request must not hold up others.
+    let _idle = TcpStream::connect(address).expect("failed to connect");
+    let response = scrape(address);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+}
@@ -0,0 +1,59 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use timely::Config;
+use timely::dataflow::Scope;
+use timely::dataflow::operators::Probe;
+use timely::order::Product;
+
+use differential_dataflow::input::{Scenario, ScenarioSession};
+use differential_dataflow::operators::Count;
+use differential_dataflow::operators::arrange::ArrangeBySelf;
+
+#[test]
+fn scenario_branches() {
+    timely::execute(Config::thread(), |worker| {
+
+        let mut session = ScenarioSession::<usize, u32, isize>::new();
+        let (mut trace, probe) = worker.dataflow(|scope| {
+            let data = session.to_collection(scope);
+            scope.iterative::<Scenario,_,_>(|child| {
+                let arranged =
+                ScenarioSession::enter(&data, child)
+                    .map(|x| x % 2)
+                    .count()
+                    .arrange_by_self();
+                (arranged.trace, arranged.stream.probe())
+            })
+        });
+
+        session.insert(1);
+        session.insert(2);
+        let branch1 = session.fork();
+        let branch2 = session.fork();
+        session.update_scenario(branch1, 3, 1);
+        session.update_scenario(branch2, 2, -1);
+        session.advance_to(1);
+        session.flush();
+        while probe.less_than(&Product::new(*session.time(), Scenario::base())) { worker.step(); }
+
+        // Each branch observes the base collection and only its own changes.
+        assert_eq!(session.read(&mut trace, Scenario::base(), 0), vec![((0, 1), (), 1), ((1, 1), (), 1)]);
+        assert_eq!(session.read(&mut trace, branch1, 0), vec![((0, 1), (), 1), ((1, 2), (), 1)]);
+        assert_eq!(session.read(&mut trace, branch2, 0), vec![((1, 1), (), 1)]);
+
+        // Changes to the base collection are visible in every branch.
+        session.insert(5);
+        session.advance_to(2);
+        session.flush();
+        while probe.less_than(&Product::new(*session.time(), Scenario::base())) { worker.step(); }
+
+        assert_eq!(session.read(&mut trace, Scenario::base(), 1), vec![((0, 1), (), 1), ((1, 2), (), 1)]);
+        assert_eq!(session.read(&mut trace, branch1, 1), vec![((0, 1), (), 1), ((1, 3), (), 1)]);
+        assert_eq!(session.read(&mut trace, branch2, 1), vec![((1, 2), (), 1)]);
+
+        // Earlier times remain readable in each branch.
+        assert_eq!(session.read(&mut trace, branch1, 0), vec![((0, 1), (), 1), ((1, 2), (), 1)]);
+
+    }).unwrap();
+}
@@ -0,0 +1,109 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use std::sync::{Arc, Mutex};
+
+use timely::dataflow::channels::pact::Exchange;
+use timely::dataflow::operators::Probe;
+use timely::progress::Antichain;
+
+use differential_dataflow::input::InputSession;
+use differential_dataflow::operators::arrange::Arrange;
+use differential_dataflow::operators::arrange::shared::{self, SharedTrace};
+use differential_dataflow::trace::{Batch, Builder, Cursor, TraceReader};
+use differential_dataflow::trace::implementations::ord::{OrdValBatch, OrdValSpineArc};
+
+#[test]
+fn shared_across_workers() {
+
+    let (writer, reference) = SharedTrace::<u64, u64, usize, isize, OrdValBatch<u64, u64, usize, isize>>::new();
+    let writer = Mutex::new(Some(writer));
+
+    let guards = timely::execute(timely::Config::process(3), move |worker| {
+
+        let index = worker.index();
+        let peers = worker.peers();
+        let writer = if index == 0 { writer.lock().unwrap().take() } else { None };
+
+        let mut input = InputSession::new();
+        worker.dataflow::<usize,_,_>(|scope| {
+            let arranged = input.to_collection(scope)
+                                .arrange_core::<_,OrdValSpineArc<u64,u64,usize,isize>>(Exchange::new(|_| 0), "Replicate");
+            shared::publish(&arranged, writer);
+        });
+
+        let mut trace = reference.clone();
+        let probe = worker.dataflow::<usize,_,_>(|scope| {
+            trace.import(scope).stream.probe()
+        });
+
+        // Each worker contributes a share of the table.
+        for round in 1 .. 4 {
+            for key in 0 .. 100u64 {
+                if (key as usize) % peers == index && (key as usize) % 3 == round - 1 {
+                    input.insert((key, key * key));
+                }
+            }
+            input.advance_to(round);
+            input.flush();
+        }
+        input.close();
+
+        while !probe.done() { worker.step(); }
+
+        // Every worker observes the whole table.
+        let (mut cursor, storage) = trace.cursor();
+        let mut count = 0;
+        while cursor.key_valid(&storage) {
+            while cursor.val_valid(&storage) {
+                let mut sum = 0;
+                cursor.map_times(&storage, |_, diff| sum += diff);
+                assert_eq!(*cursor.val(&storage), cursor.key(&storage) * cursor.key(&storage));
+                if sum > 0 { count += 1; }
+                cursor.step_val(&storage);
+            }
+            cursor.step_key(&storage);
+        }
+        count
+    }).unwrap();
+
+    for result in guards.join() {
+        assert_eq!(result.unwrap(), 100);
+    }
+}
+
+#[test]
+fn merge_as_handles_advance() {
+
+    type ValBatch = OrdValBatch<u64, u64, usize, isize>;
+    let (mut writer, mut trace) = SharedTrace::<u64, u64, usize, isize, ValBatch>::new();
+
+    for round in 0 .. 8usize {
+        let mut builder = <ValBatch as Batch<_,_,_,_>>::Builder::new();
+        builder.push((round as u64, round as u64, round, 1));
+        let batch = builder.done(Antichain::from_elem(round), Antichain::from_elem(round + 1), Antichain::from_elem(0));
+        writer.insert(Arc::new(batch), Some(round));
+    }
+
+    // A handle at the minimum frontiers prevents merging.
+    let mut count = 0;
+    trace.map_batches(|_| count += 1);
+    assert_eq!(count, 8);
+
+    // Releasing the history merges the batches, without losing updates.
+    trace.set_logical_compaction(Antichain::from_elem(8).borrow());
+    trace.set_physical_compaction(Antichain::from_elem(8).borrow());
+    let mut count = 0;
+    trace.map_batches(|_| count += 1);
+    assert!(count < 8);
+
+    let (mut cursor, storage) = trace.cursor();
+    let mut keys = Vec::new();
+    while cursor.key_valid(&storage) {
+        let mut sum = 0;
+        cursor.map_times(&storage, |_, diff| sum += diff);
+        keys.push((*cursor.key(&storage), sum));
+        cursor.step_key(&storage);
+    }
+    assert_eq!(keys, (0 .. 8).map(|key| (key, 1)).collect::<Vec<_>>());
+}
@@ -7,7 +7,7 @@ use timely::dataflow::operators::generic::OperatorInfo;
 use timely::progress::{Antichain, frontier::AntichainRef};
 
 use differential_dataflow::trace::implementations::ord::OrdValBatch;
-use differential_dataflow::trace::{Trace, TraceReader, Batch, Batcher};
+use differential_dataflow::trace::{Trace, TraceReader, Batch, BatchReader, Batcher};
 use differential_dataflow::trace::cursor::CursorDebug;
 use differential_dataflow::trace::implementations::spine_fueled::Spine;
 
@@ -63,3 +63,57 @@ fn test_trace() {
     let vec_4 = cursor4.to_vec(&storage4);
     assert_eq!(vec_4, vec_3);
 }
+
+/// Loads the same updates into spines with each merge policy, and compares their contents.
+#[test]
+fn test_merge_policies() {
+
+    use differential_dataflow::trace::implementations::spine_fueled::{MergePolicy, TieredMergePolicy, EagerMergePolicy, BoundedMergePolicy};
+
+    // Each policy, and the number of batches it should never exceed.
+    let policies: Vec<(Box<dyn MergePolicy>, Option<usize>)> = vec![
+        (Box::new(TieredMergePolicy::default()), None),
+        (Box::new(TieredMergePolicy::with_effort(4)), None),
+        (Box::new(EagerMergePolicy), None),
+        (Box::new(BoundedMergePolicy::new(2)), Some(2)),
+        (Box::new(BoundedMergePolicy::new(5)), Some(5)),
+    ];
+
+    let mut results = Vec::new();
+    for (policy, bound) in policies {
+        let op_info = OperatorInfo::new(0, 0, &[]);
+        let mut trace = IntegerTrace::with_policy(policy, op_info, None, None);
+        let mut batcher = <<IntegerTrace as TraceReader>::Batch as Batch<u64, u64, usize, i64>>::Batcher::new();
+        for round in 1 .. 100 {
+            let mut updates = (0 .. round as u64).map(|x| ((x % 7, x), round - 1, if round % 3 == 0 { -1 } else { 1 })).collect();
+            batcher.push_batch(&mut updates);
+            trace.insert(batcher.seal(Antichain::from_elem(round)));
+            trace.set_physical_compaction(AntichainRef::new(&[round]));
+            if let Some(bound) = bound {
+                assert!(trace.batches() <= bound, "{} batches exceed the bound of {}", trace.batches(), bound);
+            }
+        }
+        let (mut cursor, storage) = trace.cursor();
+        let mut contents = cursor.to_vec(&storage);
+        for (_, times) in contents.iter_mut() { times.sort(); }
+        results.push((contents, trace.batches()));
+    }
+
+    for (contents, _) in results.iter() {
+        assert_eq!(contents, &results[0].0);
+    }
+}
+
+#[test]
+fn test_heap_size() {
+    use differential_dataflow::trace::HeapSize;
+
+    let trace = get_trace();
+    let size = trace.heap_size();
+    assert!(size.used > 0);
+    assert!(size.used <= size.allocated);
+
+    let mut total = HeapSize::default();
+    trace.map_batches(|batch| total += batch.heap_size());
+    assert_eq!(size, total);
+}
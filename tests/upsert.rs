@@ -0,0 +1,168 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use timely::Config;
+use timely::PartialOrder;
+use timely::dataflow::operators::Probe;
+
+use differential_dataflow::input::UpsertSession;
+use differential_dataflow::lattice::MultiProduct;
+use differential_dataflow::trace::{Cursor, TraceReader};
+
+type Time = MultiProduct<[u64; 2]>;
+
+/// Accumulates the contents of `trace` at `time`.
+fn contents_at<Tr>(trace: &mut Tr, time: &Time) -> Vec<(u64, u64)>
+where
+    Tr: TraceReader<Key=u64, Val=u64, Time=Time, R=isize>,
+{
+    let mut results = Vec::new();
+    let (mut cursor, storage) = trace.cursor();
+    while let Some(key) = cursor.get_key(&storage) {
+        while let Some(val) = cursor.get_val(&storage) {
+            let mut count = 0;
+            cursor.map_times(&storage, |t, d| if t.less_equal(time) { count += *d; });
+            assert!(count == 0 || count == 1);
+            if count == 1 { results.push((*key, *val)); }
+            cursor.step_val(&storage);
+        }
+        cursor.step_key(&storage);
+    }
+    results
+}
+
+#[test]
+fn upsert_total_order() {
+    timely::execute(Config::thread(), |worker| {
+
+        let mut session = UpsertSession::<u64, u64, u64>::new();
+        let (probe, mut trace) = worker.dataflow(|scope| {
+            let arranged = session.to_arrangement(scope);
+            (arranged.stream.probe(), arranged.trace)
+        });
+
+        session.upsert(0, 10);
+        session.upsert(1, 11);
+        session.advance_to(1);
+        session.upsert(0, 20);
+        session.delete(1);
+        session.advance_to(2);
+        session.flush();
+        while probe.less_than(session.time()) { worker.step(); }
+
+        let (mut cursor, storage) = trace.cursor();
+        let mut results = Vec::new();
+        while let Some(key) = cursor.get_key(&storage) {
+            while let Some(val) = cursor.get_val(&storage) {
+                cursor.map_times(&storage, |t, d| results.push((*key, *val, *t, *d)));
+                cursor.step_val(&storage);
+            }
+            cursor.step_key(&storage);
+        }
+        results.sort();
+        assert_eq!(results, vec![(0, 10, 0, 1), (0, 10, 1, -1), (0, 20, 1, 1), (1, 11, 0, 1), (1, 11, 1, -1)]);
+
+    }).unwrap();
+}
+
+#[test]
+fn upsert_ttl() {
+    timely::execute(Config::thread(), |worker| {
+
+        let mut session = UpsertSession::<u64, u64, u64>::new();
+        let (probe, mut trace) = worker.dataflow(|scope| {
+            let arranged = session.to_arrangement_with_ttl(scope, 5);
+            (arranged.stream.probe(), arranged.trace)
+        });
+
+        session.upsert(0, 10);
+        session.upsert(1, 11);
+        session.upsert(2, 12);
+        session.advance_to(3);
+        // Rewriting a key defers its expiry; deleting a key cancels it.
+        session.upsert(1, 21);
+        session.delete(2);
+        session.advance_to(5);
+        session.flush();
+        while probe.less_than(session.time()) { worker.step(); }
+        // An upsert at the expiry time takes precedence over the expiry.
+        session.upsert(0, 30);
+        session.advance_to(20);
+        session.flush();
+        while probe.less_than(session.time()) { worker.step(); }
+
+        let (mut cursor, storage) = trace.cursor();
+        let mut results = Vec::new();
+        while let Some(key) = cursor.get_key(&storage) {
+            while let Some(val) = cursor.get_val(&storage) {
+                cursor.map_times(&storage, |t, d| results.push((*key, *val, *t, *d)));
+                cursor.step_val(&storage);
+            }
+            cursor.step_key(&storage);
+        }
+        results.sort();
+        assert_eq!(results, vec![
+            (0, 10, 0, 1), (0, 10, 5, -1), (0, 30, 5, 1), (0, 30, 10, -1),
+            (1, 11, 0, 1), (1, 11, 3, -1), (1, 21, 3, 1), (1, 21, 8, -1),
+            (2, 12, 0, 1), (2, 12, 3, -1),
+        ]);
+
+    }).unwrap();
+}
+
+#[test]
+fn upsert_partial_order() {
+    timely::execute(Config::thread(), |worker| {
+
+        let mut session = UpsertSession::<u64, u64, Time>::new();
+        let (probe, mut trace) = worker.dataflow(|scope| {
+            let arranged = session.to_arrangement_partial(scope);
+            (arranged.stream.probe(), arranged.trace)
+        });
+
+        session.upsert_at(0, Some(1), MultiProduct::new([0, 0]));
+        session.upsert_at(0, Some(2), MultiProduct::new([1, 0]));
+        session.upsert_at(0, Some(3), MultiProduct::new([0, 1]));
+        session.upsert_at(1, Some(1), MultiProduct::new([0, 0]));
+        session.upsert_at(1, None, MultiProduct::new([0, 2]));
+        session.advance_to(MultiProduct::new([3, 3]));
+        session.flush();
+        while probe.less_than(session.time()) { worker.step(); }
+
+        assert_eq!(contents_at(&mut trace, &MultiProduct::new([0, 0])), vec![(0, 1), (1, 1)]);
+        assert_eq!(contents_at(&mut trace, &MultiProduct::new([1, 0])), vec![(0, 2), (1, 1)]);
+        assert_eq!(contents_at(&mut trace, &MultiProduct::new([0, 1])), vec![(0, 3), (1, 1)]);
+        // Incomparable upserts are ties, resolved in favor of the greater value.
+        assert_eq!(contents_at(&mut trace, &MultiProduct::new([1, 1])), vec![(0, 3), (1, 1)]);
+        assert_eq!(contents_at(&mut trace, &MultiProduct::new([2, 2])), vec![(0, 3)]);
+
+    }).unwrap();
+}
+
+#[test]
+fn upsert_partial_order_incremental() {
+    timely::execute(Config::thread(), |worker| {
+
+        let mut session = UpsertSession::<u64, u64, Time>::new();
+        let (probe, mut trace) = worker.dataflow(|scope| {
+            let arranged = session.to_arrangement_partial(scope);
+            (arranged.stream.probe(), arranged.trace)
+        });
+
+        session.upsert_at(0, Some(5), MultiProduct::new([0, 1]));
+        session.advance_to(MultiProduct::new([1, 0]));
+        session.flush();
+        while probe.less_than(session.time()) { worker.step(); }
+
+        // A later upsert incomparable with the earlier one must tie with it where both apply.
+        session.upsert_at(0, Some(4), MultiProduct::new([1, 0]));
+        session.advance_to(MultiProduct::new([2, 2]));
+        session.flush();
+        while probe.less_than(session.time()) { worker.step(); }
+
+        assert_eq!(contents_at(&mut trace, &MultiProduct::new([0, 1])), vec![(0, 5)]);
+        assert_eq!(contents_at(&mut trace, &MultiProduct::new([1, 0])), vec![(0, 4)]);
+        assert_eq!(contents_at(&mut trace, &MultiProduct::new([1, 1])), vec![(0, 5)]);
+
+    }).unwrap();
+}